@@ -0,0 +1,150 @@
+//! Counts the sprite entity operations drawing a game takes per lock, with
+//! the bot playing and no window.
+//!
+//! "Before" is an estimate, not a measurement: the old redraw is no longer
+//! in the tree, so it is modelled by counting what it did, despawning and
+//! spawning every block sprite when the board changes and every piece sprite
+//! when the active piece moves, loading a texture for each sprite spawned.
+//! "After" is counted from the changes the game draws now, with a sprite kept
+//! for each cell and mino that is only changed when what it shows does.
+//!
+//!     cargo run --release --no-default-features --example entity_ops -- [locks]
+
+use std::collections::HashSet;
+use std::time::Duration;
+
+use rtetris::board::Cell;
+use rtetris::bot::{Bot, Controller};
+use rtetris::engine::GameState;
+use rtetris::mode::GameMode;
+use rtetris::pieces;
+use rtetris::view::{self, Change, View};
+
+const FRAME: Duration = Duration::from_millis(16);
+
+#[derive(Default)]
+struct Count {
+    spawns: usize,
+    despawns: usize,
+    updates: usize,
+    texture_loads: usize,
+}
+
+impl Count {
+    fn total(&self) -> usize {
+        self.spawns + self.despawns + self.updates
+    }
+}
+
+fn blocks(state: &GameState) -> usize {
+    state
+        .board
+        .matrix
+        .iter()
+        .flatten()
+        .filter(|cell| cell.is_filled() && **cell != Cell::Wall)
+        .count()
+}
+
+fn main() {
+    let locks: usize = std::env::args()
+        .nth(1)
+        .and_then(|arg| arg.parse().ok())
+        .unwrap_or(200);
+
+    let mode = GameMode::Marathon { endless: true };
+    let mut state = GameState::new(mode, 1, pieces::default_set());
+    let mut bot = Bot::default();
+
+    // Modelled from what the old redraw did, see the module docs.
+    let mut before = Count::default();
+    let mut after = Count::default();
+
+    // What is on screen once the game has started, which both ways spawn.
+    let mut block_sprites = blocks(&state);
+    let mut piece_sprites = view::minos(&state, true).len();
+    let mut view = View::default();
+    let mut spawned: HashSet<(isize, isize)> = view
+        .update(&state, true)
+        .into_iter()
+        .filter_map(|change| match change {
+            Change::Cell { i, j, .. } => Some((i, j)),
+            Change::Mino { .. } => None,
+        })
+        .collect();
+
+    let mut frames = 0;
+
+    while state.count < locks {
+        let movement = bot.update(FRAME, &state);
+        let step = state.update(FRAME, movement);
+        frames += 1;
+
+        if step.over {
+            println!("The bot topped out after {} locks.", state.count);
+            break;
+        }
+
+        if step.board_changed {
+            let now = blocks(&state);
+
+            before.despawns += block_sprites;
+            before.spawns += now;
+            before.texture_loads += now;
+            block_sprites = now;
+        }
+
+        if step.moved {
+            let now = view::minos(&state, true).len();
+
+            before.despawns += piece_sprites;
+            before.spawns += now;
+            before.texture_loads += now;
+            piece_sprites = now;
+        }
+
+        if !step.board_changed && !step.moved {
+            continue;
+        }
+
+        let slots = view.slots();
+
+        for change in view.update(&state, true) {
+            let new = match change {
+                Change::Cell { i, j, .. } => spawned.insert((i, j)),
+                Change::Mino { slot, .. } => slot >= slots,
+            };
+
+            if new {
+                after.spawns += 1;
+            } else {
+                after.updates += 1;
+            }
+        }
+    }
+
+    let locks = state.count.max(1) as f32;
+    let per_lock = |n: usize| n as f32 / locks;
+
+    println!("{} locks over {} frames", state.count, frames);
+    println!();
+    println!(
+        "{:<16} {:>10} {:>10} {:>10} {:>10} {:>14}",
+        "per lock", "spawns", "despawns", "updates", "total", "texture loads"
+    );
+
+    for (name, count) in [("before (est.)", &before), ("after", &after)] {
+        println!(
+            "{:<16} {:>10.1} {:>10.1} {:>10.1} {:>10.1} {:>14.1}",
+            name,
+            per_lock(count.spawns),
+            per_lock(count.despawns),
+            per_lock(count.updates),
+            per_lock(count.total()),
+            per_lock(count.texture_loads),
+        );
+    }
+
+    println!();
+    println!("before is estimated from a model of the old redraw, not measured");
+}
@@ -0,0 +1,67 @@
+//! Measures how many steps per second the training environment takes, with
+//! an agent placing pieces at random and one pressing random inputs.
+//!
+//!     cargo run --release --no-default-features --example env_steps -- [steps]
+
+use std::time::Instant;
+
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+use rtetris::board::Movement;
+use rtetris::environment::{Action, Environment, Rewards};
+use rtetris::pieces;
+
+const MOVEMENTS: [Movement; 5] = [
+    Movement::Down,
+    Movement::Left,
+    Movement::Right,
+    Movement::RotateLeft,
+    Movement::RotateRight,
+];
+
+/// Takes `steps` steps, starting a new episode whenever one ends, and
+/// returns the steps per second and the episodes played.
+fn run(steps: usize, mut act: impl FnMut(&mut Environment, &mut StdRng) -> Action) -> (f64, usize) {
+    let mut environment = Environment::new(pieces::default_set(), Rewards::default());
+    let mut rng = StdRng::seed_from_u64(0);
+    let mut episodes = 1;
+
+    let started = Instant::now();
+
+    for _ in 0..steps {
+        let action = act(&mut environment, &mut rng);
+        let (_, _, done, _) = environment.step(action);
+
+        if done {
+            environment.reset(episodes as u64);
+            episodes += 1;
+        }
+    }
+
+    (steps as f64 / started.elapsed().as_secs_f64(), episodes)
+}
+
+fn main() {
+    let steps: usize = std::env::args()
+        .nth(1)
+        .and_then(|arg| arg.parse().ok())
+        .unwrap_or(20_000);
+
+    let (places, episodes) = run(steps, |environment, rng| {
+        let count = environment.placements().len().max(1);
+        Action::Place(rng.gen_range(0..count))
+    });
+    println!(
+        "placements: {:>10.0} steps/s over {} episodes",
+        places, episodes
+    );
+
+    let (moves, episodes) = run(steps, |_, rng| {
+        Action::Move(MOVEMENTS[rng.gen_range(0..MOVEMENTS.len())])
+    });
+    println!(
+        "movements:  {:>10.0} steps/s over {} episodes",
+        moves, episodes
+    );
+}
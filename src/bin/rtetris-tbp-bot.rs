@@ -0,0 +1,130 @@
+use std::collections::VecDeque;
+use std::io::{self, BufRead, Write};
+use std::process::ExitCode;
+
+use rtetris::board::Board;
+use rtetris::bot::{self, Hold, Weights};
+use rtetris::pieces;
+use rtetris::tbp::{self, BotMessage, FrontendMessage, Move, PieceLocation, Spin};
+use rtetris::tetromino::Tetromino;
+
+/// A small TBP bot built on the game's own AI, so the protocol can be tried
+/// without an external engine. It never holds.
+fn main() -> ExitCode {
+    let set = pieces::default_set();
+    let weights = Weights::default();
+    let mut stdout = io::stdout().lock();
+
+    let mut board: Option<Board> = None;
+    let mut queue: VecDeque<String> = VecDeque::new();
+
+    let mut send = |message: BotMessage| -> io::Result<()> {
+        writeln!(stdout, "{}", serde_json::to_string(&message)?)?;
+        stdout.flush()
+    };
+
+    let info = BotMessage::Info {
+        name: "rtetris".into(),
+        version: env!("CARGO_PKG_VERSION").into(),
+        author: "rtetris".into(),
+        features: vec![],
+    };
+
+    if send(info).is_err() {
+        return ExitCode::FAILURE;
+    }
+
+    for line in io::stdin().lock().lines() {
+        let Ok(line) = line else {
+            return ExitCode::FAILURE;
+        };
+
+        let Ok(message) = serde_json::from_str::<FrontendMessage>(&line) else {
+            continue;
+        };
+
+        let reply = match message {
+            FrontendMessage::Rules {} => Some(BotMessage::Ready),
+            FrontendMessage::Start {
+                queue: pieces,
+                board: rows,
+                ..
+            } => {
+                board = Some(tbp::board_from_rows(&rows, set));
+                queue = pieces.into();
+                None
+            }
+            FrontendMessage::Stop => {
+                board = None;
+                queue.clear();
+                None
+            }
+            FrontendMessage::Suggest => Some(BotMessage::Suggestion {
+                moves: board
+                    .as_ref()
+                    .and_then(|board| suggest(board, &queue, &weights))
+                    .into_iter()
+                    .collect(),
+            }),
+            FrontendMessage::Play { mv } => {
+                if let Some(board) = board.as_mut() {
+                    play(board, &mv.location);
+                }
+
+                queue.pop_front();
+                None
+            }
+            FrontendMessage::NewPiece { piece } => {
+                queue.push_back(piece);
+                None
+            }
+            FrontendMessage::Quit => return ExitCode::SUCCESS,
+        };
+
+        if let Some(reply) = reply {
+            if send(reply).is_err() {
+                return ExitCode::FAILURE;
+            }
+        }
+    }
+
+    ExitCode::SUCCESS
+}
+
+fn suggest(board: &Board, queue: &VecDeque<String>, weights: &Weights) -> Option<Move> {
+    let set = pieces::default_set();
+    let mut pieces = queue.iter().map(|name| set.get(name));
+
+    let mut board = board.clone();
+    board.tetromino = Tetromino::new(pieces.next()??, 0, 0).at_spawn();
+    let next: Vec<_> = pieces.take(1).flatten().collect();
+
+    let choice = bot::choose(&board, Hold::Unavailable, &next, weights)?;
+    let location = PieceLocation::of(&choice.tetromino, board.matrix.len())?;
+
+    let spin = if choice.t_spin {
+        Spin::Full
+    } else {
+        Spin::None
+    };
+
+    Some(Move { location, spin })
+}
+
+fn play(board: &mut Board, location: &PieceLocation) {
+    let set = pieces::default_set();
+    let Some(piece) = set.get(&location.piece) else {
+        return;
+    };
+    let Some(tetromino) = location
+        .cells(board.matrix.len())
+        .and_then(|cells| Tetromino::placed(piece, &cells))
+    else {
+        return;
+    };
+
+    board.tetromino = tetromino;
+    board.merge();
+    board.check_completed_rows();
+    board.tetromino = Tetromino::null();
+}
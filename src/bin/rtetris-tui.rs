@@ -0,0 +1,32 @@
+use std::io;
+use std::process::ExitCode;
+
+use rtetris::fumen;
+
+const USAGE: &str = "usage:
+    rtetris-tui
+    rtetris-tui fumen decode <fumen>    print the pages of a fumen as text
+    rtetris-tui fumen encode            read text pages from stdin, print a fumen";
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+
+    let result = match args.as_slice() {
+        [] => rtetris::tui::run().map_err(|e| e.to_string()),
+        ["fumen", command @ ..] => match fumen::command(command, &mut io::stdin()) {
+            Some(result) => result
+                .map(|output| print!("{}", output))
+                .map_err(|e| e.to_string()),
+            None => Err(USAGE.into()),
+        },
+        _ => Err(USAGE.into()),
+    };
+
+    if let Err(e) = result {
+        eprintln!("{}", e);
+        return ExitCode::FAILURE;
+    }
+
+    ExitCode::SUCCESS
+}
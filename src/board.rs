@@ -1,10 +1,101 @@
+use std::fmt;
+use std::str::FromStr;
+
+use crate::pieces::{self, Piece, PieceSet};
 use crate::tetromino::Tetromino;
 
-type BoolMatrix = Vec<Vec<bool>>;
+type CellMatrix = Vec<Vec<Cell>>;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Cell {
+    Empty,
+    Wall,
+    Garbage,
+    Piece(&'static Piece),
+}
+
+impl Cell {
+    pub fn is_filled(&self) -> bool {
+        !matches!(self, Cell::Empty)
+    }
+
+    /// The character the cell is written as by `Board::to_str`. Locked
+    /// pieces are written as their name when it is a single character and as
+    /// garbage otherwise.
+    pub fn to_char(&self) -> char {
+        match self {
+            Cell::Empty => '_',
+            Cell::Wall => '#',
+            Cell::Garbage => 'X',
+            Cell::Piece(piece) => {
+                let mut chars = piece.name.chars();
+
+                match (chars.next(), chars.next()) {
+                    (Some(c), None) if !"_#X@".contains(c) => c,
+                    _ => 'X',
+                }
+            }
+        }
+    }
+}
+
+/// Why a text diagram could not be read as a board. Lines and columns count
+/// from 1.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BoardError {
+    TooSmall,
+    Ragged {
+        line: usize,
+        width: usize,
+        expected: usize,
+    },
+    UnknownCell {
+        line: usize,
+        column: usize,
+        char: char,
+    },
+    MissingWall {
+        line: usize,
+        column: usize,
+    },
+    UnknownPiece,
+}
+
+impl fmt::Display for BoardError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BoardError::TooSmall => write!(f, "board needs at least 3 rows and 3 columns"),
+            BoardError::Ragged {
+                line,
+                width,
+                expected,
+            } => write!(
+                f,
+                "line {}: row is {} cells wide, expected {}",
+                line, width, expected
+            ),
+            BoardError::UnknownCell { line, column, char } => write!(
+                f,
+                "line {}, column {}: '{}' is not a cell or piece name",
+                line, column, char
+            ),
+            BoardError::MissingWall { line, column } => write!(
+                f,
+                "line {}, column {}: the board's edge must be wall",
+                line, column
+            ),
+            BoardError::UnknownPiece => {
+                write!(f, "the '@' cells do not form any piece of the set")
+            }
+        }
+    }
+}
+
+impl std::error::Error for BoardError {}
 
 #[derive(Clone)]
 pub struct Board {
-    pub matrix: BoolMatrix,
+    pub matrix: CellMatrix,
     pub tetromino: Tetromino,
 }
 
@@ -21,16 +112,16 @@ impl Board {
     pub fn new(rows: usize, cols: usize) -> Board {
         let range_rows = 0..rows;
 
-        let matrix: BoolMatrix = range_rows
+        let matrix: CellMatrix = range_rows
             .map(|i| {
                 let range_cols = 0..cols;
 
                 range_cols
                     .map(|j| match (i, j) {
-                        (0, _) => true,
-                        (_, 0) => true,
-                        (a, b) if a == rows - 1 || b == cols - 1 => true,
-                        _ => false,
+                        (0, _) => Cell::Wall,
+                        (_, 0) => Cell::Wall,
+                        (a, b) if a == rows - 1 || b == cols - 1 => Cell::Wall,
+                        _ => Cell::Empty,
                     })
                     .collect()
             })
@@ -42,6 +133,45 @@ impl Board {
         }
     }
 
+    /// Builds an empty board and fills its bottom with one garbage row per
+    /// entry in `holes`, the first entry ending up at the very bottom.
+    pub fn with_garbage(rows: usize, cols: usize, holes: &[usize]) -> Board {
+        let mut board = Board::new(rows, cols);
+
+        for &hole in holes.iter().rev() {
+            board.push_garbage(hole);
+        }
+
+        board
+    }
+
+    /// Shifts the stack up one row and inserts a garbage row with a hole at
+    /// column `hole` at the bottom. Returns false if blocks were pushed out of
+    /// the top of the board.
+    pub fn push_garbage(&mut self, hole: usize) -> bool {
+        let floor = self.matrix.len() - 2;
+        let cols = self.matrix[floor].len();
+        let fits = self.matrix[1][1..cols - 1]
+            .iter()
+            .all(|cell| !cell.is_filled());
+
+        for i in 1..floor {
+            for j in 1..cols - 1 {
+                self.matrix[i][j] = self.matrix[i + 1][j];
+            }
+        }
+
+        for j in 1..cols - 1 {
+            self.matrix[floor][j] = if j == hole {
+                Cell::Empty
+            } else {
+                Cell::Garbage
+            };
+        }
+
+        fits
+    }
+
     pub fn apply_movement(&mut self, movement: Movement) -> bool {
         let previous_position = self.tetromino;
 
@@ -56,10 +186,10 @@ impl Board {
                 self.tetromino.move_right();
             }
             Movement::RotateLeft => {
-                self.tetromino.rotate_left();
+                return self.rotate(false);
             }
             Movement::RotateRight => {
-                self.tetromino.rotate_left();
+                return self.rotate(true);
             }
         };
 
@@ -71,51 +201,145 @@ impl Board {
         true
     }
 
-    pub fn overlaps(&self) -> bool {
-        let rotation = self.tetromino.actual_rotation();
-        for i in 0..4 {
-            for j in 0..4 {
-                let (i_board, j_board) = (self.tetromino.i + i, self.tetromino.j + j);
+    /// Rotates the active piece, trying the piece's kicks in order if the
+    /// rotated piece does not fit where it is.
+    fn rotate(&mut self, clockwise: bool) -> bool {
+        let previous_position = self.tetromino;
 
-                if j_board as usize >= self.matrix[i_board as usize].len() {
-                    continue;
-                }
+        if clockwise {
+            self.tetromino.rotate_right();
+        } else {
+            self.tetromino.rotate_left();
+        }
 
-                if rotation[i as usize][j as usize]
-                    && self.matrix[i_board as usize][j_board as usize]
-                {
-                    return true;
-                }
+        if !self.overlaps() {
+            return true;
+        }
+
+        let rotated = self.tetromino;
+        let kicks = match rotated.piece() {
+            Some(piece) => piece.kicks(previous_position.orientation(), clockwise),
+            None => &[],
+        };
+
+        for (i, j) in kicks {
+            self.tetromino.i = rotated.i + i;
+            self.tetromino.j = rotated.j + j;
+
+            if !self.overlaps() {
+                return true;
             }
         }
 
+        self.tetromino = previous_position;
         false
     }
 
-    pub fn merge(&mut self) {
-        let rotation = self.tetromino.actual_rotation();
-        for i in 0..4 {
-            for j in 0..4 {
-                let (i_board, j_board) = (self.tetromino.i + i, self.tetromino.j + j);
+    /// Where the active piece would land if dropped straight down.
+    pub fn ghost(&self) -> Tetromino {
+        let mut probe = self.clone();
 
-                if j_board as usize >= self.matrix[i_board as usize].len() {
-                    continue;
-                }
+        while probe.apply_movement(Movement::Down) {}
+
+        probe.tetromino
+    }
+
+    pub fn overlaps(&self) -> bool {
+        self.tetromino
+            .cells()
+            .into_iter()
+            .any(|(i, j)| self.occupied(i, j))
+    }
+
+    pub fn merge(&mut self) {
+        let Some(piece) = self.tetromino.piece() else {
+            return;
+        };
 
-                self.matrix[i_board as usize][j_board as usize] = rotation[i as usize][j as usize]
-                    || self.matrix[i_board as usize][j_board as usize];
+        for (i, j) in self.tetromino.cells() {
+            if self.in_bounds(i, j) {
+                self.matrix[i as usize][j as usize] = Cell::Piece(piece);
             }
         }
     }
 
+    /// Whether the active piece is a T with at least three of the four
+    /// corners around its centre filled. Whether it got there by rotating is
+    /// left for the caller to track.
+    pub fn is_t_spin(&self) -> bool {
+        if !self.tetromino.piece().is_some_and(|piece| piece.t_spin) {
+            return false;
+        }
+
+        let cells = self.tetromino.cells();
+        let centre = cells.iter().copied().find(|&(i, j)| {
+            [(-1, 0), (1, 0), (0, -1), (0, 1)]
+                .iter()
+                .filter(|(di, dj)| cells.contains(&(i + di, j + dj)))
+                .count()
+                == 3
+        });
+
+        let Some((i, j)) = centre else {
+            return false;
+        };
+
+        let corners = [(-1, -1), (-1, 1), (1, -1), (1, 1)]
+            .iter()
+            .filter(|(di, dj)| self.occupied(i + di, j + dj))
+            .count();
+
+        corners >= 3
+    }
+
+    /// Whether no blocks are left inside the walls.
+    pub fn is_clear(&self) -> bool {
+        let rows = self.matrix.len();
+
+        self.matrix[1..rows - 1].iter().all(|row| {
+            let cols = row.len();
+            row[1..cols - 1].iter().all(|cell| !cell.is_filled())
+        })
+    }
+
+    /// How many rows, counting up from the floor, reach the highest block.
+    pub fn stack_height(&self) -> usize {
+        let rows = self.matrix.len();
+
+        (1..rows - 1)
+            .find(|&i| {
+                let cols = self.matrix[i].len();
+                self.matrix[i][1..cols - 1].iter().any(Cell::is_filled)
+            })
+            .map_or(0, |i| rows - 1 - i)
+    }
+
+    fn in_bounds(&self, i: isize, j: isize) -> bool {
+        i >= 0
+            && j >= 0
+            && (i as usize) < self.matrix.len()
+            && (j as usize) < self.matrix[i as usize].len()
+    }
+
+    /// Whether a cell is filled, counting anything outside the board as filled.
+    fn occupied(&self, i: isize, j: isize) -> bool {
+        !self.in_bounds(i, j) || self.matrix[i as usize][j as usize].is_filled()
+    }
+
+    /// Writes the board as one line per row: `#` for walls, `X` for garbage,
+    /// `_` for empty cells, `@` for the active piece and the piece's name for
+    /// locked pieces. `Board::parse` reads it back.
     pub fn to_str(&self) -> String {
-        let mut copy = self.clone();
-        copy.merge();
+        let active = self.tetromino.cells();
         let mut result = String::new();
 
-        for row in copy.matrix {
-            for column in row {
-                let char = if column { 'X' } else { '_' };
+        for (i, row) in self.matrix.iter().enumerate() {
+            for (j, cell) in row.iter().enumerate() {
+                let char = if active.contains(&(i as isize, j as isize)) {
+                    '@'
+                } else {
+                    cell.to_char()
+                };
                 result.push(char);
             }
             result.push('\n');
@@ -124,6 +348,83 @@ impl Board {
         result
     }
 
+    /// Reads a board written like `to_str` does, looking locked and active
+    /// pieces up in `set`. The edge of the diagram must be wall, written as
+    /// `#` or, as older diagrams did, `X`. Without `@` cells the board has no
+    /// active piece.
+    pub fn parse(text: &str, set: &'static PieceSet) -> Result<Board, BoardError> {
+        let lines: Vec<&str> = text
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+            .collect();
+
+        let rows = lines.len();
+        let cols = lines.first().map_or(0, |line| line.chars().count());
+
+        if rows < 3 || cols < 3 {
+            return Err(BoardError::TooSmall);
+        }
+
+        let mut matrix = vec![];
+        let mut active = vec![];
+
+        for (i, line) in lines.iter().enumerate() {
+            let width = line.chars().count();
+
+            if width != cols {
+                return Err(BoardError::Ragged {
+                    line: i + 1,
+                    width,
+                    expected: cols,
+                });
+            }
+
+            let mut row = vec![];
+
+            for (j, char) in line.chars().enumerate() {
+                let edge = i == 0 || j == 0 || i == rows - 1 || j == cols - 1;
+
+                let cell = match char {
+                    '#' => Cell::Wall,
+                    'X' if edge => Cell::Wall,
+                    'X' => Cell::Garbage,
+                    '_' => Cell::Empty,
+                    '@' => {
+                        active.push((i as isize, j as isize));
+                        Cell::Empty
+                    }
+                    other => set.get(&other.to_string()).map(Cell::Piece).ok_or(
+                        BoardError::UnknownCell {
+                            line: i + 1,
+                            column: j + 1,
+                            char: other,
+                        },
+                    )?,
+                };
+
+                if edge && cell != Cell::Wall {
+                    return Err(BoardError::MissingWall {
+                        line: i + 1,
+                        column: j + 1,
+                    });
+                }
+
+                row.push(cell);
+            }
+
+            matrix.push(row);
+        }
+
+        let tetromino = if active.is_empty() {
+            Tetromino::null()
+        } else {
+            find_piece(&active, set).ok_or(BoardError::UnknownPiece)?
+        };
+
+        Ok(Board { matrix, tetromino })
+    }
+
     pub fn blocks(&self) -> Vec<(isize, isize)> {
         self.matrix
             .iter()
@@ -131,12 +432,18 @@ impl Board {
             .flat_map(|(i, vec)| {
                 vec.iter()
                     .enumerate()
-                    .filter(|(_, val)| **val)
+                    .filter(|(_, cell)| cell.is_filled())
                     .map(move |(j, _)| (i as isize, j as isize))
             })
             .collect::<Vec<_>>()
     }
 
+    pub fn completed_rows(&self) -> Vec<usize> {
+        let len = self.matrix.len() - 2;
+
+        (2..=len).filter(|&i| self.completed_row(i)).collect()
+    }
+
     pub fn check_completed_rows(&mut self) -> usize {
         let len = self.matrix.len() - 2;
         let mut return_val = 0;
@@ -162,12 +469,160 @@ impl Board {
     }
 
     fn completed_row(&self, i: usize) -> bool {
-        for val in &self.matrix[i] {
-            if !*val {
-                return false;
+        self.matrix[i].iter().all(Cell::is_filled)
+    }
+}
+
+impl FromStr for Board {
+    type Err = BoardError;
+
+    /// Parses a board using the default piece set.
+    fn from_str(text: &str) -> Result<Board, BoardError> {
+        Board::parse(text, pieces::default_set())
+    }
+}
+
+/// Finds the piece and orientation whose cells, moved into place, are
+/// exactly `cells`.
+fn find_piece(cells: &[(isize, isize)], set: &'static PieceSet) -> Option<Tetromino> {
+    set.pieces
+        .iter()
+        .find_map(|piece| Tetromino::placed(piece, cells))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DIAGRAM: &str = "
+        ######
+        #_@@_#
+        #@@__#
+        #____#
+        #T_I_#
+        #XTTX#
+        ######
+    ";
+
+    fn diagram(text: &str) -> String {
+        text.lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+            .map(|line| format!("{}\n", line))
+            .collect()
+    }
+
+    #[test]
+    fn round_trips_through_text() {
+        let board: Board = DIAGRAM.parse().unwrap();
+
+        assert_eq!(board.to_str(), diagram(DIAGRAM));
+
+        let again: Board = board.to_str().parse().unwrap();
+
+        assert_eq!(again.matrix, board.matrix);
+        assert_eq!(again.tetromino.cells(), board.tetromino.cells());
+    }
+
+    #[test]
+    fn reads_cells_and_the_active_piece() {
+        let board: Board = DIAGRAM.parse().unwrap();
+        let set = pieces::default_set();
+
+        assert_eq!(board.matrix[0][0], Cell::Wall);
+        assert_eq!(board.matrix[1][1], Cell::Empty);
+        assert_eq!(board.matrix[4][1], Cell::Piece(set.get("T").unwrap()));
+        assert_eq!(board.matrix[4][3], Cell::Piece(set.get("I").unwrap()));
+        assert_eq!(board.matrix[5][1], Cell::Garbage);
+
+        let mut active = board.tetromino.cells();
+        active.sort();
+
+        assert_eq!(board.tetromino.piece().unwrap().name, "S");
+        assert_eq!(active, [(1, 2), (1, 3), (2, 1), (2, 2)]);
+        // The active piece's cells are empty underneath.
+        assert_eq!(board.matrix[1][2], Cell::Empty);
+    }
+
+    #[test]
+    fn reads_old_walls_and_boards_without_a_piece() {
+        let board: Board = "XXX\nX_X\nXXX".parse().unwrap();
+
+        assert!(board.tetromino.is_null());
+        assert_eq!(board.matrix[1][0], Cell::Wall);
+        assert_eq!(board.to_str(), "###\n#_#\n###\n");
+    }
+
+    #[test]
+    fn every_orientation_reaches_both_walls() {
+        let pentominoes = PieceSet::parse(include_str!("../assets/pieces/pentominoes.ron"))
+            .unwrap()
+            .leak();
+
+        for set in [pieces::default_set(), pentominoes] {
+            for piece in &set.pieces {
+                for orientation in 0..piece.patterns.len() {
+                    let mut board = Board::new(12, 14);
+                    let start = Tetromino::with_orientation(piece, orientation, 4, 5);
+
+                    for (movement, column) in [(Movement::Left, 1), (Movement::Right, 12)] {
+                        board.tetromino = start;
+                        let mut moves = 0;
+
+                        while board.apply_movement(movement) {
+                            moves += 1;
+                            assert!(moves < 20, "{} kept moving {:?}", piece.name, movement);
+                        }
+
+                        let columns = board.tetromino.cells().into_iter().map(|(_, j)| j);
+                        let edge = match movement {
+                            Movement::Left => columns.min(),
+                            _ => columns.max(),
+                        };
+
+                        assert_eq!(
+                            edge,
+                            Some(column),
+                            "{} in orientation {} moving {:?}",
+                            piece.name,
+                            orientation,
+                            movement
+                        );
+                    }
+                }
             }
         }
+    }
 
-        true
+    #[test]
+    fn rejects_bad_diagrams() {
+        let cases = [
+            ("##\n##", BoardError::TooSmall),
+            (
+                "####\n#__#\n#_#\n####",
+                BoardError::Ragged {
+                    line: 3,
+                    width: 3,
+                    expected: 4,
+                },
+            ),
+            (
+                "####\n#_?#\n####",
+                BoardError::UnknownCell {
+                    line: 2,
+                    column: 3,
+                    char: '?',
+                },
+            ),
+            (
+                "####\n#___\n####",
+                BoardError::MissingWall { line: 2, column: 4 },
+            ),
+            ("#####\n#@@_#\n#####", BoardError::UnknownPiece),
+        ];
+
+        for (text, error) in cases {
+            assert_eq!(text.parse::<Board>().err(), Some(error), "{}", text);
+        }
     }
 }
@@ -0,0 +1,523 @@
+use std::collections::VecDeque;
+use std::time::Duration;
+
+use crate::board::{Board, Cell, Movement};
+use crate::engine::{GameState, Timer};
+use crate::pieces::Piece;
+use crate::placements;
+use crate::tetromino::Tetromino;
+
+/// Seconds between the bot's inputs, slow enough to follow.
+const THINK_SECONDS: f32 = 0.05;
+/// Score given to placements that leave no room for the next piece.
+const TOP_OUT: f32 = -1.0e6;
+
+/// How much each feature of a board counts towards its score. Penalties are
+/// negative, rewards positive.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Weights {
+    pub aggregate_height: f32,
+    pub holes: f32,
+    pub bumpiness: f32,
+    pub wells: f32,
+    /// Reward for clearing 1, 2, 3 and 4 lines at once.
+    pub line_clears: [f32; 4],
+    pub t_spin: f32,
+    pub t_slots: f32,
+}
+
+impl Default for Weights {
+    fn default() -> Self {
+        Weights {
+            aggregate_height: -0.51,
+            holes: -3.6,
+            bumpiness: -0.18,
+            wells: -0.3,
+            line_clears: [-0.5, 0.5, 1.5, 8.0],
+            t_spin: 6.0,
+            t_slots: 1.5,
+        }
+    }
+}
+
+/// What the hold slot offers when choosing a placement.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Hold {
+    /// The game has no hold, or it was already used for this piece.
+    Unavailable,
+    /// Holding swaps in the first piece of the queue.
+    Empty,
+    Holding(&'static Piece),
+}
+
+/// Where the bot wants the active piece to end up and the inputs that take
+/// it there. After the path the piece is dropped until it locks.
+#[derive(Debug, Clone)]
+pub struct Choice {
+    pub tetromino: Tetromino,
+    pub path: Vec<Movement>,
+    /// Whether the piece to place is the held one, swapped in first.
+    pub hold: bool,
+    pub t_spin: bool,
+    pub score: f32,
+}
+
+/// Picks the best placement for the active piece, looking one piece ahead
+/// in `next`. When the hold is available, placing the piece it would swap
+/// in is considered too.
+pub fn choose(
+    board: &Board,
+    hold: Hold,
+    next: &[&'static Piece],
+    weights: &Weights,
+) -> Option<Choice> {
+    let mut candidates = vec![(board.clone(), false, next.to_vec())];
+
+    if let Some(active) = board.tetromino.piece() {
+        let swapped = match hold {
+            Hold::Unavailable => None,
+            Hold::Empty => next.first().map(|&first| (first, next[1..].to_vec())),
+            Hold::Holding(held) => Some((held, next.to_vec())),
+        };
+
+        if let Some((piece, rest)) = swapped.filter(|(piece, _)| !std::ptr::eq(*piece, active)) {
+            let mut held = board.clone();
+            held.tetromino = Tetromino::new(piece, 0, 0).at_spawn();
+
+            if !held.overlaps() {
+                candidates.push((held, true, rest));
+            }
+        }
+    }
+
+    let mut best: Option<Choice> = None;
+
+    for (board, hold, next) in candidates {
+        for placement in placements::reachable(&board) {
+            let (after, lines) = lock(&board, placement.tetromino);
+
+            let mut score = clear_score(lines, placement.t_spin, weights);
+
+            score += match next.first() {
+                Some(&piece) => best_follow_up(&after, piece, weights),
+                None => evaluate(&after, weights),
+            };
+
+            if best.as_ref().is_none_or(|best| score > best.score) {
+                best = Some(Choice {
+                    tetromino: placement.tetromino,
+                    path: placement.path,
+                    hold,
+                    t_spin: placement.t_spin,
+                    score,
+                });
+            }
+        }
+    }
+
+    best
+}
+
+/// The score of the best placement of `piece` on a board, or a top out if
+/// it has no room to spawn.
+fn best_follow_up(board: &Board, piece: &'static Piece, weights: &Weights) -> f32 {
+    let mut board = board.clone();
+    board.tetromino = Tetromino::new(piece, 0, 0).at_spawn();
+
+    if board.overlaps() {
+        return TOP_OUT;
+    }
+
+    placements::reachable(&board)
+        .iter()
+        .map(|placement| {
+            let (after, lines) = lock(&board, placement.tetromino);
+
+            clear_score(lines, placement.t_spin, weights) + evaluate(&after, weights)
+        })
+        .fold(TOP_OUT, f32::max)
+}
+
+fn lock(board: &Board, tetromino: Tetromino) -> (Board, usize) {
+    let mut after = board.clone();
+    after.tetromino = tetromino;
+    after.merge();
+    let lines = after.check_completed_rows();
+    after.tetromino = Tetromino::null();
+
+    (after, lines)
+}
+
+fn clear_score(lines: usize, t_spin: bool, weights: &Weights) -> f32 {
+    let mut score = match lines {
+        0 => 0.0,
+        lines => weights.line_clears[lines.min(4) - 1],
+    };
+
+    if t_spin && lines > 0 {
+        score += weights.t_spin * lines as f32;
+    }
+
+    score
+}
+
+/// Scores the shape of a board's stack, ignoring the active piece.
+pub fn evaluate(board: &Board, weights: &Weights) -> f32 {
+    let rows = board.matrix.len();
+    let cols = board.matrix[0].len();
+    let filled = |i: usize, j: usize| board.matrix[i][j] != Cell::Empty;
+
+    let heights: Vec<usize> = (1..cols - 1)
+        .map(|j| {
+            (1..rows - 1)
+                .find(|&i| filled(i, j))
+                .map_or(0, |top| rows - 1 - top)
+        })
+        .collect();
+
+    let holes: usize = (1..cols - 1)
+        .map(|j| {
+            let top = rows - 1 - heights[j - 1];
+            (top..rows - 1).filter(|&i| !filled(i, j)).count()
+        })
+        .sum();
+
+    let bumpiness: usize = heights
+        .windows(2)
+        .map(|pair| pair[0].abs_diff(pair[1]))
+        .sum();
+
+    let wells: usize = (0..heights.len())
+        .map(|j| {
+            let left = if j == 0 { rows } else { heights[j - 1] };
+            let right = heights.get(j + 1).copied().unwrap_or(rows);
+
+            left.min(right).saturating_sub(heights[j])
+        })
+        .filter(|&depth| depth >= 2)
+        .sum();
+
+    let aggregate_height: usize = heights.iter().sum();
+
+    weights.aggregate_height * aggregate_height as f32
+        + weights.holes * holes as f32
+        + weights.bumpiness * bumpiness as f32
+        + weights.wells * wells as f32
+        + weights.t_slots * t_slots(board) as f32
+}
+
+/// Counts spots a T could spin into for a double: three empty cells in a
+/// row with the middle one open below, its two lower corners filled and at
+/// least one upper corner filled to hook the T under.
+fn t_slots(board: &Board) -> usize {
+    let rows = board.matrix.len();
+    let cols = board.matrix[0].len();
+    let empty = |i: usize, j: usize| board.matrix[i][j] == Cell::Empty;
+
+    let mut slots = 0;
+
+    for i in 1..rows - 2 {
+        for j in 2..cols - 2 {
+            let open = empty(i, j - 1) && empty(i, j) && empty(i, j + 1) && empty(i + 1, j);
+            let lower = !empty(i + 1, j - 1) && !empty(i + 1, j + 1);
+            let upper = !empty(i - 1, j - 1) || !empty(i - 1, j + 1);
+
+            if open && lower && upper {
+                slots += 1;
+            }
+        }
+    }
+
+    slots
+}
+
+/// Something that plays a game in place of the keyboard, asked for an
+/// input every frame.
+pub trait Controller: Send + Sync {
+    fn update(&mut self, delta: Duration, state: &GameState) -> Option<Movement>;
+}
+
+/// Steers the active piece to a placement, feeding the engine one input
+/// every `THINK_SECONDS`. If the piece ends up somewhere else than planned,
+/// say because gravity pulled it down and swallowed an input, it searches
+/// for a new way there from where the piece is. Once the path is done the
+/// piece is soft dropped until it locks.
+#[derive(Debug, Clone)]
+pub struct Pilot {
+    pace: Timer,
+    plan: VecDeque<Movement>,
+    target: Option<Tetromino>,
+    expected: Vec<(isize, isize)>,
+}
+
+impl Default for Pilot {
+    fn default() -> Self {
+        Pilot {
+            pace: Timer::from_seconds(THINK_SECONDS),
+            plan: VecDeque::new(),
+            target: None,
+            expected: vec![],
+        }
+    }
+}
+
+impl Pilot {
+    /// Heads for `target` along `path`, or just drops the piece without one.
+    pub fn steer(&mut self, target: Option<Tetromino>, path: Vec<Movement>) {
+        self.target = target;
+        self.plan = path.into();
+    }
+
+    /// Whether there is a placement to head for. It is given up when the
+    /// piece can no longer reach it.
+    pub fn has_target(&self) -> bool {
+        self.target.is_some()
+    }
+
+    /// The next input for the game, if it is time for one.
+    pub fn update(&mut self, delta: Duration, state: &GameState) -> Option<Movement> {
+        self.pace.tick(delta);
+
+        if !self.pace.just_finished() || state.board.tetromino.is_null() {
+            return None;
+        }
+
+        if state.board.tetromino.cells() != self.expected {
+            self.replan(state);
+        }
+
+        let movement = self.plan.pop_front().unwrap_or(Movement::Down);
+
+        let mut board = state.board.clone();
+        board.apply_movement(movement);
+        self.expected = board.tetromino.cells();
+
+        Some(movement)
+    }
+
+    fn replan(&mut self, state: &GameState) {
+        let Some(target) = self.target else {
+            return;
+        };
+
+        let mut cells = target.cells();
+        cells.sort();
+
+        let placement = placements::reachable(&state.board)
+            .into_iter()
+            .find(|placement| {
+                let mut placed = placement.tetromino.cells();
+                placed.sort();
+                placed == cells
+            });
+
+        match placement {
+            Some(placement) => self.plan = placement.path.into(),
+            None => self.steer(None, vec![]),
+        }
+    }
+}
+
+/// Plays a game by choosing a placement for each new piece and piloting the
+/// piece there, choosing again if it cannot get there anymore. The engine
+/// has no hold, so the bot never uses it.
+#[derive(Debug, Clone)]
+pub struct Bot {
+    pub weights: Weights,
+    pilot: Pilot,
+    planned_for: Option<usize>,
+    /// Set when the active piece had nowhere to go, so it is not searched
+    /// for again on every frame.
+    stuck: bool,
+}
+
+impl Default for Bot {
+    fn default() -> Self {
+        Bot::new(Weights::default())
+    }
+}
+
+impl Bot {
+    pub fn new(weights: Weights) -> Bot {
+        Bot {
+            weights,
+            pilot: Pilot::default(),
+            planned_for: None,
+            stuck: false,
+        }
+    }
+}
+
+impl Controller for Bot {
+    fn update(&mut self, delta: Duration, state: &GameState) -> Option<Movement> {
+        if state.board.tetromino.is_null() {
+            return None;
+        }
+
+        let new_piece = self.planned_for != Some(state.count);
+
+        if new_piece || (!self.pilot.has_target() && !self.stuck) {
+            self.planned_for = Some(state.count);
+
+            let next: Vec<&'static Piece> = state.next.piece().into_iter().collect();
+            let choice = choose(&state.board, Hold::Unavailable, &next, &self.weights);
+            self.stuck = choice.is_none();
+
+            match choice {
+                Some(choice) => self.pilot.steer(Some(choice.tetromino), choice.path),
+                None => self.pilot.steer(None, vec![]),
+            }
+        }
+
+        self.pilot.update(delta, state)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pieces;
+
+    /// Only the weight of `holes`, so boards score minus their holes.
+    fn holes_only() -> Weights {
+        Weights {
+            aggregate_height: 0.0,
+            holes: -1.0,
+            bumpiness: 0.0,
+            wells: 0.0,
+            line_clears: [0.0; 4],
+            t_spin: 0.0,
+            t_slots: 0.0,
+        }
+    }
+
+    fn board(text: &str) -> Board {
+        Board::parse(text, pieces::default_set()).unwrap()
+    }
+
+    fn with_piece(text: &str, name: &str) -> Board {
+        let set = pieces::default_set();
+        let mut board = board(text);
+        board.tetromino = Tetromino::new(set.get(name).unwrap(), 0, 0).at_spawn();
+        board
+    }
+
+    #[test]
+    fn penalises_holes() {
+        let flat = board(
+            "
+            ########
+            #______#
+            #______#
+            #XXX___#
+            #XXX___#
+            ########
+            ",
+        );
+        let covered = board(
+            "
+            ########
+            #______#
+            #______#
+            #XXX___#
+            #X_X___#
+            ########
+            ",
+        );
+
+        assert_eq!(evaluate(&flat, &holes_only()), 0.0);
+        assert_eq!(evaluate(&covered, &holes_only()), -1.0);
+        assert!(evaluate(&covered, &Weights::default()) < evaluate(&flat, &Weights::default()));
+    }
+
+    #[test]
+    fn counts_t_slots() {
+        let slot = board(
+            "
+            ########
+            #______#
+            #XX____#
+            #X___XX#
+            #XX_XXX#
+            ########
+            ",
+        );
+        let open = board(
+            "
+            ########
+            #______#
+            #______#
+            #X___XX#
+            #XX_XXX#
+            ########
+            ",
+        );
+
+        assert_eq!(t_slots(&slot), 1);
+        assert_eq!(t_slots(&open), 0);
+    }
+
+    #[test]
+    fn scores_clears() {
+        let weights = Weights::default();
+
+        assert_eq!(clear_score(0, true, &weights), 0.0);
+        assert_eq!(clear_score(4, false, &weights), weights.line_clears[3]);
+        assert_eq!(
+            clear_score(2, true, &weights),
+            weights.line_clears[1] + 2.0 * weights.t_spin
+        );
+    }
+
+    #[test]
+    fn takes_an_available_tetris() {
+        let board = with_piece(
+            "
+            ########
+            #______#
+            #______#
+            #______#
+            #______#
+            #______#
+            #______#
+            #______#
+            #______#
+            #XXXXX_#
+            #XXXXX_#
+            #XXXXX_#
+            #XXXXX_#
+            ########
+            ",
+            "I",
+        );
+
+        let choice = choose(&board, Hold::Unavailable, &[], &Weights::default()).unwrap();
+        let mut cells = choice.tetromino.cells();
+        cells.sort();
+
+        assert_eq!(cells, [(9, 6), (10, 6), (11, 6), (12, 6)]);
+        assert_eq!(lock(&board, choice.tetromino).1, 4);
+    }
+
+    #[test]
+    fn gives_up_on_pieces_with_nowhere_to_go() {
+        let mode = crate::mode::GameMode::Marathon { endless: true };
+        let mut state = GameState::new(mode, 1, pieces::default_set());
+
+        // Bury the spawn, so the piece has no placement. A gap in each row
+        // keeps them from clearing.
+        for row in state.board.matrix.iter_mut().skip(1) {
+            let cols = row.len();
+
+            for cell in row[2..cols - 1].iter_mut() {
+                *cell = Cell::Garbage;
+            }
+        }
+
+        let mut bot = Bot::default();
+        bot.update(Duration::ZERO, &state);
+
+        assert!(bot.stuck);
+        assert!(!bot.pilot.has_target());
+    }
+}
@@ -0,0 +1,702 @@
+use std::collections::VecDeque;
+use std::time::Duration;
+
+use chrono::Local;
+
+use crate::board::{Board, Movement};
+use crate::garbage::GarbageGenerator;
+use crate::highscores::{format_duration, Record};
+use crate::i18n;
+use crate::mode::{GameMode, DIG_RISE_SECONDS, LINES_PER_LEVEL, MARATHON_LEVELS};
+use crate::pieces::{self, Piece, PieceSet};
+use crate::puzzle::{Goal, Puzzle, PuzzleError};
+use crate::scoring::{ClearStats, ClearType};
+use crate::settings::Language;
+use crate::tetromino::Tetromino;
+
+/// A repeating timer, ticked with the time that passed since the last frame.
+#[derive(Debug, Clone)]
+pub struct Timer {
+    duration: Duration,
+    elapsed: Duration,
+    times_finished: u32,
+}
+
+impl Timer {
+    pub fn from_seconds(seconds: f32) -> Timer {
+        Timer {
+            duration: Duration::from_secs_f32(seconds),
+            elapsed: Duration::ZERO,
+            times_finished: 0,
+        }
+    }
+
+    pub fn tick(&mut self, delta: Duration) {
+        self.elapsed += delta;
+        self.times_finished = 0;
+
+        if self.duration.is_zero() {
+            return;
+        }
+
+        while self.elapsed >= self.duration {
+            self.elapsed -= self.duration;
+            self.times_finished += 1;
+        }
+    }
+
+    /// Whether the timer went off during the last tick.
+    pub fn just_finished(&self) -> bool {
+        self.times_finished > 0
+    }
+
+    pub fn duration(&self) -> Duration {
+        self.duration
+    }
+}
+
+/// What happened during one call to `GameState::update`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Step {
+    /// The active piece moved, rotated or was replaced.
+    pub moved: bool,
+    /// The active piece moved sideways.
+    pub shifted: bool,
+    /// The active piece rotated.
+    pub rotated: bool,
+    /// Blocks on the board changed, by a lock or rising garbage.
+    pub board_changed: bool,
+    /// The active piece locked, clearing this many lines.
+    pub locked: Option<usize>,
+    /// The lock was a T-spin.
+    pub t_spin: bool,
+    /// Garbage rows the lock sends to the opponent in versus, once the rows
+    /// on their way to this board are cancelled.
+    pub attack: usize,
+    /// The lock cleared enough lines to go up a level.
+    pub levelled_up: bool,
+    /// The game ended, by reaching its goal if `GameState::finished` is set
+    /// and by topping out otherwise.
+    pub over: bool,
+}
+
+/// A game in progress, independent of how it is drawn. Frontends call
+/// `update` with the time that passed and the player's movement, then draw
+/// the board and react to what the returned `Step` says happened.
+#[derive(Clone)]
+#[cfg_attr(feature = "bevy", derive(bevy::prelude::Resource))]
+pub struct GameState {
+    pub board: Board,
+    pub timer: Timer,
+    pub next: Tetromino,
+    pub score: usize,
+    pub count: usize,
+    pub mode: GameMode,
+    pub pieces: &'static PieceSet,
+    pub lines: usize,
+    pub level: usize,
+    pub start_level: usize,
+    pub elapsed: Duration,
+    pub finished: bool,
+    pub clears: ClearStats,
+    pub garbage: usize,
+    pub garbage_generator: GarbageGenerator,
+    pub garbage_timer: Option<Timer>,
+    /// Garbage rows sent by the opponent in versus. They come up under the
+    /// stack the next time a piece locks without clearing a line.
+    pub incoming: usize,
+    pub puzzle: Option<Puzzle>,
+    pub queue: Option<VecDeque<&'static Piece>>,
+    pub last_rotated: bool,
+    pub perfect_clears: usize,
+}
+
+impl GameState {
+    pub fn new(mode: GameMode, start_level: usize, pieces: &'static PieceSet) -> GameState {
+        let next = preview(Tetromino::random_from(pieces));
+
+        let mut garbage_generator = GarbageGenerator::default();
+        let (board, garbage, garbage_timer) = match mode {
+            GameMode::Dig { rows, rising } => (
+                Board::with_garbage(20, 15, &garbage_generator.holes(15, rows)),
+                rows,
+                rising.then(|| Timer::from_seconds(DIG_RISE_SECONDS)),
+            ),
+            _ => (Board::new(20, 15), 0, None),
+        };
+
+        GameState {
+            board,
+            timer: Timer::from_seconds(gravity(start_level)),
+            next,
+            count: 0,
+            score: 0,
+            mode,
+            pieces,
+            lines: 0,
+            level: start_level,
+            start_level,
+            elapsed: Duration::ZERO,
+            finished: false,
+            clears: ClearStats::default(),
+            garbage,
+            garbage_generator,
+            garbage_timer,
+            incoming: 0,
+            puzzle: None,
+            queue: None,
+            last_rotated: false,
+            perfect_clears: 0,
+        }
+    }
+
+    /// Starts a puzzle on a board `rows` by `cols`, walls included.
+    pub fn from_puzzle(
+        puzzle: &Puzzle,
+        rows: usize,
+        cols: usize,
+    ) -> Result<GameState, PuzzleError> {
+        let mut state = GameState::new(GameMode::Puzzle, 1, pieces::default_set());
+        let mut queue: VecDeque<&'static Piece> = puzzle.pieces()?.into_iter().collect();
+
+        state.board = puzzle.to_board(rows, cols)?;
+
+        queue.pop_front();
+        state.next = match queue.pop_front() {
+            Some(piece) => preview(Tetromino::new(piece, 0, 0)),
+            None => Tetromino::null(),
+        };
+        state.queue = Some(queue);
+        state.puzzle = Some(puzzle.clone());
+
+        Ok(state)
+    }
+
+    /// Starts a practice game on `board`, playing `queue` first and then
+    /// random pieces.
+    pub fn practice(board: Board, queue: Vec<&'static Piece>) -> GameState {
+        let mut state = GameState::new(GameMode::Practice, 1, pieces::default_set());
+        let mut queue: VecDeque<&'static Piece> = queue.into_iter().collect();
+
+        state.board = board;
+        state.board.tetromino = match queue.pop_front() {
+            Some(piece) => Tetromino::new(piece, 0, 0).at_spawn(),
+            None => Tetromino::random(),
+        };
+
+        if let Some(piece) = queue.pop_front() {
+            state.next = preview(Tetromino::new(piece, 0, 0));
+        }
+
+        state.queue = Some(queue);
+
+        state
+    }
+
+    /// Moves the next piece onto the board and draws a new one. Returns false
+    /// if there was no piece left to play.
+    pub fn spawn_next(&mut self) -> bool {
+        if self.next.is_null() {
+            return false;
+        }
+
+        self.board.tetromino = self.next.at_spawn();
+        self.last_rotated = false;
+
+        self.next = match self.queue.as_mut() {
+            Some(queue) => match queue.pop_front() {
+                Some(piece) => preview(Tetromino::new(piece, 0, 0)),
+                None if self.mode == GameMode::Practice => preview(Tetromino::random()),
+                None => Tetromino::null(),
+            },
+            None => preview(Tetromino::random_from(self.pieces)),
+        };
+
+        true
+    }
+
+    /// Whether the mode's goal has been reached, as opposed to topping out.
+    pub fn goal_reached(&self) -> bool {
+        match self.mode {
+            GameMode::Marathon { endless } => {
+                !endless && self.start_level + self.lines / LINES_PER_LEVEL > MARATHON_LEVELS
+            }
+            GameMode::Sprint { goal } => self.lines >= goal,
+            GameMode::Ultra { .. } => self.time_remaining() == Some(Duration::ZERO),
+            GameMode::Dig { .. } => self.garbage == 0,
+            GameMode::Puzzle => match self.puzzle.as_ref().map(|puzzle| puzzle.goal) {
+                Some(Goal::Lines(lines)) => self.lines >= lines,
+                Some(Goal::PerfectClear) => self.perfect_clears > 0,
+                Some(Goal::TSpinDouble) => self.clears.count(ClearType::TSpinDouble) > 0,
+                None => false,
+            },
+            GameMode::Practice | GameMode::Versus => false,
+        }
+    }
+
+    pub fn lines_remaining(&self) -> Option<usize> {
+        match self.mode {
+            GameMode::Sprint { goal } => Some(goal.saturating_sub(self.lines)),
+            GameMode::Dig { .. } => Some(self.garbage),
+            _ => None,
+        }
+    }
+
+    pub fn time_remaining(&self) -> Option<Duration> {
+        match self.mode {
+            GameMode::Ultra { seconds } => {
+                Some(Duration::from_secs(seconds).saturating_sub(self.elapsed))
+            }
+            _ => None,
+        }
+    }
+
+    pub fn pieces_per_second(&self) -> f32 {
+        let seconds = self.elapsed.as_secs_f32();
+
+        if seconds == 0.0 {
+            return 0.0;
+        }
+
+        self.count as f32 / seconds
+    }
+
+    /// Records are only kept for games played with the default pieces, so
+    /// other sets don't share their tables.
+    pub fn keeps_records(&self) -> bool {
+        self.mode.keeps_records() && std::ptr::eq(self.pieces, pieces::default_set())
+    }
+
+    pub fn record(&self, name: String) -> Record {
+        Record {
+            name,
+            score: self.score,
+            lines: self.lines,
+            level: self.level,
+            duration: self.elapsed,
+            date: Local::now(),
+        }
+    }
+
+    /// Counts the garbage rows among the rows about to be cleared. Garbage
+    /// always sits at the bottom of the stack, so any completed row within the
+    /// bottom `garbage` rows is a garbage row.
+    pub fn register_cleared_garbage(&mut self) {
+        let floor = self.board.matrix.len() - 2;
+        let cleared = self
+            .board
+            .completed_rows()
+            .iter()
+            .filter(|&&i| i + self.garbage > floor)
+            .count();
+
+        self.garbage -= cleared;
+    }
+
+    /// Advances the rising garbage timer. Returns `None` if no row was added,
+    /// otherwise whether the new row still fit on the board.
+    pub fn tick_garbage(&mut self, delta: Duration) -> Option<bool> {
+        let timer = self.garbage_timer.as_mut()?;
+        timer.tick(delta);
+
+        if !timer.just_finished() {
+            return None;
+        }
+
+        let columns = self.board.matrix[0].len();
+        let hole = self.garbage_generator.next_hole(columns);
+        let mut fits = self.board.push_garbage(hole);
+        self.garbage += 1;
+
+        if self.board.overlaps() {
+            self.board.tetromino.i -= 1;
+            fits = fits && !self.board.overlaps();
+        }
+
+        Some(fits)
+    }
+
+    /// Pushes the incoming garbage under the stack, every row with its hole
+    /// in the same column. Returns whether the stack still fits on the board.
+    pub fn receive_garbage(&mut self) -> bool {
+        let rows = std::mem::take(&mut self.incoming);
+        let columns = self.board.matrix[0].len();
+        let hole = self.garbage_generator.next_hole(columns);
+        let mut fits = true;
+
+        for _ in 0..rows {
+            fits &= self.board.push_garbage(hole);
+        }
+
+        self.garbage += rows;
+
+        fits
+    }
+
+    /// Advances the game by `delta` and applies the player's movement, if
+    /// any. Gravity takes priority over the player's movement when both
+    /// happen in the same step.
+    pub fn update(&mut self, delta: Duration, movement: Option<Movement>) -> Step {
+        let mut step = Step::default();
+
+        self.timer.tick(delta);
+        self.elapsed += delta;
+
+        if self.goal_reached() {
+            self.finished = true;
+            step.over = true;
+            return step;
+        }
+
+        match self.tick_garbage(delta) {
+            Some(false) => {
+                step.over = true;
+                return step;
+            }
+            Some(true) => {
+                step.board_changed = true;
+                step.moved = true;
+            }
+            None => {}
+        }
+
+        let movement = if self.timer.just_finished() {
+            Some(Movement::Down)
+        } else {
+            movement
+        };
+
+        let Some(movement) = movement else {
+            return step;
+        };
+
+        let status = self.board.apply_movement(movement);
+        step.moved = true;
+
+        if status {
+            self.last_rotated = matches!(movement, Movement::RotateLeft | Movement::RotateRight);
+            step.rotated = self.last_rotated;
+            step.shifted = matches!(movement, Movement::Left | Movement::Right);
+        }
+
+        if status || movement != Movement::Down {
+            return step;
+        }
+
+        self.count += 1;
+        let t_spin = self.last_rotated && self.board.is_t_spin();
+        self.board.merge();
+        self.register_cleared_garbage();
+        let completed = self.board.check_completed_rows();
+        let level = self.level;
+        self.register_cleared_lines(completed, t_spin);
+
+        let attack = ClearType::from_lines(completed, t_spin).map_or(0, |clear| clear.garbage());
+        let cancelled = attack.min(self.incoming);
+        self.incoming -= cancelled;
+
+        step.locked = Some(completed);
+        step.t_spin = t_spin;
+        step.attack = attack - cancelled;
+        step.levelled_up = self.level > level;
+        step.board_changed = true;
+
+        if completed == 0 && self.incoming > 0 && !self.receive_garbage() {
+            step.over = true;
+            return step;
+        }
+
+        if self.goal_reached() {
+            self.finished = true;
+            step.over = true;
+            return step;
+        }
+
+        if !self.spawn_next() || self.board.overlaps() {
+            step.over = true;
+        }
+
+        step
+    }
+
+    /// Advances the game by `delta` and drops the active piece until it
+    /// locks, adding up what happened on the way down.
+    pub fn hard_drop(&mut self, delta: Duration) -> Step {
+        let mut step = self.update(delta, Some(Movement::Down));
+
+        while step.locked.is_none() && !step.over {
+            let next = self.update(Duration::ZERO, Some(Movement::Down));
+
+            step = Step {
+                moved: step.moved || next.moved,
+                board_changed: step.board_changed || next.board_changed,
+                ..next
+            };
+        }
+
+        step
+    }
+
+    /// A summary of how the game went, for the game over screen, in
+    /// `language`. `previous_best` is the mode's best time before this game,
+    /// if any.
+    pub fn summary(&self, previous_best: Option<Duration>, language: Language) -> String {
+        let line = |id: &str, args: &[&dyn std::fmt::Display]| {
+            format!("{}\n", i18n::format(language, id, args))
+        };
+        let elapsed = format_duration(self.elapsed);
+
+        match self.mode {
+            GameMode::Sprint { .. } | GameMode::Dig { .. } if self.finished => {
+                let pps = format!("{:.2}", self.pieces_per_second());
+                let mut summary = line("summary.finished", &[&elapsed, &pps]);
+
+                match previous_best {
+                    Some(best) if best <= self.elapsed => {
+                        summary.push_str(&line("summary.personal_best", &[&format_duration(best)]));
+                    }
+                    _ => summary.push_str(&line("summary.new_best", &[])),
+                }
+
+                summary
+            }
+            GameMode::Ultra { .. } => {
+                let mut summary = if self.finished {
+                    line("summary.time_up", &[&self.score])
+                } else {
+                    line("summary.lost", &[&self.score])
+                };
+
+                for clear_type in ClearType::ALL {
+                    summary.push_str(&line(
+                        "summary.clear",
+                        &[
+                            &clear_type.name(language),
+                            &self.clears.count(clear_type),
+                            &self.clears.points(clear_type),
+                        ],
+                    ));
+                }
+
+                summary
+            }
+            GameMode::Versus => {
+                let result = if self.finished {
+                    "summary.won"
+                } else {
+                    "summary.beaten"
+                };
+
+                line(result, &[]) + &line("summary.stats", &[&self.lines, &self.level, &elapsed])
+            }
+            GameMode::Puzzle => {
+                let goal = self
+                    .puzzle
+                    .as_ref()
+                    .map(|puzzle| puzzle.goal.description(language))
+                    .unwrap_or_default();
+
+                if self.finished {
+                    line("summary.solved", &[&goal])
+                } else {
+                    line("summary.failed", &[&goal])
+                }
+            }
+            _ => {
+                let result = if self.mode.levels_up() && self.finished {
+                    "summary.marathon_complete"
+                } else {
+                    "summary.lost"
+                };
+
+                line(result, &[&self.score])
+                    + &line("summary.stats", &[&self.lines, &self.level, &elapsed])
+            }
+        }
+    }
+
+    pub fn register_cleared_lines(&mut self, completed: usize, t_spin: bool) {
+        if completed > 0 && self.board.is_clear() {
+            self.perfect_clears += 1;
+        }
+
+        if let Some(clear_type) = ClearType::from_lines(completed, t_spin) {
+            let points = clear_type.base_points() * self.level;
+
+            self.score += points;
+            self.clears.record(clear_type, points);
+        }
+
+        self.lines += completed;
+
+        if !self.mode.levels_up() || self.goal_reached() {
+            return;
+        }
+
+        let level = self.start_level + self.lines / LINES_PER_LEVEL;
+
+        if level != self.level {
+            self.level = level;
+            self.timer = Timer::from_seconds(gravity(level));
+        }
+    }
+}
+
+impl Default for GameState {
+    fn default() -> Self {
+        GameState::new(GameMode::default(), 1, pieces::default_set())
+    }
+}
+
+fn preview(mut tetromino: Tetromino) -> Tetromino {
+    tetromino.i = 4;
+    tetromino.j = -6;
+
+    tetromino
+}
+
+/// Seconds per row at the given level, following the guideline gravity curve.
+pub fn gravity(level: usize) -> f32 {
+    let level = level.max(1) as f32 - 1.0;
+
+    (0.8 - level * 0.007).powf(level)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board::Cell;
+
+    fn versus() -> GameState {
+        GameState::new(GameMode::Versus, 1, pieces::default_set())
+    }
+
+    /// Fills the bottom `rows` rows of the board but for column 1.
+    fn fill_bottom(state: &mut GameState, rows: usize) {
+        let floor = state.board.matrix.len() - 2;
+
+        for row in &mut state.board.matrix[floor + 1 - rows..=floor] {
+            let cols = row.len();
+
+            for cell in &mut row[2..cols - 1] {
+                *cell = Cell::Garbage;
+            }
+        }
+    }
+
+    #[test]
+    fn incoming_garbage_rises_when_a_piece_locks_without_clearing() {
+        let mut state = versus();
+        state.incoming = 3;
+
+        let step = state.hard_drop(Duration::ZERO);
+
+        assert_eq!(step.locked, Some(0));
+        assert_eq!(state.incoming, 0);
+
+        let floor = state.board.matrix.len() - 2;
+        let holes: Vec<Vec<usize>> = state.board.matrix[floor - 2..=floor]
+            .iter()
+            .map(|row| {
+                let cols = row.len();
+
+                (1..cols - 1).filter(|&j| row[j] == Cell::Empty).collect()
+            })
+            .collect();
+
+        assert_eq!(holes.len(), 3);
+        assert_eq!(holes[0].len(), 1);
+        assert!(holes.iter().all(|row| *row == holes[0]));
+    }
+
+    #[test]
+    fn clears_cancel_incoming_garbage_before_attacking() {
+        let mut state = versus();
+        fill_bottom(&mut state, 4);
+        state.incoming = 1;
+
+        let piece = pieces::default_set().get("I").unwrap();
+        state.board.tetromino =
+            Tetromino::placed(piece, &[(2, 1), (3, 1), (4, 1), (5, 1)]).unwrap();
+
+        let step = state.hard_drop(Duration::ZERO);
+
+        assert_eq!(step.locked, Some(4));
+        assert_eq!(step.attack, ClearType::Tetris.garbage() - 1);
+        assert_eq!(state.incoming, 0);
+    }
+
+    #[test]
+    fn small_clears_keep_the_rest_of_the_incoming_garbage_waiting() {
+        let mut state = versus();
+        fill_bottom(&mut state, 2);
+        state.incoming = 5;
+
+        let piece = pieces::default_set().get("I").unwrap();
+        state.board.tetromino =
+            Tetromino::placed(piece, &[(2, 1), (3, 1), (4, 1), (5, 1)]).unwrap();
+
+        let step = state.hard_drop(Duration::ZERO);
+
+        assert_eq!(step.locked, Some(2));
+        assert_eq!(step.attack, 0);
+        assert_eq!(state.incoming, 4);
+        assert_eq!(state.garbage, 0);
+    }
+
+    #[test]
+    fn garbage_that_reaches_the_top_ends_the_game() {
+        let mut state = versus();
+        state.incoming = state.board.matrix.len();
+
+        let step = state.hard_drop(Duration::ZERO);
+
+        assert!(step.over);
+        assert!(!state.finished);
+    }
+
+    fn basics() -> crate::puzzle::PuzzlePack {
+        let path =
+            std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("assets/puzzles/basics.ron");
+
+        crate::puzzle::PuzzlePack::load(&path, 20, 15).unwrap()
+    }
+
+    #[test]
+    fn reports_a_puzzle_solved() {
+        let pack = basics();
+        let puzzle = pack
+            .puzzles
+            .iter()
+            .find(|puzzle| puzzle.name == "Tetris")
+            .unwrap();
+        let mut state = GameState::from_puzzle(puzzle, 20, 15).unwrap();
+
+        let piece = pieces::default_set().get("I").unwrap();
+        state.board.tetromino =
+            Tetromino::placed(piece, &[(2, 13), (3, 13), (4, 13), (5, 13)]).unwrap();
+
+        let step = state.hard_drop(Duration::ZERO);
+
+        assert_eq!(step.locked, Some(4));
+        assert!(step.over);
+        assert!(state.finished);
+    }
+
+    #[test]
+    fn reports_a_puzzle_failed_when_the_pieces_run_out() {
+        let pack = basics();
+        let puzzle = pack
+            .puzzles
+            .iter()
+            .find(|puzzle| puzzle.name == "Tetris")
+            .unwrap();
+        let mut state = GameState::from_puzzle(puzzle, 20, 15).unwrap();
+
+        let step = state.hard_drop(Duration::ZERO);
+
+        assert_eq!(step.locked, Some(0));
+        assert!(step.over);
+        assert!(!state.finished);
+    }
+}
@@ -0,0 +1,441 @@
+use std::collections::VecDeque;
+use std::time::Duration;
+
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+
+use crate::board::{Board, Movement};
+use crate::bot::{self, Weights};
+use crate::engine::GameState;
+use crate::mode::GameMode;
+use crate::pieces::{Piece, PieceSet};
+use crate::placements::{self, Placement};
+use crate::tetromino::Tetromino;
+
+type Cells = Vec<(isize, isize)>;
+
+/// What the agent does in one step: a single input, or placing the active
+/// piece at one of the positions listed by `Environment::placements`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Action {
+    Move(Movement),
+    Place(usize),
+}
+
+/// What the agent sees of the game.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Observation {
+    /// Filled cells inside the walls, top row first.
+    pub board: Vec<Vec<bool>>,
+    /// The active piece's cells, on a grid like `board`.
+    pub piece: Vec<Vec<bool>>,
+    /// The active piece and the next one, as indices into the piece set.
+    pub queue: Vec<usize>,
+}
+
+/// What happened during a step, besides the reward.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Info {
+    /// The active piece locked.
+    pub locked: bool,
+    pub lines: usize,
+    pub t_spin: bool,
+    /// The action could not be taken and the game did not change.
+    pub invalid: bool,
+    pub score: usize,
+    pub pieces: usize,
+}
+
+/// How steps are rewarded. Every field defaults to a plain line clear
+/// reward; the rest is there to shape it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Rewards {
+    /// For clearing 1, 2, 3 and 4 lines at once.
+    pub lines: [f32; 4],
+    /// Added to the line reward when the clear is a T-spin.
+    pub t_spin: f32,
+    /// For every piece locked, to reward staying alive.
+    pub piece: f32,
+    pub game_over: f32,
+    /// Scores the stack with the bot's heuristic, rewarding each lock by how
+    /// much it improved the score.
+    pub stack: Option<Weights>,
+}
+
+impl Default for Rewards {
+    fn default() -> Self {
+        Rewards {
+            lines: [1.0, 3.0, 5.0, 8.0],
+            t_spin: 0.0,
+            piece: 0.0,
+            game_over: 0.0,
+            stack: None,
+        }
+    }
+}
+
+/// A gym-style wrapper around an endless marathon for training agents.
+/// Nothing happens between steps: gravity is off and pieces only lock when
+/// moved down onto the stack or placed. Pieces come from a generator seeded
+/// on `reset`, so episodes can be replayed.
+pub struct Environment {
+    pub rewards: Rewards,
+    pieces: &'static PieceSet,
+    state: GameState,
+    rng: StdRng,
+    /// The placements found for the active piece, and where it was then.
+    placements: Option<(Cells, Vec<Placement>)>,
+}
+
+impl Environment {
+    pub fn new(pieces: &'static PieceSet, rewards: Rewards) -> Environment {
+        let mut environment = Environment {
+            rewards,
+            pieces,
+            state: GameState::new(GameMode::Marathon { endless: true }, 1, pieces),
+            rng: StdRng::seed_from_u64(0),
+            placements: None,
+        };
+
+        environment.reset(0);
+        environment
+    }
+
+    /// Starts a new episode.
+    pub fn reset(&mut self, seed: u64) -> Observation {
+        self.rng = StdRng::seed_from_u64(seed);
+        self.placements = None;
+
+        let mut state = GameState::new(GameMode::Marathon { endless: true }, 1, self.pieces);
+        let queue = [self.draw(), self.draw()];
+        state.queue = Some(VecDeque::from(queue));
+
+        // Bring the seeded pieces in, replacing the ones drawn by the game.
+        state.spawn_next();
+        state.spawn_next();
+
+        self.state = state;
+        self.observe()
+    }
+
+    /// Takes an action, returning what the agent sees afterwards, its reward,
+    /// whether the episode is over and what happened.
+    pub fn step(&mut self, action: Action) -> (Observation, f32, bool, Info) {
+        if self.done() {
+            return (self.observe(), 0.0, true, self.info());
+        }
+
+        self.refill();
+
+        let before = self.stack_score();
+        let mut info = self.info();
+        let mut over = false;
+
+        let mut movements: Vec<Movement> = match action {
+            Action::Move(movement) => vec![movement],
+            Action::Place(index) => match self.placements().get(index) {
+                Some(placement) => placement.path.clone(),
+                None => {
+                    info.invalid = true;
+                    return (self.observe(), 0.0, false, info);
+                }
+            },
+        };
+
+        // A placement's path ends with the piece resting on the stack, so a
+        // final Down locks it.
+        if let Action::Place(_) = action {
+            movements.push(Movement::Down);
+        }
+
+        for movement in movements {
+            let step = self.state.update(Duration::ZERO, Some(movement));
+
+            // Only a Down the board rejects does something, locking the piece.
+            if let Action::Move(movement) = action {
+                if movement != Movement::Down && !step.shifted && !step.rotated {
+                    info.invalid = true;
+                    return (self.observe(), 0.0, false, info);
+                }
+            }
+
+            if let Some(lines) = step.locked {
+                info.locked = true;
+                info.lines = lines;
+                info.t_spin = step.t_spin;
+            }
+
+            if step.over || step.locked.is_some() {
+                over = step.over;
+                break;
+            }
+        }
+
+        let mut reward = 0.0;
+
+        if info.locked {
+            if info.lines > 0 {
+                reward += self.rewards.lines[info.lines.min(4) - 1];
+
+                if info.t_spin {
+                    reward += self.rewards.t_spin;
+                }
+            }
+
+            reward += self.rewards.piece + self.stack_score() - before;
+        }
+
+        if over {
+            reward += self.rewards.game_over;
+        }
+
+        info.score = self.state.score;
+        info.pieces = self.state.count;
+
+        (self.observe(), reward, over, info)
+    }
+
+    /// The positions the active piece can be placed at, indexed by
+    /// `Action::Place`.
+    pub fn placements(&mut self) -> &[Placement] {
+        let board = &self.state.board;
+        let cells = board.tetromino.cells();
+
+        if self.placements.as_ref().is_none_or(|(at, _)| *at != cells) {
+            self.placements = Some((cells, placements::reachable(board)));
+        }
+
+        self.placements
+            .as_ref()
+            .map_or(&[], |(_, placements)| placements)
+    }
+
+    pub fn state(&self) -> &GameState {
+        &self.state
+    }
+
+    fn done(&self) -> bool {
+        self.state.board.tetromino.is_null() || self.state.board.overlaps()
+    }
+
+    fn draw(&mut self) -> &'static Piece {
+        self.pieces.random_with(&mut self.rng)
+    }
+
+    /// Keeps a piece waiting in the queue, so the game never runs out.
+    fn refill(&mut self) {
+        if self
+            .state
+            .queue
+            .as_ref()
+            .is_some_and(|queue| queue.is_empty())
+        {
+            let piece = self.draw();
+            self.state.queue.as_mut().unwrap().push_back(piece);
+        }
+    }
+
+    fn stack_score(&self) -> f32 {
+        match &self.rewards.stack {
+            Some(weights) => {
+                let mut board = self.state.board.clone();
+                board.tetromino = Tetromino::null();
+                bot::evaluate(&board, weights)
+            }
+            None => 0.0,
+        }
+    }
+
+    fn info(&self) -> Info {
+        Info {
+            score: self.state.score,
+            pieces: self.state.count,
+            ..Info::default()
+        }
+    }
+
+    fn observe(&self) -> Observation {
+        let board = &self.state.board;
+        let active = board.tetromino.cells();
+
+        let grid = |filled: &dyn Fn(usize, usize) -> bool| -> Vec<Vec<bool>> {
+            inner(board)
+                .map(|i| inner_columns(board).map(|j| filled(i, j)).collect())
+                .collect()
+        };
+
+        let queue = [&board.tetromino, &self.state.next]
+            .iter()
+            .filter_map(|tetromino| tetromino.piece())
+            .filter_map(|piece| {
+                self.pieces
+                    .pieces
+                    .iter()
+                    .position(|other| std::ptr::eq(other, piece))
+            })
+            .collect();
+
+        Observation {
+            board: grid(&|i, j| board.matrix[i][j].is_filled()),
+            piece: grid(&|i, j| active.contains(&(i as isize, j as isize))),
+            queue,
+        }
+    }
+}
+
+fn inner(board: &Board) -> std::ops::Range<usize> {
+    1..board.matrix.len() - 1
+}
+
+fn inner_columns(board: &Board) -> std::ops::Range<usize> {
+    1..board.matrix[0].len() - 1
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board::Cell;
+    use crate::pieces;
+
+    fn environment() -> Environment {
+        Environment::new(pieces::default_set(), Rewards::default())
+    }
+
+    #[test]
+    fn replays_episodes_with_the_same_seed() {
+        let actions = [
+            Action::Move(Movement::Left),
+            Action::Place(3),
+            Action::Move(Movement::RotateRight),
+            Action::Place(0),
+            Action::Place(7),
+            Action::Move(Movement::Down),
+        ];
+
+        let [first, second] = [environment(), environment()].map(|mut environment| {
+            let start = environment.reset(7);
+            let steps: Vec<_> = (0..5)
+                .flat_map(|_| actions)
+                .map(|action| environment.step(action))
+                .collect();
+
+            (start, steps)
+        });
+
+        assert_eq!(first, second);
+    }
+
+    #[test]
+    fn rewards_line_clears() {
+        let mut environment = environment();
+        environment.reset(0);
+
+        // Fill the bottom row but for four cells on the left.
+        let board = &mut environment.state.board;
+        let bottom = board.matrix.len() - 2;
+        let cols = board.matrix[0].len() - 1;
+
+        for j in 5..cols {
+            board.matrix[bottom][j] = Cell::Garbage;
+        }
+
+        board.tetromino = Tetromino::new(pieces::default_set().get("I").unwrap(), 0, 0).at_spawn();
+        let row = bottom as isize;
+
+        let index = environment
+            .placements()
+            .iter()
+            .position(|placement| {
+                let mut cells = placement.tetromino.cells();
+                cells.sort();
+                cells == [(row, 1), (row, 2), (row, 3), (row, 4)]
+            })
+            .expect("I fits the gap");
+
+        let (observation, reward, done, info) = environment.step(Action::Place(index));
+
+        assert!(info.locked);
+        assert_eq!(info.lines, 1);
+        assert_eq!(reward, environment.rewards.lines[0]);
+        assert!(!done);
+        assert!(observation
+            .board
+            .last()
+            .unwrap()
+            .iter()
+            .all(|&filled| !filled));
+    }
+
+    #[test]
+    fn ends_the_episode_on_top_out() {
+        let rewards = Rewards {
+            piece: 0.5,
+            game_over: -10.0,
+            ..Rewards::default()
+        };
+        let mut environment = Environment::new(pieces::default_set(), rewards);
+        environment.reset(3);
+
+        let mut last = None;
+
+        for _ in 0..200 {
+            let (_, reward, done, info) = environment.step(Action::Place(0));
+
+            if done {
+                last = Some((reward, info));
+                break;
+            }
+
+            assert_eq!(reward, 0.5 + clear_reward(&environment.rewards, info.lines));
+        }
+
+        let (reward, info) = last.expect("stacking in one place tops out");
+        assert!(info.locked);
+        assert_eq!(
+            reward,
+            0.5 - 10.0 + clear_reward(&environment.rewards, info.lines)
+        );
+
+        let (_, reward, done, _) = environment.step(Action::Place(0));
+        assert!(done);
+        assert_eq!(reward, 0.0);
+    }
+
+    fn clear_reward(rewards: &Rewards, lines: usize) -> f32 {
+        match lines {
+            0 => 0.0,
+            lines => rewards.lines[lines.min(4) - 1],
+        }
+    }
+
+    #[test]
+    fn flags_actions_that_cannot_be_taken() {
+        let mut environment = environment();
+        let start = environment.reset(0);
+
+        let (observation, reward, done, info) = environment.step(Action::Place(10_000));
+        assert!(info.invalid);
+        assert_eq!((observation, reward, done), (start, 0.0, false));
+
+        let mut moves = 0;
+
+        loop {
+            let (observation, _, _, info) = environment.step(Action::Move(Movement::Left));
+
+            if info.invalid {
+                // Against the wall: nothing moved.
+                let (again, _, _, _) = environment.step(Action::Move(Movement::Left));
+                assert_eq!(observation, again);
+                break;
+            }
+
+            moves += 1;
+            assert!(moves < 20, "piece never reached the wall");
+        }
+
+        assert!(moves > 0);
+
+        let (_, _, _, info) = environment.step(Action::Move(Movement::Down));
+        assert!(!info.invalid);
+    }
+}
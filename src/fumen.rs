@@ -0,0 +1,1213 @@
+use std::fmt;
+use std::io::Read;
+
+use crate::board::{Board, Cell};
+use crate::pieces::{self, Piece};
+use crate::tetromino::Tetromino;
+
+const PREFIX: &str = "v115@";
+const TABLE: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+const ASCII: &[u8] = b" !\"#$%&'()*+,-./0123456789:;<=>?@ABCDEFGHIJKLMNOPQRSTUVWXYZ[\\]^_`abcdefghijklmnopqrstuvwxyz{|}~";
+
+const WIDTH: usize = 10;
+const HEIGHT: usize = 23;
+/// The visible field plus the garbage row below it.
+const BLOCKS: usize = WIDTH * (HEIGHT + 1);
+
+/// Fumen piece numbers, after `0` for empty cells. `8` is gray.
+const NAMES: [&str; 7] = ["I", "L", "O", "Z", "T", "J", "S"];
+const GRAY: u8 = 8;
+
+/// Fumen's rotations in the order they are encoded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Rotation {
+    Reverse,
+    Right,
+    Spawn,
+    Left,
+}
+
+const ROTATIONS: [Rotation; 4] = [
+    Rotation::Reverse,
+    Rotation::Right,
+    Rotation::Spawn,
+    Rotation::Left,
+];
+
+/// The rotations tried when encoding a piece that several fit, in the order
+/// fumen's editor prefers them.
+const PREFERRED: [Rotation; 4] = [
+    Rotation::Spawn,
+    Rotation::Right,
+    Rotation::Reverse,
+    Rotation::Left,
+];
+
+/// One page of a fumen, the format the community shares boards in. Fumen
+/// fields are 10 columns wide and the page's placement, if any, is the
+/// board's active piece.
+#[derive(Clone)]
+pub struct Page {
+    pub board: Board,
+    pub comment: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FumenError {
+    UnsupportedVersion,
+    InvalidChar(char),
+    UnexpectedEnd,
+    Invalid(String),
+}
+
+impl fmt::Display for FumenError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FumenError::UnsupportedVersion => write!(f, "only v115 fumen data is supported"),
+            FumenError::InvalidChar(c) => write!(f, "'{}' is not valid fumen data", c),
+            FumenError::UnexpectedEnd => write!(f, "fumen data ends in the middle of a page"),
+            FumenError::Invalid(reason) => write!(f, "{}", reason),
+        }
+    }
+}
+
+impl std::error::Error for FumenError {}
+
+/// A piece placement as fumen stores it: the piece number, its rotation and
+/// the position of its rotation centre, with `y` counting up from the floor.
+#[derive(Debug, Clone, Copy)]
+struct Operation {
+    kind: u8,
+    rotation: Rotation,
+    x: isize,
+    y: isize,
+}
+
+impl Operation {
+    fn cells(&self) -> Vec<(isize, isize)> {
+        let blocks: [(isize, isize); 4] = match self.kind {
+            1 => [(0, 0), (-1, 0), (1, 0), (2, 0)],
+            2 => [(0, 0), (-1, 0), (1, 0), (1, 1)],
+            3 => [(0, 0), (1, 0), (0, 1), (1, 1)],
+            4 => [(0, 0), (1, 0), (0, 1), (-1, 1)],
+            5 => [(0, 0), (-1, 0), (1, 0), (0, 1)],
+            6 => [(0, 0), (-1, 0), (1, 0), (-1, 1)],
+            _ => [(0, 0), (-1, 0), (0, 1), (1, 1)],
+        };
+
+        blocks
+            .iter()
+            .map(|&(x, y)| match self.rotation {
+                Rotation::Spawn => (x, y),
+                Rotation::Right => (y, -x),
+                Rotation::Reverse => (-x, -y),
+                Rotation::Left => (-y, x),
+            })
+            .map(|(x, y)| (self.x + x, self.y + y))
+            .collect()
+    }
+
+    /// Fumen stores some rotations of O, I, S and Z relative to a different
+    /// centre than the one their cells are built around. Decoding adds this
+    /// to the stored position and encoding takes it away.
+    fn stored_offset(&self) -> (isize, isize) {
+        match (self.kind, self.rotation) {
+            (3, Rotation::Left) => (1, -1),
+            (3, Rotation::Reverse) => (1, 0),
+            (3, Rotation::Spawn) => (0, -1),
+            (1, Rotation::Reverse) => (1, 0),
+            (1, Rotation::Left) => (0, -1),
+            (7, Rotation::Spawn) => (0, -1),
+            (7, Rotation::Right) => (-1, 0),
+            (4, Rotation::Spawn) => (0, -1),
+            (4, Rotation::Left) => (1, 0),
+            _ => (0, 0),
+        }
+    }
+}
+
+/// The 10 column field, top row first, followed by the garbage row.
+#[derive(Clone, PartialEq, Eq)]
+struct Field([u8; BLOCKS]);
+
+impl Field {
+    fn index(x: isize, y: isize) -> Option<usize> {
+        if !(0..WIDTH as isize).contains(&x) || !(-1..HEIGHT as isize).contains(&y) {
+            return None;
+        }
+
+        Some((HEIGHT - 1 - y as usize) * WIDTH + x as usize)
+    }
+
+    fn put(&mut self, operation: &Operation) {
+        for (x, y) in operation.cells() {
+            if let Some(index) = Field::index(x, y) {
+                self.0[index] = operation.kind;
+            }
+        }
+    }
+
+    fn clear_lines(&mut self) {
+        let rows: Vec<&[u8]> = self.0[..HEIGHT * WIDTH]
+            .chunks(WIDTH)
+            .filter(|row| row.contains(&0))
+            .collect();
+
+        let mut cleared = [0; BLOCKS];
+        let start = (HEIGHT - rows.len()) * WIDTH;
+
+        for (n, row) in rows.iter().enumerate() {
+            cleared[start + n * WIDTH..start + (n + 1) * WIDTH].copy_from_slice(row);
+        }
+
+        cleared[HEIGHT * WIDTH..].copy_from_slice(&self.0[HEIGHT * WIDTH..]);
+        self.0 = cleared;
+    }
+
+    /// Pushes the field up, copying the garbage row in at the bottom.
+    fn rise(&mut self) {
+        self.0.copy_within(WIDTH..HEIGHT * WIDTH, 0);
+        self.0.copy_within(HEIGHT * WIDTH.., (HEIGHT - 1) * WIDTH);
+    }
+
+    fn mirror(&mut self) {
+        for row in self.0[..HEIGHT * WIDTH].chunks_mut(WIDTH) {
+            row.reverse();
+        }
+    }
+
+    fn to_board(&self) -> Board {
+        let mut board = Board::new(HEIGHT + 2, WIDTH + 2);
+        board.tetromino = Tetromino::null();
+
+        for y in 0..HEIGHT as isize {
+            for x in 0..WIDTH as isize {
+                let kind = self.0[Field::index(x, y).unwrap_or_default()];
+                board.matrix[HEIGHT - y as usize][x as usize + 1] = match kind {
+                    0 => Cell::Empty,
+                    kind => piece(kind).map_or(Cell::Garbage, Cell::Piece),
+                };
+            }
+        }
+
+        board
+    }
+
+    fn from_board(board: &Board) -> Result<Field, FumenError> {
+        let (rows, cols) = interior(board)?;
+        let mut field = Field([0; BLOCKS]);
+
+        for i in 1..=rows {
+            for j in 1..=cols {
+                let y = (rows - i) as isize;
+                let index = Field::index(j as isize - 1, y).unwrap_or_default();
+
+                field.0[index] = match board.matrix[i][j] {
+                    Cell::Empty => 0,
+                    Cell::Piece(piece) => kind(piece).unwrap_or(GRAY),
+                    Cell::Garbage | Cell::Wall => GRAY,
+                };
+            }
+        }
+
+        Ok(field)
+    }
+}
+
+/// The fumen piece number of one of the standard tetrominoes.
+fn kind(piece: &Piece) -> Option<u8> {
+    NAMES
+        .iter()
+        .position(|name| *name == piece.name)
+        .map(|index| index as u8 + 1)
+}
+
+/// The default set's piece for a fumen piece number, if it has one.
+fn piece(kind: u8) -> Option<&'static Piece> {
+    let name = NAMES.get((kind as usize).checked_sub(1)?)?;
+
+    pieces::default_set().get(name)
+}
+
+fn interior(board: &Board) -> Result<(usize, usize), FumenError> {
+    let rows = board.matrix.len().saturating_sub(2);
+    let cols = board.matrix.first().map_or(0, Vec::len).saturating_sub(2);
+
+    if cols != WIDTH || rows > HEIGHT {
+        return Err(FumenError::Invalid(format!(
+            "fumen boards are {} columns wide and at most {} rows tall, not {}x{}",
+            WIDTH, HEIGHT, cols, rows
+        )));
+    }
+
+    Ok((rows, cols))
+}
+
+fn operation_from_board(board: &Board) -> Result<Option<Operation>, FumenError> {
+    let Some(piece) = board.tetromino.piece() else {
+        return Ok(None);
+    };
+
+    let (rows, _) = interior(board)?;
+    let kind = kind(piece).ok_or_else(|| {
+        FumenError::Invalid(format!("piece \"{}\" has no fumen equivalent", piece.name))
+    })?;
+
+    let mut cells: Vec<(isize, isize)> = board
+        .tetromino
+        .cells()
+        .iter()
+        .map(|&(i, j)| (j - 1, rows as isize - i))
+        .collect();
+    cells.sort();
+
+    for rotation in PREFERRED {
+        let mut operation = Operation {
+            kind,
+            rotation,
+            x: 0,
+            y: 0,
+        };
+        let mut blocks = operation.cells();
+        blocks.sort();
+
+        operation.x = cells[0].0 - blocks[0].0;
+        operation.y = cells[0].1 - blocks[0].1;
+
+        let mut placed = operation.cells();
+        placed.sort();
+
+        if placed == cells {
+            return Ok(Some(operation));
+        }
+    }
+
+    Err(FumenError::Invalid(format!(
+        "active piece \"{}\" is not in a fumen orientation",
+        piece.name
+    )))
+}
+
+fn place(board: &mut Board, operation: &Operation) -> Result<(), FumenError> {
+    let piece = piece(operation.kind).ok_or_else(|| {
+        FumenError::Invalid("the default piece set has no standard tetrominoes".into())
+    })?;
+
+    let cells: Vec<(isize, isize)> = operation
+        .cells()
+        .iter()
+        .map(|&(x, y)| (HEIGHT as isize - y, x + 1))
+        .collect();
+
+    board.tetromino = Tetromino::placed(piece, &cells).ok_or_else(|| {
+        FumenError::Invalid(format!(
+            "piece \"{}\" has no orientation matching the placement",
+            piece.name
+        ))
+    })?;
+
+    Ok(())
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl Reader<'_> {
+    fn poll(&mut self, digits: usize) -> Result<usize, FumenError> {
+        let mut value = 0;
+
+        for n in 0..digits {
+            let &char = self
+                .data
+                .get(self.position)
+                .ok_or(FumenError::UnexpectedEnd)?;
+            let digit = TABLE
+                .iter()
+                .position(|&c| c == char)
+                .ok_or(FumenError::InvalidChar(char as char))?;
+
+            value += digit * 64usize.pow(n as u32);
+            self.position += 1;
+        }
+
+        Ok(value)
+    }
+
+    fn is_empty(&self) -> bool {
+        self.position >= self.data.len()
+    }
+}
+
+fn push(buffer: &mut Vec<u8>, mut value: usize, digits: usize) {
+    for _ in 0..digits {
+        buffer.push(TABLE[value % 64]);
+        value /= 64;
+    }
+}
+
+/// Decodes a fumen string, with or without the URL in front of it.
+pub fn decode(fumen: &str) -> Result<Vec<Page>, FumenError> {
+    let start = fumen.find(PREFIX).ok_or(FumenError::UnsupportedVersion)?;
+    let data: Vec<u8> = fumen[start + PREFIX.len()..]
+        .trim()
+        .bytes()
+        .filter(|&c| c != b'?')
+        .collect();
+
+    let mut reader = Reader {
+        data: &data,
+        position: 0,
+    };
+    let mut pages = vec![];
+    let mut previous = Field([0; BLOCKS]);
+    let mut comment = String::new();
+    let mut skip = 0;
+
+    while !reader.is_empty() {
+        let mut field = previous.clone();
+
+        if skip > 0 {
+            skip -= 1;
+        } else {
+            let mut index = 0;
+            let mut changed = true;
+
+            while index < BLOCKS {
+                let value = reader.poll(2)?;
+                let diff = value / BLOCKS;
+                let count = value % BLOCKS + 1;
+
+                if diff > 16 || index + count > BLOCKS {
+                    return Err(FumenError::Invalid("field data is out of range".into()));
+                }
+
+                if diff == 8 && count == BLOCKS {
+                    changed = false;
+                }
+
+                for cell in &mut field.0[index..index + count] {
+                    *cell = (*cell as isize + diff as isize - 8).clamp(0, GRAY as isize) as u8;
+                }
+
+                index += count;
+            }
+
+            if !changed {
+                skip = reader.poll(1)?;
+            }
+        }
+
+        let mut value = reader.poll(3)?;
+        let kind = (value % 8) as u8;
+        value /= 8;
+        let rotation = ROTATIONS[value % 4];
+        value /= 4;
+        let location = value % BLOCKS;
+        value /= BLOCKS;
+        let rise = value % 2 == 1;
+        let mirror = (value / 2) % 2 == 1;
+        let has_comment = (value / 8) % 2 == 1;
+        let lock = (value / 16) % 2 == 0;
+
+        let operation = (kind != 0).then(|| {
+            let mut operation = Operation {
+                kind,
+                rotation,
+                x: (location % WIDTH) as isize,
+                y: HEIGHT as isize - (location / WIDTH) as isize - 1,
+            };
+            let (dx, dy) = operation.stored_offset();
+            operation.x += dx;
+            operation.y += dy;
+
+            operation
+        });
+
+        if has_comment {
+            let length = reader.poll(2)?;
+            let mut escaped = String::new();
+
+            for _ in 0..length.div_ceil(4) {
+                let mut value = reader.poll(5)?;
+
+                for _ in 0..4 {
+                    let char = ASCII.get(value % 96).ok_or_else(|| {
+                        FumenError::Invalid("comment has an invalid character".into())
+                    })?;
+                    escaped.push(*char as char);
+                    value /= 96;
+                }
+            }
+
+            escaped.truncate(length);
+            comment = unescape(&escaped);
+        }
+
+        let mut board = field.to_board();
+
+        if let Some(operation) = &operation {
+            place(&mut board, operation)?;
+        }
+
+        pages.push(Page {
+            board,
+            comment: comment.clone(),
+        });
+
+        if lock {
+            if let Some(operation) = &operation {
+                field.put(operation);
+            }
+
+            field.clear_lines();
+
+            if rise {
+                field.rise();
+            }
+
+            if mirror {
+                field.mirror();
+            }
+        }
+
+        previous = field;
+    }
+
+    if pages.is_empty() {
+        return Err(FumenError::UnexpectedEnd);
+    }
+
+    Ok(pages)
+}
+
+/// Encodes pages into a fumen string. Every board must be 10 columns wide
+/// and its active piece, if any, one of the standard tetrominoes. Pieces are
+/// locked into the field between pages.
+pub fn encode(pages: &[Page]) -> Result<String, FumenError> {
+    let mut buffer = vec![];
+    let mut previous = Field([0; BLOCKS]);
+    let mut previous_comment = String::new();
+    let mut repeat: Option<usize> = None;
+
+    for (n, page) in pages.iter().enumerate() {
+        let mut field = Field::from_board(&page.board)?;
+
+        let mut runs: Vec<(usize, usize)> = vec![];
+
+        for (current, before) in field.0.iter().zip(previous.0.iter()) {
+            let diff = *current as usize + 8 - *before as usize;
+
+            match runs.last_mut() {
+                Some((last, count)) if *last == diff => *count += 1,
+                _ => runs.push((diff, 1)),
+            }
+        }
+
+        let unchanged = runs == [(8, BLOCKS)];
+
+        match repeat {
+            Some(index) if unchanged && buffer[index] != TABLE[63] => {
+                let count = TABLE.iter().position(|&c| c == buffer[index]).unwrap_or(0);
+                buffer[index] = TABLE[count + 1];
+            }
+            _ => {
+                for (diff, count) in runs {
+                    push(&mut buffer, diff * BLOCKS + count - 1, 2);
+                }
+
+                repeat = unchanged.then(|| {
+                    push(&mut buffer, 0, 1);
+                    buffer.len() - 1
+                });
+            }
+        }
+
+        let operation = operation_from_board(&page.board)?;
+        let has_comment = page.comment != previous_comment;
+
+        let (kind, rotation, location) = match &operation {
+            Some(operation) => {
+                let (dx, dy) = operation.stored_offset();
+                let (x, y) = (operation.x - dx, operation.y - dy);
+
+                if Field::index(x, y).is_none() || y < 0 {
+                    return Err(FumenError::Invalid(
+                        "active piece is outside the fumen field".into(),
+                    ));
+                }
+
+                let location = (HEIGHT as isize - y - 1) as usize * WIDTH + x as usize;
+                let rotation = ROTATIONS
+                    .iter()
+                    .position(|&r| r == operation.rotation)
+                    .unwrap_or_default();
+
+                (operation.kind as usize, rotation, location)
+            }
+            None => (0, 0, 0),
+        };
+
+        // From the top bit down: not locked, comment, colour, mirror and rise.
+        let flags = (has_comment as usize) << 3 | ((n == 0) as usize) << 2;
+        let value = ((flags * BLOCKS + location) * 4 + rotation) * 8 + kind;
+        push(&mut buffer, value, 3);
+
+        if has_comment {
+            let escaped = escape(&page.comment);
+            let length = escaped.len().min(4095);
+            push(&mut buffer, length, 2);
+
+            for chunk in escaped.as_bytes()[..length].chunks(4) {
+                let mut value = 0;
+
+                for &char in chunk.iter().rev() {
+                    value = value * 96 + ASCII.iter().position(|&c| c == char).unwrap_or(0);
+                }
+
+                push(&mut buffer, value, 5);
+            }
+
+            previous_comment = page.comment.clone();
+        }
+
+        if let Some(operation) = &operation {
+            field.put(operation);
+        }
+
+        field.clear_lines();
+        previous = field;
+    }
+
+    let data = String::from_utf8(buffer).unwrap_or_default();
+
+    Ok(format!("{}{}", PREFIX, split(&data)))
+}
+
+/// Fumen puts a `?` after the first 42 characters and every 47 after that.
+fn split(data: &str) -> String {
+    if data.len() < 41 {
+        return data.into();
+    }
+
+    let (head, tail) = data.split_at(42.min(data.len()));
+    let mut chunks = vec![head];
+    let mut rest = tail;
+
+    while !rest.is_empty() {
+        let (chunk, remaining) = rest.split_at(47.min(rest.len()));
+        chunks.push(chunk);
+        rest = remaining;
+    }
+
+    chunks.join("?")
+}
+
+/// JavaScript's `escape`, which fumen applies to comments.
+fn escape(text: &str) -> String {
+    let mut escaped = String::new();
+
+    for unit in text.encode_utf16() {
+        match char::from_u32(unit as u32) {
+            Some(c) if c.is_ascii_alphanumeric() || "@*_+-./".contains(c) => escaped.push(c),
+            _ if unit < 256 => escaped.push_str(&format!("%{:02X}", unit)),
+            _ => escaped.push_str(&format!("%u{:04X}", unit)),
+        }
+    }
+
+    escaped
+}
+
+fn unescape(text: &str) -> String {
+    let mut units = vec![];
+    let mut chars = text.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            units.extend(c.encode_utf16(&mut [0; 2]).iter());
+            continue;
+        }
+
+        let (prefix, digits) = if chars.peek() == Some(&'u') {
+            chars.next();
+            ("%u", 4)
+        } else {
+            ("%", 2)
+        };
+
+        let hex: String = chars.by_ref().take(digits).collect();
+
+        match u16::from_str_radix(&hex, 16) {
+            Ok(unit) if hex.len() == digits => units.push(unit),
+            _ => units.extend(prefix.encode_utf16().chain(hex.encode_utf16())),
+        }
+    }
+
+    String::from_utf16_lossy(&units)
+}
+
+/// Writes pages in the text format, one board diagram per page with its
+/// comment on a `//` line above it, pages separated by a blank line.
+pub fn to_text(pages: &[Page]) -> String {
+    pages
+        .iter()
+        .map(|page| {
+            let mut text = String::new();
+
+            if !page.comment.is_empty() {
+                text.push_str(&format!("// {}\n", page.comment));
+            }
+
+            text.push_str(&page.board.to_str());
+            text
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Reads pages written by `to_text`.
+pub fn from_text(text: &str) -> Result<Vec<Page>, FumenError> {
+    let mut pages = vec![];
+
+    for (n, block) in text.split("\n\n").enumerate() {
+        let mut comment = String::new();
+        let mut diagram = String::new();
+
+        for line in block.lines().map(str::trim) {
+            match line.strip_prefix("//") {
+                Some(text) => comment = text.trim().into(),
+                None => {
+                    diagram.push_str(line);
+                    diagram.push('\n');
+                }
+            }
+        }
+
+        if diagram.trim().is_empty() {
+            continue;
+        }
+
+        let board = diagram
+            .parse()
+            .map_err(|e| FumenError::Invalid(format!("page {}: {}", n + 1, e)))?;
+
+        pages.push(Page { board, comment });
+    }
+
+    Ok(pages)
+}
+
+/// Runs a `fumen` subcommand of the binaries: `decode <fumen>` converts a
+/// fumen to text pages and `encode` converts text pages read from `input`
+/// to a fumen. Returns what to print, or `None` if `args` is not one of
+/// them.
+pub fn command(args: &[&str], input: &mut impl Read) -> Option<Result<String, FumenError>> {
+    match args {
+        ["decode", data] => Some(decode(data).map(|pages| to_text(&pages))),
+        ["encode"] => {
+            let mut text = String::new();
+
+            if let Err(e) = input.read_to_string(&mut text) {
+                return Some(Err(FumenError::Invalid(format!(
+                    "could not read pages: {}",
+                    e
+                ))));
+            }
+
+            Some(
+                from_text(&text)
+                    .and_then(|pages| encode(&pages))
+                    .map(|data| data + "\n"),
+            )
+        }
+        _ => None,
+    }
+}
+
+/// The board of a page cut down to `rows` rows, walls included, along with
+/// the pieces placed on it and every later page, so it can be played as
+/// practice. Fails if blocks would be cut off the top.
+pub fn practice(
+    pages: &[Page],
+    page: usize,
+    rows: usize,
+) -> Result<(Board, Vec<&'static Piece>), FumenError> {
+    let Some(start) = pages.get(page) else {
+        return Err(FumenError::Invalid(format!(
+            "there is no page {}, the fumen has {}",
+            page + 1,
+            pages.len()
+        )));
+    };
+
+    let mut board = start.board.clone();
+    let cut = board.matrix.len().saturating_sub(rows);
+
+    let fits = board.matrix[1..=cut]
+        .iter()
+        .all(|row| row[1..row.len() - 1].iter().all(|cell| !cell.is_filled()));
+
+    if !fits {
+        return Err(FumenError::Invalid(format!(
+            "page {} is too tall for a {} row board",
+            page + 1,
+            rows
+        )));
+    }
+
+    board.matrix.drain(1..=cut);
+    board.tetromino = Tetromino::null();
+
+    let queue = pages[page..]
+        .iter()
+        .filter_map(|page| page.board.tetromino.piece())
+        .collect();
+
+    Ok((board, queue))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io;
+
+    use super::*;
+
+    /// A fumen, how `encode` writes it back, the piece and its cells.
+    type Placement = (
+        &'static str,
+        &'static str,
+        &'static str,
+        [(isize, isize); 4],
+    );
+
+    /// Every piece in every rotation, centred on column 4 of row 2, as
+    /// fumen's editor writes it and as `encode` writes the same cells back,
+    /// since O, I, S and Z have several rotations that cover them.
+    const PLACEMENTS: [Placement; 28] = [
+        (
+            "v115@vhARGJ",
+            "v115@vhARGJ",
+            "I",
+            [(3, 2), (4, 2), (5, 2), (6, 2)],
+        ),
+        (
+            "v115@vhAJGJ",
+            "v115@vhAJGJ",
+            "I",
+            [(4, 0), (4, 1), (4, 2), (4, 3)],
+        ),
+        (
+            "v115@vhAhFJ",
+            "v115@vhAxFJ",
+            "I",
+            [(2, 2), (3, 2), (4, 2), (5, 2)],
+        ),
+        (
+            "v115@vhAZBJ",
+            "v115@vhAJBJ",
+            "I",
+            [(4, 1), (4, 2), (4, 3), (4, 4)],
+        ),
+        (
+            "v115@vhASGJ",
+            "v115@vhASGJ",
+            "L",
+            [(3, 2), (4, 2), (5, 2), (5, 3)],
+        ),
+        (
+            "v115@vhAKGJ",
+            "v115@vhAKGJ",
+            "L",
+            [(4, 1), (4, 2), (4, 3), (5, 1)],
+        ),
+        (
+            "v115@vhACGJ",
+            "v115@vhACGJ",
+            "L",
+            [(3, 1), (3, 2), (4, 2), (5, 2)],
+        ),
+        (
+            "v115@vhAaGJ",
+            "v115@vhAaGJ",
+            "L",
+            [(3, 3), (4, 1), (4, 2), (4, 3)],
+        ),
+        (
+            "v115@vhATBJ",
+            "v115@vhATBJ",
+            "O",
+            [(4, 2), (4, 3), (5, 2), (5, 3)],
+        ),
+        (
+            "v115@vhALGJ",
+            "v115@vhATGJ",
+            "O",
+            [(4, 1), (4, 2), (5, 1), (5, 2)],
+        ),
+        (
+            "v115@vhAjFJ",
+            "v115@vhAzFJ",
+            "O",
+            [(3, 1), (3, 2), (4, 1), (4, 2)],
+        ),
+        (
+            "v115@vhA7AJ",
+            "v115@vhAzAJ",
+            "O",
+            [(3, 2), (3, 3), (4, 2), (4, 3)],
+        ),
+        (
+            "v115@vhAUBJ",
+            "v115@vhAUBJ",
+            "Z",
+            [(3, 3), (4, 2), (4, 3), (5, 2)],
+        ),
+        (
+            "v115@vhAMGJ",
+            "v115@vhAMGJ",
+            "Z",
+            [(4, 1), (4, 2), (5, 2), (5, 3)],
+        ),
+        (
+            "v115@vhAEGJ",
+            "v115@vhAUGJ",
+            "Z",
+            [(3, 2), (4, 1), (4, 2), (5, 1)],
+        ),
+        (
+            "v115@vhA8FJ",
+            "v115@vhAsFJ",
+            "Z",
+            [(3, 1), (3, 2), (4, 2), (4, 3)],
+        ),
+        (
+            "v115@vhAVGJ",
+            "v115@vhAVGJ",
+            "T",
+            [(3, 2), (4, 2), (4, 3), (5, 2)],
+        ),
+        (
+            "v115@vhANGJ",
+            "v115@vhANGJ",
+            "T",
+            [(4, 1), (4, 2), (4, 3), (5, 2)],
+        ),
+        (
+            "v115@vhAFGJ",
+            "v115@vhAFGJ",
+            "T",
+            [(3, 2), (4, 1), (4, 2), (5, 2)],
+        ),
+        (
+            "v115@vhAdGJ",
+            "v115@vhAdGJ",
+            "T",
+            [(3, 2), (4, 1), (4, 2), (4, 3)],
+        ),
+        (
+            "v115@vhAWGJ",
+            "v115@vhAWGJ",
+            "J",
+            [(3, 2), (3, 3), (4, 2), (5, 2)],
+        ),
+        (
+            "v115@vhAOGJ",
+            "v115@vhAOGJ",
+            "J",
+            [(4, 1), (4, 2), (4, 3), (5, 3)],
+        ),
+        (
+            "v115@vhAGGJ",
+            "v115@vhAGGJ",
+            "J",
+            [(3, 2), (4, 2), (5, 1), (5, 2)],
+        ),
+        (
+            "v115@vhAeGJ",
+            "v115@vhAeGJ",
+            "J",
+            [(3, 1), (4, 1), (4, 2), (4, 3)],
+        ),
+        (
+            "v115@vhAXBJ",
+            "v115@vhAXBJ",
+            "S",
+            [(3, 2), (4, 2), (4, 3), (5, 3)],
+        ),
+        (
+            "v115@vhAvGJ",
+            "v115@vhAvGJ",
+            "S",
+            [(4, 2), (4, 3), (5, 1), (5, 2)],
+        ),
+        (
+            "v115@vhAHGJ",
+            "v115@vhAXGJ",
+            "S",
+            [(3, 1), (4, 1), (4, 2), (5, 2)],
+        ),
+        (
+            "v115@vhAfGJ",
+            "v115@vhAPGJ",
+            "S",
+            [(3, 2), (3, 3), (4, 1), (4, 2)],
+        ),
+    ];
+
+    /// The active piece's cells as fumen `(x, y)`, `y` up from the floor.
+    fn active(page: &Page) -> Vec<(isize, isize)> {
+        let mut cells: Vec<(isize, isize)> = page
+            .board
+            .tetromino
+            .cells()
+            .iter()
+            .map(|&(i, j)| (j - 1, HEIGHT as isize - i))
+            .collect();
+        cells.sort();
+        cells
+    }
+
+    #[test]
+    fn empty_field() {
+        let pages = decode("v115@vhAAgH").unwrap();
+
+        assert_eq!(pages.len(), 1);
+        assert!(pages[0].board.tetromino.is_null());
+        assert_eq!(encode(&pages).unwrap(), "v115@vhAAgH");
+    }
+
+    #[test]
+    fn decodes_every_piece_and_rotation() {
+        for (fumen, _, name, cells) in PLACEMENTS {
+            let pages = decode(fumen).unwrap();
+            let piece = pages[0].board.tetromino.piece().unwrap();
+
+            assert_eq!(piece.name, name, "{}", fumen);
+            assert_eq!(active(&pages[0]), cells, "{}", fumen);
+        }
+    }
+
+    #[test]
+    fn encodes_every_piece_and_rotation() {
+        for (fumen, encoded, _, _) in PLACEMENTS {
+            let pages = decode(fumen).unwrap();
+
+            assert_eq!(encode(&pages).unwrap(), encoded, "{}", fumen);
+        }
+    }
+
+    #[test]
+    fn o_against_the_walls() {
+        for (fumen, cells) in [
+            ("v115@vhATJJ", [(0, 0), (0, 1), (1, 0), (1, 1)]),
+            ("v115@vhATNJ", [(8, 0), (8, 1), (9, 0), (9, 1)]),
+        ] {
+            let pages = decode(fumen).unwrap();
+
+            assert_eq!(active(&pages[0]), cells, "{}", fumen);
+            assert_eq!(encode(&pages).unwrap(), fumen);
+        }
+    }
+
+    /// The field solution-finder's documentation uses for perfect clears:
+    /// the left six columns of the bottom four rows filled with gray.
+    const PERFECT_CLEAR: &str = "v115@9gF8DeF8DeF8DeF8NeAgH";
+
+    fn page(board: Board, comment: &str) -> Page {
+        Page {
+            board,
+            comment: comment.into(),
+        }
+    }
+
+    /// `board` with `name` placed on cells given as fumen `(x, y)`.
+    fn with_piece(mut board: Board, name: &str, cells: &[(isize, isize)]) -> Board {
+        let piece = pieces::default_set().get(name).unwrap();
+        let cells: Vec<(isize, isize)> = cells
+            .iter()
+            .map(|&(x, y)| (HEIGHT as isize - y, x + 1))
+            .collect();
+
+        board.tetromino = Tetromino::placed(piece, &cells).unwrap();
+        board
+    }
+
+    fn assert_same_pages(actual: &[Page], expected: &[Page]) {
+        assert_eq!(actual.len(), expected.len());
+
+        for (n, (actual, expected)) in actual.iter().zip(expected).enumerate() {
+            assert_eq!(actual.board.matrix, expected.board.matrix, "page {}", n + 1);
+            assert_eq!(active(actual), active(expected), "page {}", n + 1);
+            assert_eq!(
+                actual.board.tetromino.piece().map(|piece| &piece.name),
+                expected.board.tetromino.piece().map(|piece| &piece.name),
+                "page {}",
+                n + 1
+            );
+            assert_eq!(actual.comment, expected.comment, "page {}", n + 1);
+        }
+    }
+
+    #[test]
+    fn decodes_a_known_field() {
+        let pages = decode(PERFECT_CLEAR).unwrap();
+        let board = &pages[0].board;
+
+        assert_eq!(pages.len(), 1);
+        assert!(board.tetromino.is_null());
+        assert_eq!(pages[0].comment, "");
+
+        for y in 0..HEIGHT {
+            let row = &board.matrix[HEIGHT - y][1..=WIDTH];
+            let filled: Vec<bool> = row.iter().map(|cell| cell.is_filled()).collect();
+
+            if y < 4 {
+                assert_eq!(
+                    filled,
+                    [true; 6].into_iter().chain([false; 4]).collect::<Vec<_>>()
+                );
+                assert!(row[..6].iter().all(|&cell| cell == Cell::Garbage));
+            } else {
+                assert!(filled.iter().all(|&filled| !filled), "row {}", y);
+            }
+        }
+
+        assert_eq!(encode(&pages).unwrap(), PERFECT_CLEAR);
+    }
+
+    #[test]
+    fn round_trips_several_pages_with_comments() {
+        let field = decode(PERFECT_CLEAR).unwrap().remove(0).board;
+        let opener = with_piece(field.clone(), "I", &[(9, 0), (9, 1), (9, 2), (9, 3)]);
+
+        let mut locked = field.clone();
+        for i in HEIGHT - 3..=HEIGHT {
+            locked.matrix[i][WIDTH] = Cell::Piece(pieces::default_set().get("I").unwrap());
+        }
+
+        let pages = vec![
+            page(opener, "Opener: 100% ação"),
+            page(locked.clone(), "Opener: 100% ação"),
+            page(locked.clone(), "日本語 50%"),
+            page(
+                with_piece(locked, "T", &[(6, 0), (7, 0), (8, 0), (7, 1)]),
+                "",
+            ),
+        ];
+
+        let encoded = encode(&pages).unwrap();
+
+        assert_same_pages(&decode(&encoded).unwrap(), &pages);
+        assert_eq!(encode(&decode(&encoded).unwrap()).unwrap(), encoded);
+    }
+
+    #[test]
+    fn repeats_unchanged_fields() {
+        let empty = decode("v115@vhAAgH").unwrap().remove(0);
+        let pages = vec![empty.clone(), empty.clone(), empty];
+
+        // The field is written once, followed by the number of pages that
+        // repeat it, then each page's piece.
+        let encoded = encode(&pages).unwrap();
+
+        assert_eq!(encoded, "v115@vhCAgHAAAAAA");
+        assert_same_pages(&decode(&encoded).unwrap(), &pages);
+    }
+
+    #[test]
+    fn escapes_comments_like_javascript() {
+        assert_eq!(escape("a-Z_9 100%"), "a-Z_9%20100%25");
+        assert_eq!(escape("ação"), "a%E7%E3o");
+        assert_eq!(escape("日本"), "%u65E5%u672C");
+        assert_eq!(escape("🙂"), "%uD83D%uDE42");
+
+        for text in ["a-Z_9 100%", "ação", "日本", "🙂", "%", "%%41"] {
+            assert_eq!(unescape(&escape(text)), text);
+        }
+
+        assert_eq!(unescape("100%"), "100%");
+        assert_eq!(unescape("%zz%u12"), "%zz%u12");
+    }
+
+    #[test]
+    fn unlocked_pieces_stay_off_the_next_page() {
+        let filled = |page: &Page| {
+            page.board
+                .matrix
+                .iter()
+                .flatten()
+                .filter(|&&cell| cell != Cell::Wall && cell.is_filled())
+                .count()
+        };
+
+        // The same T, with and without the flag that leaves it unlocked.
+        let locked = decode("v115@vhAVGJvhAAAA").unwrap();
+        let unlocked = decode("v115@vhAVGnvhAAAA").unwrap();
+
+        assert_eq!(active(&locked[0]), active(&unlocked[0]));
+        assert_eq!(filled(&locked[1]), 4);
+        assert_eq!(filled(&unlocked[1]), 0);
+    }
+
+    #[test]
+    fn practices_from_a_page() {
+        let field = decode(PERFECT_CLEAR).unwrap().remove(0).board;
+        let pages = vec![
+            page(
+                with_piece(field.clone(), "I", &[(9, 0), (9, 1), (9, 2), (9, 3)]),
+                "",
+            ),
+            page(field.clone(), ""),
+            page(
+                with_piece(field, "T", &[(6, 0), (7, 0), (8, 0), (7, 1)]),
+                "",
+            ),
+        ];
+
+        let (board, queue) = practice(&pages, 0, 20).unwrap();
+        let names: Vec<&str> = queue.iter().map(|piece| piece.name.as_str()).collect();
+
+        assert_eq!(board.matrix.len(), 20);
+        assert!(board.tetromino.is_null());
+        assert_eq!(
+            board.matrix[18][1..=WIDTH],
+            pages[1].board.matrix[HEIGHT][1..=WIDTH]
+        );
+        assert_eq!(names, ["I", "T"]);
+
+        let (_, queue) = practice(&pages, 1, 20).unwrap();
+        assert_eq!(queue.len(), 1);
+
+        assert!(matches!(
+            practice(&pages, 3, 20),
+            Err(FumenError::Invalid(_))
+        ));
+
+        let mut tall = pages[1].board.clone();
+        tall.matrix[2][1] = Cell::Garbage;
+
+        assert!(matches!(
+            practice(&[page(tall, "")], 0, 20),
+            Err(FumenError::Invalid(_))
+        ));
+    }
+
+    #[test]
+    fn runs_the_fumen_commands() {
+        let text = command(&["decode", PERFECT_CLEAR], &mut io::empty())
+            .unwrap()
+            .unwrap();
+        assert_eq!(text, to_text(&decode(PERFECT_CLEAR).unwrap()));
+
+        let data = command(&["encode"], &mut text.as_bytes()).unwrap().unwrap();
+        assert_eq!(data, format!("{}\n", PERFECT_CLEAR));
+
+        assert!(matches!(
+            command(&["encode"], &mut "not a board".as_bytes()),
+            Some(Err(_))
+        ));
+        assert!(command(&["decode"], &mut io::empty()).is_none());
+        assert!(command(&["convert", PERFECT_CLEAR], &mut io::empty()).is_none());
+    }
+}
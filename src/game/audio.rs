@@ -0,0 +1,131 @@
+use std::collections::HashMap;
+
+use bevy::asset::io::file::FileAssetReader;
+use bevy::audio::{PlaybackMode, Volume};
+use bevy::prelude::*;
+
+use super::tetromino::GameEvent;
+use crate::settings::Settings;
+use crate::sounds::{Cue, Sound, SoundPacks};
+
+/// A sound effect waiting to be played, for cues that repeat.
+#[derive(Component)]
+pub struct ScheduledSound {
+    pub path: String,
+    pub timer: Timer,
+}
+
+pub struct AudioHandler;
+
+impl Plugin for AudioHandler {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(load_sound_packs())
+            .add_systems(Update, (apply_volume, play_sounds, play_scheduled_sounds));
+    }
+}
+
+fn load_sound_packs() -> SoundPacks {
+    let dir = FileAssetReader::get_base_path().join("assets/sounds");
+    let (packs, errors) = SoundPacks::load_dir(&dir);
+
+    for e in errors {
+        warn!("Could not load sound pack: {}", e);
+    }
+
+    packs
+}
+
+/// The sounds an event can be given, most specific first. The first one the
+/// pack has a cue for is played.
+fn sounds(event: &GameEvent) -> Vec<Sound> {
+    match *event {
+        GameEvent::Move => vec![Sound::Move],
+        GameEvent::Rotate => vec![Sound::Rotate],
+        GameEvent::Lock { lines, t_spin } => {
+            let mut sounds = vec![];
+
+            if t_spin {
+                sounds.push(Sound::TSpin);
+            }
+
+            sounds.extend([Sound::Clear(lines), Sound::Lock]);
+            sounds
+        }
+        GameEvent::LevelUp => vec![Sound::LevelUp],
+        GameEvent::GameOver => vec![Sound::GameOver],
+        GameEvent::Attack(_) => vec![],
+    }
+}
+
+fn play_sounds(
+    mut commands: Commands,
+    mut events: EventReader<GameEvent>,
+    packs: Res<SoundPacks>,
+    settings: Res<Settings>,
+    asset_server: Res<AssetServer>,
+    mut turns: Local<HashMap<Sound, usize>>,
+) {
+    let Some(pack) = packs.get(&settings.sound_pack) else {
+        events.clear();
+        return;
+    };
+
+    for event in events.read() {
+        let Some((sound, cue)) = sounds(event)
+            .into_iter()
+            .find_map(|sound| pack.cue(sound).map(|cue| (sound, cue)))
+        else {
+            continue;
+        };
+
+        let turn = turns.entry(sound).or_default();
+        let path = format!("sounds/{}", cue.sounds[*turn % cue.sounds.len()]);
+        *turn += 1;
+
+        play(&mut commands, &asset_server, &settings, &path);
+        schedule_repeats(&mut commands, cue, &path);
+    }
+}
+
+fn schedule_repeats(commands: &mut Commands, cue: &Cue, path: &str) {
+    for time in 1..cue.times {
+        commands.spawn(ScheduledSound {
+            path: path.into(),
+            timer: Timer::from_seconds(cue.interval * time as f32, TimerMode::Once),
+        });
+    }
+}
+
+fn play_scheduled_sounds(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    settings: Res<Settings>,
+    time: Res<Time>,
+    mut scheduled_sound: Query<(Entity, &mut ScheduledSound)>,
+) {
+    for (ent, mut sound) in scheduled_sound.iter_mut() {
+        sound.timer.tick(time.delta());
+
+        if sound.timer.finished() {
+            play(&mut commands, &asset_server, &settings, &sound.path);
+            commands.entity(ent).despawn();
+        }
+    }
+}
+
+fn play(commands: &mut Commands, asset_server: &AssetServer, settings: &Settings, path: &str) {
+    commands.spawn(AudioBundle {
+        source: asset_server.load(path.to_string()),
+        settings: PlaybackSettings {
+            mode: PlaybackMode::Despawn,
+            volume: Volume::new_relative(settings.volume.effects),
+            ..default()
+        },
+    });
+}
+
+fn apply_volume(settings: Res<Settings>, mut volume: ResMut<GlobalVolume>) {
+    if settings.is_changed() {
+        *volume = GlobalVolume::new(settings.volume.master);
+    }
+}
@@ -0,0 +1,404 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use bevy::input::InputSystem;
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use super::gamepad::{self, Players};
+use crate::i18n;
+use crate::settings::{Language, Settings};
+
+const FILE_NAME: &str = "controls.ron";
+const MAX_BINDINGS: usize = 3;
+/// Toggles the inspector, so it can't be bound to anything else.
+pub const RESERVED_KEY: KeyCode = KeyCode::Escape;
+
+/// Something the player can do, whatever key it is bound to. Game actions
+/// and menu actions are bound separately, so the same key can do one thing
+/// in game and another in the menus.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum Action {
+    MoveLeft,
+    MoveRight,
+    SoftDrop,
+    HardDrop,
+    RotateCW,
+    RotateCCW,
+    Pause,
+    MenuUp,
+    MenuDown,
+    MenuLeft,
+    MenuRight,
+    Confirm,
+}
+
+impl Action {
+    pub const ALL: [Action; 12] = [
+        Action::MoveLeft,
+        Action::MoveRight,
+        Action::SoftDrop,
+        Action::HardDrop,
+        Action::RotateCW,
+        Action::RotateCCW,
+        Action::Pause,
+        Action::MenuUp,
+        Action::MenuDown,
+        Action::MenuLeft,
+        Action::MenuRight,
+        Action::Confirm,
+    ];
+
+    pub fn name(&self, language: Language) -> &'static str {
+        let id = match self {
+            Action::MoveLeft => "action.move_left",
+            Action::MoveRight => "action.move_right",
+            Action::SoftDrop => "action.soft_drop",
+            Action::HardDrop => "action.hard_drop",
+            Action::RotateCW => "action.rotate_cw",
+            Action::RotateCCW => "action.rotate_ccw",
+            Action::Pause => "action.pause",
+            Action::MenuUp => "action.menu_up",
+            Action::MenuDown => "action.menu_down",
+            Action::MenuLeft => "action.menu_left",
+            Action::MenuRight => "action.menu_right",
+            Action::Confirm => "action.confirm",
+        };
+
+        i18n::text(language, id)
+    }
+
+    fn in_menus(&self) -> bool {
+        matches!(
+            self,
+            Action::MenuUp
+                | Action::MenuDown
+                | Action::MenuLeft
+                | Action::MenuRight
+                | Action::Confirm
+        )
+    }
+
+    /// Whether holding the action repeats it, with the DAS and ARR from the
+    /// settings.
+    fn repeats(&self) -> bool {
+        matches!(self, Action::MoveLeft | Action::MoveRight)
+    }
+
+    fn default_buttons(&self) -> Vec<GamepadButtonType> {
+        match self {
+            Action::MoveLeft | Action::MenuLeft => vec![GamepadButtonType::DPadLeft],
+            Action::MoveRight | Action::MenuRight => vec![GamepadButtonType::DPadRight],
+            Action::SoftDrop | Action::MenuDown => vec![GamepadButtonType::DPadDown],
+            Action::HardDrop | Action::MenuUp => vec![GamepadButtonType::DPadUp],
+            Action::RotateCW => vec![GamepadButtonType::East],
+            Action::RotateCCW | Action::Confirm => vec![GamepadButtonType::South],
+            Action::Pause => vec![GamepadButtonType::Start],
+        }
+    }
+
+    fn default_keys(&self) -> Vec<KeyCode> {
+        match self {
+            Action::MoveLeft => vec![KeyCode::Left],
+            Action::MoveRight => vec![KeyCode::Right],
+            Action::SoftDrop => vec![KeyCode::Down],
+            Action::HardDrop => vec![KeyCode::Space],
+            Action::RotateCW => vec![KeyCode::Up, KeyCode::X],
+            Action::RotateCCW => vec![KeyCode::Z],
+            Action::Pause => vec![KeyCode::P],
+            Action::MenuUp => vec![KeyCode::Up],
+            Action::MenuDown => vec![KeyCode::Down],
+            Action::MenuLeft => vec![KeyCode::Left],
+            Action::MenuRight => vec![KeyCode::Right],
+            Action::Confirm => vec![KeyCode::Space],
+        }
+    }
+}
+
+/// The keys and gamepad buttons bound to each action, kept in the config
+/// directory.
+#[derive(Resource, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Controls {
+    bindings: BTreeMap<Action, Vec<KeyCode>>,
+    #[serde(default)]
+    buttons: BTreeMap<Action, Vec<GamepadButtonType>>,
+}
+
+impl Default for Controls {
+    fn default() -> Self {
+        Controls {
+            bindings: Action::ALL
+                .iter()
+                .map(|action| (*action, action.default_keys()))
+                .collect(),
+            buttons: Action::ALL
+                .iter()
+                .map(|action| (*action, action.default_buttons()))
+                .collect(),
+        }
+    }
+}
+
+impl Controls {
+    pub fn path() -> Option<PathBuf> {
+        dirs::config_dir().map(|dir| dir.join("rtetris").join(FILE_NAME))
+    }
+
+    /// Loads the saved controls. Reserved keys are dropped and actions left
+    /// without keys get their default ones back.
+    pub fn load() -> io::Result<Controls> {
+        let Some(path) = Controls::path() else {
+            return Ok(Controls::default());
+        };
+
+        if !path.exists() {
+            return Ok(Controls::default());
+        }
+
+        let contents = fs::read_to_string(path)?;
+        let controls: Controls =
+            ron::from_str(&contents).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+
+        Ok(controls.validated())
+    }
+
+    pub fn save(&self) -> io::Result<()> {
+        let Some(path) = Controls::path() else {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                "no config directory available",
+            ));
+        };
+
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        let contents = ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+
+        fs::write(path, contents)
+    }
+
+    fn validated(mut self) -> Controls {
+        for action in Action::ALL {
+            self.buttons
+                .entry(action)
+                .or_insert_with(|| action.default_buttons())
+                .truncate(MAX_BINDINGS);
+
+            let keys = self.bindings.entry(action).or_default();
+
+            keys.retain(|&key| key != RESERVED_KEY);
+            keys.truncate(MAX_BINDINGS);
+
+            if keys.is_empty() {
+                *keys = action.default_keys();
+            }
+        }
+
+        self
+    }
+
+    pub fn keys(&self, action: Action) -> &[KeyCode] {
+        self.bindings.get(&action).map(Vec::as_slice).unwrap_or(&[])
+    }
+
+    pub fn buttons(&self, action: Action) -> &[GamepadButtonType] {
+        self.buttons.get(&action).map(Vec::as_slice).unwrap_or(&[])
+    }
+
+    /// Binds `key` to `action`, taking it from whichever action it was bound
+    /// to among the game or menu actions. The oldest binding goes once there
+    /// are too many. Returns false if the key is reserved, or is the last
+    /// one left for the action it would be taken from.
+    pub fn bind(&mut self, action: Action, key: KeyCode) -> bool {
+        if key == RESERVED_KEY {
+            return false;
+        }
+
+        let owner = Action::ALL.into_iter().find(|other| {
+            *other != action
+                && other.in_menus() == action.in_menus()
+                && self.keys(*other).contains(&key)
+        });
+
+        if let Some(owner) = owner {
+            if self.keys(owner).len() == 1 {
+                return false;
+            }
+
+            if let Some(keys) = self.bindings.get_mut(&owner) {
+                keys.retain(|&other| other != key);
+            }
+        }
+
+        let keys = self.bindings.entry(action).or_default();
+
+        if !keys.contains(&key) {
+            keys.push(key);
+        }
+
+        if keys.len() > MAX_BINDINGS {
+            keys.remove(0);
+        }
+
+        true
+    }
+
+    /// Binds a gamepad button to `action`, taking it from whichever action
+    /// it was bound to among the game or menu actions. Actions can be left
+    /// without buttons, as the keyboard still works.
+    pub fn bind_button(&mut self, action: Action, button: GamepadButtonType) {
+        for other in Action::ALL {
+            if other != action && other.in_menus() == action.in_menus() {
+                if let Some(buttons) = self.buttons.get_mut(&other) {
+                    buttons.retain(|&bound| bound != button);
+                }
+            }
+        }
+
+        let buttons = self.buttons.entry(action).or_default();
+
+        if !buttons.contains(&button) {
+            buttons.push(button);
+        }
+
+        if buttons.len() > MAX_BINDINGS {
+            buttons.remove(0);
+        }
+    }
+
+    /// Removes the most recent button bound to `action`, or its most recent
+    /// key as long as another one is left.
+    pub fn unbind_last(&mut self, action: Action) -> bool {
+        if let Some(buttons) = self.buttons.get_mut(&action) {
+            if buttons.pop().is_some() {
+                return true;
+            }
+        }
+
+        match self.bindings.get_mut(&action) {
+            Some(keys) if keys.len() > 1 => {
+                keys.pop();
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Which actions the player is doing this frame, from the keyboard and
+/// the player's gamepad together. Systems read input from here rather than
+/// from the devices.
+#[derive(Resource, Debug, Default)]
+pub struct Actions {
+    pressed: HashSet<Action>,
+    previous: HashSet<Action>,
+    /// How long each repeating action has been held.
+    held: HashMap<Action, Duration>,
+    repeated: HashSet<Action>,
+}
+
+impl Actions {
+    pub fn pressed(&self, action: Action) -> bool {
+        self.pressed.contains(&action)
+    }
+
+    pub fn just_pressed(&self, action: Action) -> bool {
+        self.pressed.contains(&action) && !self.previous.contains(&action)
+    }
+
+    pub fn just_released(&self, action: Action) -> bool {
+        !self.pressed.contains(&action) && self.previous.contains(&action)
+    }
+
+    /// Whether a repeating action fires this frame: when it is pressed, and
+    /// then over and over while it is held.
+    pub fn repeated(&self, action: Action) -> bool {
+        self.repeated.contains(&action)
+    }
+
+    fn advance(&mut self, pressed: HashSet<Action>, delta: Duration, das: Duration, arr: Duration) {
+        self.previous = std::mem::replace(&mut self.pressed, pressed);
+        self.repeated.clear();
+
+        for action in Action::ALL.into_iter().filter(Action::repeats) {
+            if !self.pressed.contains(&action) {
+                self.held.remove(&action);
+                continue;
+            }
+
+            let before = self.held.get(&action).copied();
+            let after = before.map_or(Duration::ZERO, |held| held + delta);
+            self.held.insert(action, after);
+
+            if before.is_none_or(|before| repeats(after, das, arr) > repeats(before, das, arr)) {
+                self.repeated.insert(action);
+            }
+        }
+    }
+}
+
+/// How many times a move held for `held` has repeated, after waiting `das`
+/// and then every `arr`.
+fn repeats(held: Duration, das: Duration, arr: Duration) -> u128 {
+    if held < das {
+        return 0;
+    }
+
+    1 + (held - das).as_nanos() / arr.as_nanos().max(1)
+}
+
+pub struct ControlsHandler;
+
+impl Plugin for ControlsHandler {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Actions>()
+            .init_resource::<Players>()
+            .add_systems(
+                PreUpdate,
+                (gamepad::assign_gamepads, update_actions)
+                    .chain()
+                    .after(InputSystem),
+            );
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn update_actions(
+    time: Res<Time>,
+    keyboard_input: Res<Input<KeyCode>>,
+    buttons: Res<Input<GamepadButton>>,
+    axes: Res<Axis<GamepadAxis>>,
+    controls: Res<Controls>,
+    settings: Res<Settings>,
+    players: Res<Players>,
+    mut actions: ResMut<Actions>,
+) {
+    // The game has a single player, who also gets the keyboard.
+    let gamepad = players.gamepad(0);
+
+    let pressed = Action::ALL
+        .into_iter()
+        .filter(|&action| {
+            keyboard_input.any_pressed(controls.keys(action).iter().copied())
+                || gamepad.is_some_and(|gamepad| {
+                    controls
+                        .buttons(action)
+                        .iter()
+                        .any(|&button| gamepad::button_pressed(gamepad, button, &buttons, &axes))
+                })
+        })
+        .collect();
+
+    actions.advance(
+        pressed,
+        time.delta(),
+        Duration::from_millis(settings.das),
+        Duration::from_millis(settings.arr),
+    );
+}
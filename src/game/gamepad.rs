@@ -0,0 +1,88 @@
+use bevy::input::gamepad::{GamepadConnection, GamepadConnectionEvent};
+use bevy::prelude::*;
+
+/// Players that can have a controller of their own.
+pub const MAX_PLAYERS: usize = 2;
+/// How far the left stick has to be pushed to count as the D-pad.
+const STICK_THRESHOLD: f32 = 0.5;
+
+/// The gamepad each player uses. Gamepads go to the first player without
+/// one as they connect, and can be reassigned in the settings.
+#[derive(Resource, Debug, Default)]
+pub struct Players {
+    gamepads: [Option<Gamepad>; MAX_PLAYERS],
+}
+
+impl Players {
+    pub fn gamepad(&self, player: usize) -> Option<Gamepad> {
+        self.gamepads.get(player).copied().flatten()
+    }
+
+    /// Gives `gamepad` to `player`, taking it from whoever had it.
+    pub fn assign(&mut self, player: usize, gamepad: Option<Gamepad>) {
+        if gamepad.is_some() {
+            for slot in self.gamepads.iter_mut() {
+                if *slot == gamepad {
+                    *slot = None;
+                }
+            }
+        }
+
+        if let Some(slot) = self.gamepads.get_mut(player) {
+            *slot = gamepad;
+        }
+    }
+
+    fn connect(&mut self, gamepad: Gamepad) {
+        if self.gamepads.contains(&Some(gamepad)) {
+            return;
+        }
+
+        if let Some(slot) = self.gamepads.iter_mut().find(|slot| slot.is_none()) {
+            *slot = Some(gamepad);
+        }
+    }
+
+    fn disconnect(&mut self, gamepad: Gamepad) {
+        for slot in self.gamepads.iter_mut() {
+            if *slot == Some(gamepad) {
+                *slot = None;
+            }
+        }
+    }
+}
+
+pub fn assign_gamepads(
+    mut events: EventReader<GamepadConnectionEvent>,
+    mut players: ResMut<Players>,
+) {
+    for event in events.read() {
+        match event.connection {
+            GamepadConnection::Connected(_) => players.connect(event.gamepad),
+            GamepadConnection::Disconnected => players.disconnect(event.gamepad),
+        }
+    }
+}
+
+/// Whether `button` is held on `gamepad`. Pushing the left stick counts as
+/// holding the D-pad in that direction.
+pub fn button_pressed(
+    gamepad: Gamepad,
+    button: GamepadButtonType,
+    buttons: &Input<GamepadButton>,
+    axes: &Axis<GamepadAxis>,
+) -> bool {
+    if buttons.pressed(GamepadButton::new(gamepad, button)) {
+        return true;
+    }
+
+    let stick = |axis| axes.get(GamepadAxis::new(gamepad, axis)).unwrap_or(0.0);
+
+    match button {
+        GamepadButtonType::DPadLeft => stick(GamepadAxisType::LeftStickX) < -STICK_THRESHOLD,
+        GamepadButtonType::DPadRight => stick(GamepadAxisType::LeftStickX) > STICK_THRESHOLD,
+        GamepadButtonType::DPadDown => stick(GamepadAxisType::LeftStickY) < -STICK_THRESHOLD,
+        GamepadButtonType::DPadUp => stick(GamepadAxisType::LeftStickY) > STICK_THRESHOLD,
+        _ => false,
+    }
+}
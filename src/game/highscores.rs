@@ -0,0 +1,278 @@
+use std::time::Duration;
+
+use bevy::prelude::*;
+
+use crate::highscores::{format_duration, HighScores};
+use crate::i18n;
+use crate::mode::GameMode;
+use crate::settings::{Language, Settings};
+
+use super::controls::{Action, Actions};
+use super::menu::TextComponent;
+use super::skin::{ActiveSkin, SkinFont};
+use super::{AiPlayer, GameState, State};
+
+const MAX_NAME_LENGTH: usize = 12;
+
+#[derive(Resource, Default)]
+pub struct NameEntry {
+    pub name: String,
+    pub active: bool,
+    pub previous_best: Option<Duration>,
+}
+
+#[derive(Resource, Default)]
+pub struct HighScoreView {
+    pub page: usize,
+}
+
+#[derive(Component)]
+pub struct GameOverText;
+
+#[derive(Component)]
+pub struct HighScoreText;
+
+pub struct HighScoreHandler;
+
+impl Plugin for HighScoreHandler {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<NameEntry>()
+            .init_resource::<HighScoreView>()
+            .add_systems(OnEnter(State::GameOver), on_game_over)
+            .add_systems(Update, handle_name_entry.run_if(in_state(State::GameOver)))
+            .add_systems(OnEnter(State::HighScores), on_high_scores_enter)
+            .add_systems(
+                Update,
+                handle_high_scores.run_if(in_state(State::HighScores)),
+            );
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn on_game_over(
+    mut commands: Commands,
+    state: Res<GameState>,
+    ai: Res<AiPlayer>,
+    high_scores: Res<HighScores>,
+    mut entry: ResMut<NameEntry>,
+    settings: Res<Settings>,
+    skin: Res<ActiveSkin>,
+    asset_server: Res<AssetServer>,
+) {
+    let eligible = ai.bot.is_none()
+        && state.keeps_records()
+        && (state.finished || !state.mode.ranks_by_time());
+
+    entry.name.clear();
+    entry.active = eligible && high_scores.qualifies(state.mode, &state.record(String::new()));
+    entry.previous_best = high_scores.best(state.mode).map(|record| record.duration);
+
+    let style = TextStyle {
+        font: skin.font(&asset_server),
+        font_size: 50.0,
+        ..default()
+    };
+
+    commands.spawn((
+        TextComponent {},
+        GameOverText,
+        SkinFont,
+        TextBundle::from_sections(game_over_sections(
+            &state,
+            &entry,
+            settings.language,
+            &style,
+        ))
+        .with_text_alignment(TextAlignment::Center)
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            bottom: Val::Px(5.0),
+            right: Val::Px(5.0),
+            ..default()
+        }),
+    ));
+}
+
+fn game_over_sections(
+    state: &GameState,
+    entry: &NameEntry,
+    language: Language,
+    style: &TextStyle,
+) -> Vec<TextSection> {
+    let summary = state.summary(entry.previous_best, language);
+
+    let mut sections = vec![TextSection::new(summary, style.clone())];
+
+    if entry.active {
+        sections.push(TextSection::new(
+            i18n::format(language, "scores.new_record", &[&entry.name]) + "\n",
+            style.clone(),
+        ));
+        sections.push(TextSection::new(
+            i18n::text(language, "scores.confirm"),
+            style.clone(),
+        ));
+    } else {
+        sections.push(TextSection::new(
+            i18n::text(language, "scores.back"),
+            style.clone(),
+        ));
+    }
+
+    sections
+}
+
+#[allow(clippy::too_many_arguments)]
+fn handle_name_entry(
+    keyboard_input: Res<Input<KeyCode>>,
+    actions: Res<Actions>,
+    mut characters: EventReader<ReceivedCharacter>,
+    mut entry: ResMut<NameEntry>,
+    mut high_scores: ResMut<HighScores>,
+    state: Res<GameState>,
+    settings: Res<Settings>,
+    mut app_state: ResMut<NextState<State>>,
+    mut text: Query<&mut Text, With<GameOverText>>,
+) {
+    if !entry.active {
+        characters.clear();
+
+        if actions.just_released(Action::Confirm) {
+            app_state.set(State::MainMenu);
+        }
+
+        return;
+    }
+
+    let mut changed = false;
+
+    for event in characters.read() {
+        if event.char.is_control() || entry.name.chars().count() >= MAX_NAME_LENGTH {
+            continue;
+        }
+
+        entry.name.push(event.char);
+        changed = true;
+    }
+
+    if keyboard_input.just_pressed(KeyCode::Back) {
+        entry.name.pop();
+        changed = true;
+    }
+
+    if keyboard_input.just_pressed(KeyCode::Return) {
+        let name = entry.name.trim();
+        let name = if name.is_empty() { "???" } else { name };
+
+        high_scores.insert(state.mode, state.record(name.into()));
+
+        if let Err(e) = high_scores.save() {
+            warn!("Could not save high scores: {}", e);
+        }
+
+        entry.active = false;
+        changed = true;
+    }
+
+    if !changed {
+        return;
+    }
+
+    for mut text in text.iter_mut() {
+        let style = text.sections[0].style.clone();
+        text.sections = game_over_sections(&state, &entry, settings.language, &style);
+    }
+}
+
+fn on_high_scores_enter(
+    mut commands: Commands,
+    text: Query<Entity, With<TextComponent>>,
+    high_scores: Res<HighScores>,
+    mut view: ResMut<HighScoreView>,
+    settings: Res<Settings>,
+    skin: Res<ActiveSkin>,
+    asset_server: Res<AssetServer>,
+) {
+    for ent in text.iter() {
+        commands.entity(ent).despawn();
+    }
+
+    view.page = 0;
+
+    commands.spawn((
+        TextComponent {},
+        HighScoreText,
+        SkinFont,
+        TextBundle::from_section(
+            high_score_page(&high_scores, GameMode::all()[view.page], settings.language),
+            TextStyle {
+                font: skin.font(&asset_server),
+                font_size: 30.0,
+                ..default()
+            },
+        )
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            top: Val::Px(5.0),
+            left: Val::Px(5.0),
+            ..default()
+        }),
+    ));
+}
+
+fn high_score_page(high_scores: &HighScores, mode: GameMode, language: Language) -> String {
+    let mut contents = i18n::format(language, "scores.title", &[&mode.name(language)]) + "\n\n";
+    let table = high_scores.table(mode);
+
+    if table.is_empty() {
+        contents.push_str(i18n::text(language, "scores.empty"));
+        contents.push('\n');
+    }
+
+    for (position, record) in table.iter().enumerate() {
+        contents.push_str(&format!(
+            "{:>2}. {:<12} {:>8}  {} {:>3}  {} {:>2}  {}  {}\n",
+            position + 1,
+            record.name,
+            record.score,
+            i18n::text(language, "scores.lines"),
+            record.lines,
+            i18n::text(language, "scores.level"),
+            record.level,
+            format_duration(record.duration),
+            record.date.format("%Y-%m-%d"),
+        ));
+    }
+
+    contents.push('\n');
+    contents.push_str(i18n::text(language, "scores.help"));
+
+    contents
+}
+
+fn handle_high_scores(
+    actions: Res<Actions>,
+    high_scores: Res<HighScores>,
+    settings: Res<Settings>,
+    mut view: ResMut<HighScoreView>,
+    mut app_state: ResMut<NextState<State>>,
+    mut text: Query<&mut Text, With<HighScoreText>>,
+) {
+    if actions.just_released(Action::Confirm) {
+        app_state.set(State::MainMenu);
+    }
+
+    let modes = GameMode::all();
+
+    if actions.just_pressed(Action::MenuLeft) {
+        view.page = (view.page + modes.len() - 1) % modes.len();
+    } else if actions.just_pressed(Action::MenuRight) {
+        view.page = (view.page + 1) % modes.len();
+    } else {
+        return;
+    }
+
+    for mut text in text.iter_mut() {
+        text.sections[0].value = high_score_page(&high_scores, modes[view.page], settings.language);
+    }
+}
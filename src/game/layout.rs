@@ -0,0 +1,138 @@
+use bevy::prelude::*;
+use bevy::window::{PrimaryWindow, WindowResized};
+
+use super::skin::SkinBackground;
+use super::{COLUMNS, ROWS, TILE_SIZE};
+use crate::settings::Settings;
+
+/// The area the game is laid out in, in the units sprites are placed in.
+/// It is scaled to fit the window, keeping its aspect ratio.
+const VIEW_WIDTH: f32 = 1280.0;
+const VIEW_HEIGHT: f32 = 720.0;
+
+/// How the game fits the window.
+#[derive(Resource, Debug, Clone, Copy, PartialEq)]
+pub struct Layout {
+    /// Pixels per unit.
+    pub scale: f32,
+    /// The window's size, in units.
+    pub width: f32,
+    pub height: f32,
+}
+
+impl Default for Layout {
+    fn default() -> Self {
+        Layout {
+            scale: 1.0,
+            width: VIEW_WIDTH,
+            height: VIEW_HEIGHT,
+        }
+    }
+}
+
+impl Layout {
+    /// The layout for a window `width` by `height` pixels. With integer
+    /// scaling, the scale is a whole number, or one over a whole number
+    /// for windows too small to show the game at its size.
+    pub fn fit(width: f32, height: f32, integer_scaling: bool) -> Layout {
+        let scale = (width / VIEW_WIDTH).min(height / VIEW_HEIGHT);
+
+        let scale = if !scale.is_finite() || scale <= 0.0 {
+            1.0
+        } else if !integer_scaling {
+            scale
+        } else if scale >= 1.0 {
+            scale.floor()
+        } else {
+            1.0 / (1.0 / scale).ceil()
+        };
+
+        Layout {
+            scale,
+            width: width / scale,
+            height: height / scale,
+        }
+    }
+
+    /// The size of a tile on screen, in pixels.
+    pub fn tile_size(&self) -> f32 {
+        TILE_SIZE * self.scale
+    }
+
+    /// Where UI placed at row `i` and column `j` of the board goes, as
+    /// distances from the top left of the window in UI units.
+    pub fn ui_position(&self, i: isize, j: isize) -> (f32, f32) {
+        let w = TILE_SIZE * COLUMNS as f32;
+        let h = TILE_SIZE * ROWS as f32;
+
+        let top = (self.height / 2.0) - (h / 2.0) + (TILE_SIZE * i as f32);
+        let left = (self.width / 2.0) - (w / 2.0) + (TILE_SIZE * j as f32);
+
+        (top, left)
+    }
+}
+
+/// UI placed at a tile of the board, which moves with it.
+#[derive(Component)]
+pub struct Anchored {
+    pub i: isize,
+    pub j: isize,
+}
+
+pub struct LayoutHandler;
+
+impl Plugin for LayoutHandler {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Layout>()
+            .add_systems(Startup, update_layout)
+            .add_systems(
+                Update,
+                update_layout
+                    .run_if(on_event::<WindowResized>().or_else(resource_changed::<Settings>())),
+            )
+            .add_systems(Update, apply_layout.run_if(resource_changed::<Layout>()));
+    }
+}
+
+fn update_layout(
+    settings: Res<Settings>,
+    window_query: Query<&Window, With<PrimaryWindow>>,
+    mut layout: ResMut<Layout>,
+) {
+    let Ok(window) = window_query.get_single() else {
+        return;
+    };
+
+    layout.set_if_neq(Layout::fit(
+        window.width(),
+        window.height(),
+        settings.integer_scaling,
+    ));
+}
+
+/// Zooms the camera and the UI to the layout's scale, and moves the UI
+/// placed on the board to where the board now is.
+fn apply_layout(
+    layout: Res<Layout>,
+    mut ui_scale: ResMut<UiScale>,
+    mut projections: Query<&mut OrthographicProjection>,
+    mut anchored: Query<(&mut Style, &Anchored)>,
+    mut backgrounds: Query<&mut Sprite, With<SkinBackground>>,
+) {
+    ui_scale.0 = layout.scale as f64;
+
+    for mut projection in projections.iter_mut() {
+        projection.scale = 1.0 / layout.scale;
+    }
+
+    for (mut style, anchor) in anchored.iter_mut() {
+        let (top, left) = layout.ui_position(anchor.i, anchor.j);
+
+        style.top = Val::Px(top);
+        style.left = Val::Px(left);
+    }
+
+    for mut sprite in backgrounds.iter_mut() {
+        sprite.custom_size = Some(Vec2::new(layout.width, layout.height));
+    }
+}
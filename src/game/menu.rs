@@ -1,42 +1,340 @@
-use crate::game::tetromino::TetrominoComponent;
 use crate::game::State;
 use bevy::prelude::*;
-use bevy::window::PrimaryWindow;
 
-use super::{
-    tetromino::{Block, ScheduledSound},
-    to_transform, GameState, COLUMNS, ROWS, TILE_SIZE,
-};
+use super::controls::{Action, Actions};
+use super::layout::{Anchored, Layout};
+use super::pause::starting_game;
+use super::settings::SettingsMenu;
+use super::skin::{ActiveSkin, Border, SkinFont};
+use super::versus::Opponent;
+use super::{to_transform, AiPlayer, GameState, COLUMNS, ROWS, TILE_SIZE};
+use crate::bot::Bot;
+use crate::highscores::format_duration;
+use crate::i18n;
+use crate::mode::{GameMode, DIG_ROWS, MARATHON_LEVELS, SPRINT_GOALS, ULTRA_DURATIONS};
+use crate::pieces::PieceSets;
+use crate::puzzle::PuzzlePacks;
+use crate::settings::{Language, Settings};
 
 #[derive(Component)]
 pub struct TextComponent {}
 
+/// A tile of a border around the board or a panel.
 #[derive(Component)]
-pub struct Tile;
+pub struct Tile(pub Border);
+
+#[derive(Component)]
+pub struct MenuText;
+
+/// Text showing message `0` of the catalogue, redrawn when the language
+/// changes.
+#[derive(Component)]
+pub struct Label(pub &'static str);
+
+#[derive(Component)]
+pub enum HudText {
+    Score,
+    Lines,
+    Time,
+    Level,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MenuItem {
+    Marathon,
+    Endless,
+    Sprint,
+    Ultra,
+    Dig,
+    RisingDig,
+    Pieces,
+    WatchAi,
+    Versus,
+    Puzzle,
+    Settings,
+    HighScores,
+}
+
+const MENU_ITEMS: [MenuItem; 12] = [
+    MenuItem::Marathon,
+    MenuItem::Endless,
+    MenuItem::Sprint,
+    MenuItem::Ultra,
+    MenuItem::Dig,
+    MenuItem::RisingDig,
+    MenuItem::Pieces,
+    MenuItem::WatchAi,
+    MenuItem::Versus,
+    MenuItem::Puzzle,
+    MenuItem::Settings,
+    MenuItem::HighScores,
+];
+
+#[derive(Resource)]
+pub struct MenuSelection {
+    pub item: usize,
+    pub start_level: usize,
+    pub sprint_goal: usize,
+    pub ultra_duration: usize,
+    pub dig_rows: usize,
+    pub piece_set: usize,
+    pub puzzle: usize,
+}
+
+impl Default for MenuSelection {
+    fn default() -> Self {
+        MenuSelection {
+            item: 0,
+            start_level: 1,
+            sprint_goal: 1,
+            ultra_duration: 0,
+            dig_rows: 1,
+            piece_set: 0,
+            puzzle: 0,
+        }
+    }
+}
+
+impl MenuSelection {
+    fn adjust(&mut self, direction: isize, piece_sets: usize, puzzles: usize) {
+        let step = |value: usize, len: usize| {
+            (value as isize + direction).rem_euclid(len as isize) as usize
+        };
+
+        match MENU_ITEMS[self.item] {
+            MenuItem::Marathon | MenuItem::Endless | MenuItem::WatchAi => {
+                self.start_level = step(self.start_level - 1, MARATHON_LEVELS) + 1;
+            }
+            MenuItem::Sprint => self.sprint_goal = step(self.sprint_goal, SPRINT_GOALS.len()),
+            MenuItem::Ultra => {
+                self.ultra_duration = step(self.ultra_duration, ULTRA_DURATIONS.len());
+            }
+            MenuItem::Dig | MenuItem::RisingDig => {
+                self.dig_rows = step(self.dig_rows, DIG_ROWS.len());
+            }
+            MenuItem::Pieces => self.piece_set = step(self.piece_set, piece_sets),
+            MenuItem::Puzzle if puzzles > 0 => self.puzzle = step(self.puzzle, puzzles),
+            MenuItem::Versus | MenuItem::Puzzle | MenuItem::Settings | MenuItem::HighScores => {}
+        }
+    }
+}
 
 pub struct MenuHandler;
 
 impl Plugin for MenuHandler {
     fn build(&self, app: &mut App) {
-        app.add_systems(Update, handle_main_menu.run_if(in_state(State::MainMenu)))
+        app.init_resource::<MenuSelection>()
+            .add_systems(Update, handle_main_menu.run_if(in_state(State::MainMenu)))
             .add_systems(OnEnter(State::MainMenu), on_menu_enter)
-            .add_systems(OnEnter(State::GameOver), on_game_over)
+            .add_systems(Update, update_hud.run_if(in_state(State::Running)))
+            .add_systems(Update, update_labels.run_if(resource_changed::<Settings>()))
             .add_systems(
                 OnEnter(State::Running),
-                (draw_rectangle, on_game_start)
+                (draw_rectangle, on_game_start).run_if(starting_game),
             );
     }
 }
 
-fn handle_main_menu(keyboard_input: Res<Input<KeyCode>>, mut app_state: ResMut<NextState<State>>) {
-    if keyboard_input.just_released(KeyCode::Space) {
-        app_state.set(State::Running);
+#[allow(clippy::too_many_arguments)]
+fn handle_main_menu(
+    mut commands: Commands,
+    actions: Res<Actions>,
+    mut state: ResMut<GameState>,
+    mut ai: ResMut<AiPlayer>,
+    mut selection: ResMut<MenuSelection>,
+    mut settings_menu: ResMut<SettingsMenu>,
+    settings: Res<Settings>,
+    piece_sets: Res<PieceSets>,
+    puzzles: Res<PuzzlePacks>,
+    mut app_state: ResMut<NextState<State>>,
+    mut text: Query<&mut Text, With<MenuText>>,
+) {
+    let set_count = piece_sets.sets.len();
+    let puzzle_count = puzzles.puzzles().len();
+
+    if actions.just_pressed(Action::MenuUp) {
+        selection.item = (selection.item + MENU_ITEMS.len() - 1) % MENU_ITEMS.len();
+    }
+
+    if actions.just_pressed(Action::MenuDown) {
+        selection.item = (selection.item + 1) % MENU_ITEMS.len();
+    }
+
+    if actions.just_pressed(Action::MenuLeft) {
+        selection.adjust(-1, set_count, puzzle_count);
+    }
+
+    if actions.just_pressed(Action::MenuRight) {
+        selection.adjust(1, set_count, puzzle_count);
+    }
+
+    if selection.is_changed() {
+        for mut text in text.iter_mut() {
+            text.sections[0].value =
+                menu_text(&selection, &piece_sets, &puzzles, settings.language);
+        }
+    }
+
+    if !actions.just_released(Action::Confirm) {
+        return;
+    }
+
+    ai.bot = None;
+
+    let mode = match MENU_ITEMS[selection.item] {
+        MenuItem::Marathon => GameMode::Marathon { endless: false },
+        MenuItem::Endless => GameMode::Marathon { endless: true },
+        MenuItem::Sprint => GameMode::Sprint {
+            goal: SPRINT_GOALS[selection.sprint_goal],
+        },
+        MenuItem::Ultra => GameMode::Ultra {
+            seconds: ULTRA_DURATIONS[selection.ultra_duration],
+        },
+        MenuItem::Dig => GameMode::Dig {
+            rows: DIG_ROWS[selection.dig_rows],
+            rising: false,
+        },
+        MenuItem::RisingDig => GameMode::Dig {
+            rows: DIG_ROWS[selection.dig_rows],
+            rising: true,
+        },
+        MenuItem::Puzzle => {
+            if let Some((_, puzzle)) = puzzles.puzzles().get(selection.puzzle) {
+                match GameState::from_puzzle(puzzle, ROWS as usize, COLUMNS as usize) {
+                    Ok(puzzle) => {
+                        *state = puzzle;
+                        app_state.set(State::Running);
+                    }
+                    Err(e) => warn!("Could not start puzzle: {}", e),
+                }
+            }
+
+            return;
+        }
+        MenuItem::WatchAi => {
+            ai.bot = Some(Box::new(Bot::default()));
+            GameMode::Marathon { endless: true }
+        }
+        MenuItem::Versus => {
+            *state = GameState::new(GameMode::Versus, 1, piece_sets.sets[selection.piece_set]);
+            commands.insert_resource(Opponent::new(&state));
+            app_state.set(State::Running);
+            return;
+        }
+        MenuItem::Pieces => return,
+        MenuItem::Settings => {
+            settings_menu.return_to = State::MainMenu;
+            app_state.set(State::Settings);
+            return;
+        }
+        MenuItem::HighScores => {
+            app_state.set(State::HighScores);
+            return;
+        }
+    };
+
+    let start_level = if mode.levels_up() {
+        selection.start_level
+    } else {
+        1
+    };
+
+    *state = GameState::new(mode, start_level, piece_sets.sets[selection.piece_set]);
+    app_state.set(State::Running);
+}
+
+fn menu_text(
+    selection: &MenuSelection,
+    piece_sets: &PieceSets,
+    puzzles: &PuzzlePacks,
+    language: Language,
+) -> String {
+    let mut text = format!("{}\n\n", i18n::text(language, "menu.title"));
+    let level = i18n::format(language, "menu.level", &[&selection.start_level]);
+    let sprint_lines = i18n::format(
+        language,
+        "menu.lines",
+        &[&SPRINT_GOALS[selection.sprint_goal]],
+    );
+    let dig_lines = i18n::format(language, "menu.lines", &[&DIG_ROWS[selection.dig_rows]]);
+    let seconds = ULTRA_DURATIONS[selection.ultra_duration];
+
+    for (index, item) in MENU_ITEMS.iter().enumerate() {
+        let marker = if index == selection.item { ">" } else { " " };
+
+        let (name, value) = match item {
+            MenuItem::Marathon => ("menu.marathon", Some(level.clone())),
+            MenuItem::Endless => ("menu.endless", Some(level.clone())),
+            MenuItem::Sprint => ("menu.sprint", Some(sprint_lines.clone())),
+            MenuItem::Ultra => (
+                "menu.ultra",
+                Some(format!("{}:{:02}", seconds / 60, seconds % 60)),
+            ),
+            MenuItem::Dig => ("menu.dig", Some(dig_lines.clone())),
+            MenuItem::RisingDig => ("menu.rising_dig", Some(dig_lines.clone())),
+            MenuItem::Pieces => (
+                "menu.pieces",
+                Some(piece_sets.sets[selection.piece_set].name.clone()),
+            ),
+            MenuItem::WatchAi => ("menu.watch_ai", Some(level.clone())),
+            MenuItem::Versus => ("menu.versus", None),
+            MenuItem::Puzzle => match puzzles.puzzles().get(selection.puzzle) {
+                Some((pack, puzzle)) => (
+                    "menu.puzzle",
+                    Some(format!("{}: {}", pack.name, puzzle.name)),
+                ),
+                None => (
+                    "menu.puzzle",
+                    Some(i18n::text(language, "menu.none").into()),
+                ),
+            },
+            MenuItem::Settings => ("menu.settings", None),
+            MenuItem::HighScores => ("menu.high_scores", None),
+        };
+
+        let line = match value {
+            Some(value) => format!("{:<17} < {} >", i18n::text(language, name), value),
+            None => i18n::text(language, name).to_string(),
+        };
+
+        text.push_str(&format!("{} {}\n", marker, line));
+    }
+
+    text.push('\n');
+    text.push_str(i18n::text(language, "menu.help"));
+
+    text
+}
+
+fn update_hud(state: Res<GameState>, mut text: Query<(&mut Text, &HudText)>) {
+    for (mut text, hud) in text.iter_mut() {
+        text.sections[0].value = match hud {
+            HudText::Score => format!("     {}", state.score),
+            HudText::Lines => format!("     {}", state.lines_remaining().unwrap_or(state.lines)),
+            HudText::Time => format!(
+                "  {}",
+                format_duration(state.time_remaining().unwrap_or(state.elapsed))
+            ),
+            HudText::Level => format!("     {}", state.level),
+        };
+    }
+}
+
+fn update_labels(settings: Res<Settings>, mut text: Query<(&mut Text, &Label)>) {
+    for (mut text, label) in text.iter_mut() {
+        text.sections[0].value = i18n::text(settings.language, label.0).into();
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 fn on_menu_enter(
     mut commands: Commands,
     text: Query<Entity, With<TextComponent>>,
+    selection: Res<MenuSelection>,
+    settings: Res<Settings>,
+    piece_sets: Res<PieceSets>,
+    puzzles: Res<PuzzlePacks>,
+    skin: Res<ActiveSkin>,
     asset_server: Res<AssetServer>,
 ) {
     for ent in text.iter() {
@@ -45,19 +343,20 @@ fn on_menu_enter(
 
     commands.spawn((
         TextComponent {},
+        MenuText,
+        SkinFont,
         TextBundle::from_section(
-            "Ó O GÁAAAAAAAAAAS!!!!!! Aperte espaço para iniciar o jogo.",
+            menu_text(&selection, &piece_sets, &puzzles, settings.language),
             TextStyle {
-                font: asset_server.load("fonts/FiraSans-Bold.ttf"),
-                font_size: 100.0,
+                font: skin.font(&asset_server),
+                font_size: 40.0,
                 ..default()
             },
         )
-        .with_text_alignment(TextAlignment::Center)
         .with_style(Style {
             position_type: PositionType::Absolute,
-            bottom: Val::Px(5.0),
-            right: Val::Px(5.0),
+            top: Val::Px(5.0),
+            left: Val::Px(5.0),
             ..default()
         }),
     ));
@@ -69,210 +368,187 @@ fn on_game_start(mut commands: Commands, text: Query<Entity, With<TextComponent>
     }
 }
 
-fn on_game_over(
-    mut commands: Commands,
-    tetromino: Query<Entity, With<TetrominoComponent>>,
-    blocks: Query<Entity, With<Block>>,
-    asset_server: Res<AssetServer>,
-) {
-    commands.spawn((
-        TextComponent {},
-        TextBundle::from_section(
-            "Perdeu, mané! Não amola. Aperte espaço pra voltar pro menu.",
-            TextStyle {
-                font: asset_server.load("fonts/FiraSans-Bold.ttf"),
-                font_size: 100.0,
-                ..default()
-            },
-        )
-        .with_text_alignment(TextAlignment::Center)
-        .with_style(Style {
-            position_type: PositionType::Absolute,
-            bottom: Val::Px(5.0),
-            right: Val::Px(5.0),
-            ..default()
-        }),
-    ));
-
-    for ent in &blocks {
-        commands.entity(ent).despawn();
-    }
-
-    for ent in &tetromino {
-        commands.entity(ent).despawn();
-    }
-
-    for i in 1..10 {
-        commands.spawn(ScheduledSound {
-            timer: Timer::from_seconds(0.2 * i as f32, TimerMode::Once),
-        });
-    }
-}
-
 fn draw_rectangle(
     mut commands: Commands,
     asset_server: Res<AssetServer>,
+    skin: Res<ActiveSkin>,
     state: ResMut<GameState>,
-    window_query: Query<&Window, With<PrimaryWindow>>,
+    settings: Res<Settings>,
+    layout: Res<Layout>,
 ) {
-    let window = window_query.get_single().unwrap();
-    let width = window.width();
-    let height = window.height();
+    let rows = state.board.matrix.len() as isize;
+    let columns = state.board.matrix[0].len() as isize;
 
     draw_rect(
         &mut commands,
         &asset_server,
-        &window_query,
-        [0, 0, ROWS - 1, COLUMNS - 1],
+        &skin,
+        [0, 0, rows - 1, columns - 1],
         &[],
     );
 
-    draw_text(&mut commands, &asset_server, "Score", 0, -6, width, height);
+    let label = |commands: &mut Commands, id, i| {
+        let text = i18n::text(settings.language, id);
+        let entity = draw_text(commands, &asset_server, &skin, &layout, text, i, -6);
+        commands.entity(entity).insert(Label(id));
+    };
+
+    label(&mut commands, "hud.score", 0);
 
     let score = format!("     {}", state.score);
 
-    draw_text(&mut commands, &asset_server, &score, 1, -6, width, height);
+    let score_text = draw_text(&mut commands, &asset_server, &skin, &layout, &score, 1, -6);
+    commands.entity(score_text).insert(HudText::Score);
 
     draw_rect(
         &mut commands,
         &asset_server,
-        &window_query,
+        &skin,
         [0, -8, 2, 6],
         &[2, 3, 4],
     );
 
-    draw_text(&mut commands, &asset_server, " Next", 3, -6, width, height);
+    label(&mut commands, "hud.next", 3);
 
     draw_rect(
         &mut commands,
         &asset_server,
-        &window_query,
+        &skin,
         [3, -8, 6, 6],
         &[2, 3, 4],
     );
+
+    let lines_label = match state.mode {
+        GameMode::Sprint { .. } | GameMode::Dig { .. } => "hud.left",
+        _ => "hud.lines",
+    };
+
+    label(&mut commands, lines_label, 10);
+
+    let lines_text = draw_text(&mut commands, &asset_server, &skin, &layout, "", 11, -6);
+    commands.entity(lines_text).insert(HudText::Lines);
+
+    draw_rect(
+        &mut commands,
+        &asset_server,
+        &skin,
+        [10, -8, 2, 6],
+        &[2, 3, 4],
+    );
+
+    label(&mut commands, "hud.time", 13);
+
+    let time_text = draw_text(&mut commands, &asset_server, &skin, &layout, "", 14, -6);
+    commands.entity(time_text).insert(HudText::Time);
+
+    draw_rect(
+        &mut commands,
+        &asset_server,
+        &skin,
+        [13, -8, 2, 6],
+        &[2, 3, 4],
+    );
+
+    label(&mut commands, "hud.level", 16);
+
+    let level_text = draw_text(&mut commands, &asset_server, &skin, &layout, "", 17, -6);
+    commands.entity(level_text).insert(HudText::Level);
+
+    draw_rect(
+        &mut commands,
+        &asset_server,
+        &skin,
+        [16, -8, 2, 6],
+        &[2, 3, 4],
+    );
 }
 
 fn draw_text(
     commands: &mut Commands,
     asset_server: &Res<AssetServer>,
+    skin: &ActiveSkin,
+    layout: &Layout,
     text: &str,
     i: isize,
     j: isize,
-    width: f32,
-    height: f32,
-) {
-    let w = TILE_SIZE * COLUMNS as f32;
-    let h = TILE_SIZE * ROWS as f32;
-
-    let x = (width / 2.0) - (w / 2.0) + (TILE_SIZE * j as f32);
-    let y = (height / 2.0) - (h / 2.0) + (TILE_SIZE * i as f32);
-
-    commands.spawn((
-        TextComponent {},
-        TextBundle::from_section(
-            text,
-            TextStyle {
-                font: asset_server.load("fonts/FiraSans-Bold.ttf"),
-                font_size: 30.0,
-                ..default()
-            },
-        )
-        .with_text_alignment(TextAlignment::Center)
-        .with_style(Style {
-            position_type: PositionType::Absolute,
-            top: Val::Px(y),
-            left: Val::Px(x),
-            margin: UiRect {
-                top: Val::Px(-15.0),
+) -> Entity {
+    let (top, left) = layout.ui_position(i, j);
+
+    commands
+        .spawn((
+            TextComponent {},
+            SkinFont,
+            Anchored { i, j },
+            TextBundle::from_section(
+                text,
+                TextStyle {
+                    font: skin.font(asset_server),
+                    font_size: 30.0,
+                    ..default()
+                },
+            )
+            .with_text_alignment(TextAlignment::Center)
+            .with_style(Style {
+                position_type: PositionType::Absolute,
+                top: Val::Px(top),
+                left: Val::Px(left),
+                margin: UiRect {
+                    top: Val::Px(-15.0),
+                    ..default()
+                },
                 ..default()
-            },
-            ..default()
-        }),
-    ));
+            }),
+        ))
+        .id()
 }
 
 fn draw_rect(
     commands: &mut Commands,
     asset_server: &Res<AssetServer>,
-    window_query: &Query<&Window, With<PrimaryWindow>>,
+    skin: &ActiveSkin,
     coordinates: [isize; 4],
     ignore: &[isize],
 ) {
-    let window = window_query.get_single().unwrap();
-    let width = window.width();
-    let height = window.height();
-
     let [i, j, rows, columns] = coordinates;
 
-    let mut transform = to_transform(i, j, width, height);
+    let mut tile = |transform: Transform, border: Border| {
+        commands.spawn((
+            SpriteBundle {
+                transform,
+                texture: skin.border(asset_server, border),
+                ..default()
+            },
+            Tile(border),
+        ));
+    };
 
-    commands.spawn((
-        SpriteBundle {
-            transform: Transform { ..transform },
-            texture: asset_server.load("sprites/border_topleft.png"),
-            ..default()
-        },
-        Tile,
-    ));
+    let mut transform = to_transform(i, j);
+
+    tile(transform, Border::TopLeft);
     let mut t = Transform { ..transform };
     t.translation.x += TILE_SIZE * columns as f32;
-    commands.spawn((
-        SpriteBundle {
-            transform: t,
-            texture: asset_server.load("sprites/border_topright.png"),
-            ..default()
-        },
-        Tile,
-    ));
+    tile(t, Border::TopRight);
 
     transform.translation.y -= TILE_SIZE;
 
     for _ in 1..rows {
-        commands.spawn((
-            SpriteBundle {
-                transform: Transform { ..transform },
-                texture: asset_server.load("sprites/border.png"),
-                ..default()
-            },
-            Tile,
-        ));
+        tile(transform, Border::Left);
 
         let mut t = Transform { ..transform };
         t.translation.x += TILE_SIZE * columns as f32;
-        commands.spawn((
-            SpriteBundle {
-                transform: t,
-                texture: asset_server.load("sprites/border_right.png"),
-                ..default()
-            },
-            Tile,
-        ));
+        tile(t, Border::Right);
 
         transform.translation.y -= TILE_SIZE;
     }
 
-    commands.spawn((
-        SpriteBundle {
-            transform: Transform { ..transform },
-            texture: asset_server.load("sprites/border_bottomleft.png"),
-            ..default()
-        },
-        Tile,
-    ));
+    tile(transform, Border::BottomLeft);
 
     let mut t = Transform { ..transform };
     t.translation.x += TILE_SIZE * columns as f32;
 
-    commands.spawn((
-        SpriteBundle {
-            transform: t,
-            texture: asset_server.load("sprites/border_bottomright.png"),
-            ..default()
-        },
-        Tile,
-    ));
+    tile(t, Border::BottomRight);
 
-    let mut transform = to_transform(i, j, width, height);
+    let mut transform = to_transform(i, j);
     transform.translation.x += TILE_SIZE;
 
     for _j in 1..columns {
@@ -284,26 +560,12 @@ fn draw_rect(
             }
         }
         if !skip {
-            commands.spawn((
-                SpriteBundle {
-                    transform: Transform { ..transform },
-                    texture: asset_server.load("sprites/border_top.png"),
-                    ..default()
-                },
-                Tile,
-            ));
+            tile(transform, Border::Top);
         }
 
         let mut t = Transform { ..transform };
         t.translation.y -= TILE_SIZE * rows as f32;
-        commands.spawn((
-            SpriteBundle {
-                transform: t,
-                texture: asset_server.load("sprites/border_bottom.png"),
-                ..default()
-            },
-            Tile,
-        ));
+        tile(t, Border::Bottom);
 
         transform.translation.x += TILE_SIZE;
     }
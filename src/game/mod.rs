@@ -1,28 +1,63 @@
-use bevy::{audio::PlaybackMode, prelude::*};
+use std::io;
 
-use crate::board::Board;
-use crate::tetromino::Tetromino;
-use bevy::window::PrimaryWindow;
+use bevy::prelude::*;
+
+use crate::fumen::{self, FumenError};
+use crate::highscores::HighScores;
+use crate::pieces::PieceSets;
+use crate::puzzle::PuzzlePacks;
+use crate::settings::Settings;
+use crate::tbp::{self, TbpBot, TbpPlayer};
+use bevy::asset::io::file::FileAssetReader;
 
 use bevy::input::common_conditions::input_toggle_active;
 use bevy_inspector_egui::quick::WorldInspectorPlugin;
 
+use self::audio::AudioHandler;
+use self::controls::{Controls, ControlsHandler};
+use self::highscores::HighScoreHandler;
+use self::layout::LayoutHandler;
 use self::menu::MenuHandler;
-use self::tetromino::{ScheduledSound, TetrominoHandler};
+use self::music::MusicHandler;
+use self::pause::PauseHandler;
+use self::settings::SettingsHandler;
+use self::skin::SkinHandler;
+use self::tetromino::TetrominoHandler;
+use self::versus::VersusHandler;
+use crate::bot::Controller;
+
+pub use crate::engine::GameState;
+
+/// The bot playing the current game instead of the keyboard, if any.
+#[derive(Resource, Default)]
+pub struct AiPlayer {
+    pub bot: Option<Box<dyn Controller>>,
+}
 
+pub mod audio;
+pub mod controls;
+pub mod gamepad;
+pub mod highscores;
+pub mod layout;
 pub mod menu;
+pub mod music;
+pub mod pause;
+pub mod settings;
+pub mod skin;
 pub mod tetromino;
+pub mod versus;
 
 const COLUMNS: isize = 15;
 const ROWS: isize = 20;
+/// The size of a tile in the units sprites are placed in. How big it is on
+/// screen depends on the window, see `Layout`.
 const TILE_SIZE: f32 = 30.0;
 
-pub fn to_transform(i: isize, j: isize, width: f32, height: f32) -> Transform {
-    let x_m = width / 2.0;
-    let y_m = height / 2.0;
-
-    let x: f32 = x_m - ((COLUMNS as f32 * TILE_SIZE) / 2.0) + (j as f32 * TILE_SIZE);
-    let y: f32 = y_m + ((ROWS as f32 * TILE_SIZE) / 2.0) - (i as f32 * TILE_SIZE);
+/// Where the tile in row `i` and column `j` of the board goes, with the
+/// board centred on the origin.
+pub fn to_transform(i: isize, j: isize) -> Transform {
+    let x: f32 = -((COLUMNS as f32 * TILE_SIZE) / 2.0) + (j as f32 * TILE_SIZE);
+    let y: f32 = ((ROWS as f32 * TILE_SIZE) / 2.0) - (i as f32 * TILE_SIZE);
 
     Transform::from_xyz(x, y, 0.0)
 }
@@ -34,94 +69,131 @@ pub enum State {
     Running,
     Paused,
     GameOver,
+    HighScores,
+    Settings,
 }
 
-#[derive(Resource)]
-pub struct GameState {
-    pub board: Board,
-    pub timer: Timer,
-    pub next: Tetromino,
-    pub score: usize,
-    pub count: usize,
+pub fn spawn_camera(mut commands: Commands) {
+    commands.spawn(Camera2dBundle::default());
 }
 
-impl Default for GameState {
-    fn default() -> Self {
-        let mut next = Tetromino::random();
-        next.i = 4;
-        next.j = -6;
-
-        GameState {
-            board: Board::new(20, 15),
-            timer: Timer::from_seconds(1.0, TimerMode::Repeating),
-            next,
-            count: 0,
-            score: 0,
-        }
-    }
+fn load_high_scores() -> HighScores {
+    HighScores::load().unwrap_or_else(|e| {
+        warn!("Could not load high scores: {}", e);
+        HighScores::default()
+    })
 }
 
-pub fn spawn_camera(mut commands: Commands, window_query: Query<&Window, With<PrimaryWindow>>) {
-    let window = window_query.get_single().unwrap();
+fn load_settings() -> Settings {
+    Settings::load().unwrap_or_else(|e| {
+        warn!("Could not load settings: {}", e);
+        Settings::default()
+    })
+}
 
-    commands.spawn(Camera2dBundle {
-        transform: Transform::from_xyz(window.width() / 2.0, window.height() / 2.0, 0.0),
-        ..default()
-    });
+fn load_controls() -> Controls {
+    Controls::load().unwrap_or_else(|e| {
+        warn!("Could not load controls: {}", e);
+        Controls::default()
+    })
 }
 
-fn handle_game_over(
-    mut commands: Commands,
-    keyboard_input: Res<Input<KeyCode>>,
-    mut app_state: ResMut<NextState<State>>,
-    asset_server: Res<AssetServer>,
-    time: Res<Time>,
-    mut scheduled_sound: Query<(Entity, &mut ScheduledSound)>,
-) {
-    if keyboard_input.just_released(KeyCode::Space) {
-        app_state.set(State::MainMenu);
+fn load_puzzles() -> PuzzlePacks {
+    let dir = FileAssetReader::get_base_path().join("assets/puzzles");
+    let (packs, errors) = PuzzlePacks::load_dir(&dir, ROWS as usize, COLUMNS as usize);
+
+    for e in errors {
+        warn!("Could not load puzzle pack: {}", e);
     }
 
-    for (ent, mut sound) in scheduled_sound.iter_mut() {
-        sound.timer.tick(time.delta());
+    packs
+}
 
-        if sound.timer.finished() {
-            commands.spawn(AudioBundle {
-                source: asset_server.load("sounds/oogas.ogg"),
-                settings: PlaybackSettings {
-                    mode: PlaybackMode::Despawn,
-                    ..default()
-                },
-            });
+/// Loads the piece sets in the assets folder, installing the tetrominoes
+/// there as the default set. The built-in tetrominoes are kept if they
+/// cannot be loaded.
+fn load_pieces() -> PieceSets {
+    let dir = FileAssetReader::get_base_path().join("assets/pieces");
+    let (sets, errors) = PieceSets::load_dir(&dir);
 
-            commands.entity(ent).despawn();
-        }
+    for e in errors {
+        warn!("Could not load piece set: {}", e);
     }
+
+    sets
 }
 
-pub fn start_game() {
-    App::new()
-        .add_plugins(
-            DefaultPlugins
-                .set(ImagePlugin::default_nearest())
-                .set(WindowPlugin {
-                    primary_window: Some(Window {
-                        title: "R-Tetris".into(),
-                        resizable: false,
-                        ..default()
-                    }),
+fn build_app() -> App {
+    let mut app = App::new();
+
+    app.add_plugins(
+        DefaultPlugins
+            .set(ImagePlugin::default_nearest())
+            .set(WindowPlugin {
+                primary_window: Some(Window {
+                    title: "R-Tetris".into(),
                     ..default()
-                })
-                .build(),
-        )
-        .add_plugins(
-            WorldInspectorPlugin::default().run_if(input_toggle_active(false, KeyCode::Escape)),
-        )
-        .init_resource::<GameState>()
+                }),
+                ..default()
+            })
+            .build(),
+    )
+    .add_plugins(
+        WorldInspectorPlugin::default().run_if(input_toggle_active(false, KeyCode::Escape)),
+    );
+
+    let pieces = load_pieces();
+
+    app.insert_resource(pieces)
+        .insert_resource(load_high_scores())
+        .insert_resource(load_settings())
+        .insert_resource(load_controls())
+        .insert_resource(load_puzzles())
+        .init_resource::<AiPlayer>()
+        .add_plugins(SkinHandler)
+        .add_plugins(LayoutHandler)
+        .add_plugins(ControlsHandler)
         .add_plugins(TetrominoHandler)
-        .add_plugins(MenuHandler)        
+        .add_plugins(VersusHandler)
+        .add_plugins(MenuHandler)
+        .add_plugins(HighScoreHandler)
+        .add_plugins(PauseHandler)
+        .add_plugins(SettingsHandler)
+        .add_plugins(AudioHandler)
+        .add_plugins(MusicHandler)
         .add_state::<State>()
-        .add_systems(Startup, spawn_camera)
-        .add_systems(Update, handle_game_over.run_if(in_state(State::GameOver)))
+        .add_systems(Startup, spawn_camera);
+
+    app
+}
+
+pub fn start_game() {
+    build_app().init_resource::<GameState>().run();
+}
+
+/// Lets the bot run by `program` play an endless marathon, talking to it
+/// over the Tetris Bot Protocol.
+pub fn start_tbp(program: &str, args: &[String]) -> io::Result<()> {
+    let bot = TbpBot::spawn(program, args)?;
+    let mut app = build_app();
+
+    app.insert_resource(tbp::marathon())
+        .insert_resource(AiPlayer {
+            bot: Some(Box::new(TbpPlayer::new(bot))),
+        })
         .run();
+
+    Ok(())
+}
+
+/// Starts a practice game from a page of a fumen, counting pages from 0.
+pub fn start_practice(fumen: &str, page: usize) -> Result<(), FumenError> {
+    let mut app = build_app();
+
+    let pages = fumen::decode(fumen)?;
+    let (board, queue) = fumen::practice(&pages, page, ROWS as usize)?;
+
+    app.insert_resource(GameState::practice(board, queue)).run();
+
+    Ok(())
 }
@@ -0,0 +1,302 @@
+use std::time::Duration;
+
+use bevy::audio::{AddAudioSource, Decodable, PlaybackMode, Source, Volume};
+use bevy::prelude::*;
+
+use super::settings::SettingsMenu;
+use super::{GameState, State};
+use crate::settings::Settings;
+use crate::sounds::{Music, Note, SoundPacks, Theme, Track, Tune};
+
+const SAMPLE_RATE: u32 = 44_100;
+/// How loud the synthesiser plays, before the volume settings.
+const TUNE_AMPLITUDE: f32 = 0.15;
+/// Seconds notes take to fade in and out, so they don't click.
+const NOTE_FADE: f32 = 0.01;
+
+/// A tune ready to be played by Bevy's audio.
+#[derive(Asset, TypePath, Debug, Clone)]
+pub struct TuneSource {
+    notes: Vec<Note>,
+}
+
+impl Decodable for TuneSource {
+    type DecoderItem = f32;
+    type Decoder = TuneDecoder;
+
+    fn decoder(&self) -> Self::Decoder {
+        TuneDecoder {
+            notes: self.notes.clone(),
+            note: 0,
+            sample: 0,
+        }
+    }
+}
+
+/// Plays each note as a triangle wave, silent for rests.
+pub struct TuneDecoder {
+    notes: Vec<Note>,
+    note: usize,
+    sample: usize,
+}
+
+impl Iterator for TuneDecoder {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        loop {
+            let note = self.notes.get(self.note)?;
+            let length = (note.seconds * SAMPLE_RATE as f32) as usize;
+
+            if self.sample >= length {
+                self.note += 1;
+                self.sample = 0;
+                continue;
+            }
+
+            let time = self.sample as f32 / SAMPLE_RATE as f32;
+            self.sample += 1;
+
+            let Some(frequency) = note.frequency else {
+                return Some(0.0);
+            };
+
+            let phase = (time * frequency).fract();
+            let wave = 4.0 * (phase - 0.5).abs() - 1.0;
+            let envelope = (time / NOTE_FADE)
+                .min((note.seconds - time) / NOTE_FADE)
+                .clamp(0.0, 1.0);
+
+            return Some(wave * envelope * TUNE_AMPLITUDE);
+        }
+    }
+}
+
+impl Source for TuneDecoder {
+    fn current_frame_len(&self) -> Option<usize> {
+        None
+    }
+
+    fn channels(&self) -> u16 {
+        1
+    }
+
+    fn sample_rate(&self) -> u32 {
+        SAMPLE_RATE
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        let seconds = self.notes.iter().map(|note| note.seconds).sum();
+
+        Some(Duration::from_secs_f32(seconds))
+    }
+}
+
+/// A playing track, fading in until it is replaced and then fading out.
+#[derive(Component)]
+pub struct MusicTrack {
+    fade: f32,
+    leaving: bool,
+}
+
+/// The track that is playing, by pack and theme.
+#[derive(Resource, Default)]
+pub struct MusicPlayer {
+    playing: Option<(String, Theme)>,
+}
+
+pub struct MusicHandler;
+
+impl Plugin for MusicHandler {
+    fn build(&self, app: &mut App) {
+        app.add_audio_source::<TuneSource>()
+            .init_resource::<MusicPlayer>()
+            .add_systems(Update, (choose_music, play_music).chain());
+    }
+}
+
+/// Whether the stack is close enough to the top to worry about.
+fn in_danger(state: &GameState, danger_rows: usize) -> bool {
+    let rows = state.board.matrix.len().saturating_sub(2);
+
+    state.board.stack_height() + danger_rows > rows
+}
+
+/// The theme for the current screen, if the sound pack has music for it.
+fn theme(
+    app_state: State,
+    menu: &SettingsMenu,
+    state: &GameState,
+    packs: &SoundPacks,
+    settings: &Settings,
+) -> Option<Theme> {
+    let music = &packs.get(&settings.sound_pack)?.music;
+
+    let in_game = match app_state {
+        State::Running | State::Paused => true,
+        State::Settings => menu.return_to == State::Paused,
+        State::MainMenu | State::HighScores | State::GameOver => false,
+    };
+
+    let theme = if in_game {
+        let danger = in_danger(state, music.danger_rows);
+        music.game_theme(state.mode, state.level, danger)?
+    } else if app_state == State::GameOver {
+        Theme::GameOver
+    } else {
+        Theme::Menu
+    };
+
+    music.tracks.contains_key(&theme).then_some(theme)
+}
+
+/// Crossfades to the theme that fits what is going on.
+#[allow(clippy::too_many_arguments)]
+fn choose_music(
+    mut commands: Commands,
+    app_state: Res<bevy::prelude::State<State>>,
+    menu: Res<SettingsMenu>,
+    state: Res<GameState>,
+    packs: Res<SoundPacks>,
+    settings: Res<Settings>,
+    mut player: ResMut<MusicPlayer>,
+    mut tunes: ResMut<Assets<TuneSource>>,
+    asset_server: Res<AssetServer>,
+    mut tracks: Query<&mut MusicTrack>,
+) {
+    let theme = theme(*app_state.get(), &menu, &state, &packs, &settings);
+    let wanted = theme.map(|theme| (settings.sound_pack.clone(), theme));
+
+    if wanted == player.playing {
+        return;
+    }
+
+    for mut track in tracks.iter_mut() {
+        track.leaving = true;
+    }
+
+    player.playing = wanted;
+
+    let Some(theme) = theme else {
+        return;
+    };
+
+    let Some(track) = packs
+        .get(&settings.sound_pack)
+        .and_then(|pack| pack.music.tracks.get(&theme))
+    else {
+        return;
+    };
+
+    let playback = PlaybackSettings {
+        mode: PlaybackMode::Loop,
+        volume: Volume::new_absolute(0.0),
+        ..default()
+    };
+    let music = MusicTrack {
+        fade: 0.0,
+        leaving: false,
+    };
+
+    match track {
+        Track::File(file) => {
+            commands.spawn((
+                AudioBundle {
+                    source: asset_server.load(format!("sounds/{}", file)),
+                    settings: playback,
+                },
+                music,
+            ));
+        }
+        Track::Tune(tune) => {
+            commands.spawn((
+                AudioSourceBundle {
+                    source: tunes.add(tune_source(tune)),
+                    settings: playback,
+                },
+                music,
+            ));
+        }
+    }
+}
+
+fn tune_source(tune: &Tune) -> TuneSource {
+    // Packs are checked when they load, so the notes parse.
+    TuneSource {
+        notes: tune.notes().unwrap_or_default(),
+    }
+}
+
+/// Fades tracks in and out, follows the volume settings and the game's
+/// speed, and holds the music while the game is paused.
+#[allow(clippy::too_many_arguments)]
+fn play_music(
+    mut commands: Commands,
+    time: Res<Time>,
+    app_state: Res<bevy::prelude::State<State>>,
+    menu: Res<SettingsMenu>,
+    state: Res<GameState>,
+    packs: Res<SoundPacks>,
+    settings: Res<Settings>,
+    mut tracks: Query<(Entity, &mut MusicTrack, Option<&AudioSink>)>,
+) {
+    let silence = Music::default();
+    let music = packs
+        .get(&settings.sound_pack)
+        .map_or(&silence, |pack| &pack.music);
+
+    let paused = match app_state.get() {
+        State::Paused => true,
+        State::Settings => menu.return_to == State::Paused,
+        _ => false,
+    };
+
+    let speed = match app_state.get() {
+        State::Running | State::Paused => {
+            music.speed(state.level, in_danger(&state, music.danger_rows))
+        }
+        _ => 1.0,
+    };
+
+    let step = if music.crossfade > 0.0 {
+        time.delta_seconds() / music.crossfade
+    } else {
+        1.0
+    };
+
+    for (entity, mut track, sink) in tracks.iter_mut() {
+        let Some(sink) = sink else {
+            // Not started yet, so there is nothing to fade out.
+            if track.leaving {
+                commands.entity(entity).despawn();
+            }
+
+            continue;
+        };
+
+        if track.leaving {
+            track.fade -= step;
+        } else if !paused {
+            track.fade += step;
+        }
+
+        track.fade = track.fade.clamp(0.0, 1.0);
+
+        if track.leaving && track.fade <= 0.0 {
+            commands.entity(entity).despawn();
+            continue;
+        }
+
+        sink.set_volume(settings.volume.master * settings.volume.music * track.fade);
+
+        if !track.leaving && sink.speed() != speed {
+            sink.set_speed(speed);
+        }
+
+        if paused && !track.leaving {
+            sink.pause();
+        } else {
+            sink.play();
+        }
+    }
+}
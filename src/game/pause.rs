@@ -0,0 +1,199 @@
+use bevy::prelude::*;
+use bevy::window::WindowFocused;
+
+use super::controls::{Action, Actions};
+use super::settings::SettingsMenu;
+use super::skin::{ActiveSkin, SkinFont};
+use super::tetromino::hide_sprites;
+use super::{GameState, State};
+use crate::i18n;
+use crate::settings::{Language, Settings};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PauseItem {
+    Resume,
+    Restart,
+    Settings,
+    QuitToMenu,
+}
+
+const PAUSE_ITEMS: [PauseItem; 4] = [
+    PauseItem::Resume,
+    PauseItem::Restart,
+    PauseItem::Settings,
+    PauseItem::QuitToMenu,
+];
+
+/// The pause menu, and what is needed to leave it.
+#[derive(Resource, Default)]
+pub struct PauseMenu {
+    pub item: usize,
+    /// Set when going back to a paused game, so entering `State::Running`
+    /// does not set the game up again.
+    pub resuming: bool,
+    /// The game as it was when it started, to restart it from.
+    pub start: Option<GameState>,
+}
+
+#[derive(Component)]
+pub struct PauseText;
+
+pub struct PauseHandler;
+
+impl Plugin for PauseHandler {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<PauseMenu>()
+            .add_systems(
+                OnEnter(State::Running),
+                remember_start.run_if(starting_game),
+            )
+            .add_systems(Update, pause_game.run_if(in_state(State::Running)))
+            .add_systems(OnEnter(State::Paused), (on_pause, hide_sprites))
+            .add_systems(Update, handle_pause_menu.run_if(in_state(State::Paused)))
+            .add_systems(OnExit(State::Paused), on_pause_exit);
+    }
+}
+
+/// Whether entering `State::Running` starts a game, rather than resuming one.
+pub fn starting_game(pause: Res<PauseMenu>) -> bool {
+    !pause.resuming
+}
+
+fn remember_start(state: Res<GameState>, mut pause: ResMut<PauseMenu>) {
+    pause.start = Some(state.clone());
+}
+
+/// Pauses on the pause key or when the window loses focus.
+fn pause_game(
+    actions: Res<Actions>,
+    mut focus: EventReader<WindowFocused>,
+    mut pause: ResMut<PauseMenu>,
+    mut app_state: ResMut<NextState<State>>,
+) {
+    pause.bypass_change_detection().resuming = false;
+
+    let unfocused = focus.read().any(|event| !event.focused);
+
+    if unfocused || actions.just_pressed(Action::Pause) {
+        app_state.set(State::Paused);
+    }
+}
+
+fn on_pause(
+    mut commands: Commands,
+    pause: Res<PauseMenu>,
+    settings: Res<Settings>,
+    skin: Res<ActiveSkin>,
+    asset_server: Res<AssetServer>,
+) {
+    commands.spawn((
+        PauseText,
+        SkinFont,
+        TextBundle::from_section(
+            pause_text(&pause, settings.language),
+            TextStyle {
+                font: skin.font(&asset_server),
+                font_size: 40.0,
+                ..default()
+            },
+        )
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            top: Val::Px(5.0),
+            left: Val::Px(5.0),
+            ..default()
+        })
+        .with_background_color(Color::BLACK),
+    ));
+}
+
+#[allow(clippy::too_many_arguments)]
+fn handle_pause_menu(
+    mut keyboard_input: ResMut<Input<KeyCode>>,
+    actions: Res<Actions>,
+    mut state: ResMut<GameState>,
+    mut pause: ResMut<PauseMenu>,
+    mut settings_menu: ResMut<SettingsMenu>,
+    settings: Res<Settings>,
+    mut app_state: ResMut<NextState<State>>,
+    mut text: Query<&mut Text, With<PauseText>>,
+) {
+    if actions.just_pressed(Action::MenuUp) {
+        pause.item = (pause.item + PAUSE_ITEMS.len() - 1) % PAUSE_ITEMS.len();
+    }
+
+    if actions.just_pressed(Action::MenuDown) {
+        pause.item = (pause.item + 1) % PAUSE_ITEMS.len();
+    }
+
+    if pause.is_changed() {
+        for mut text in text.iter_mut() {
+            text.sections[0].value = pause_text(&pause, settings.language);
+        }
+    }
+
+    let item = if actions.just_pressed(Action::Pause) {
+        PauseItem::Resume
+    } else if actions.just_released(Action::Confirm) {
+        PAUSE_ITEMS[pause.item]
+    } else {
+        return;
+    };
+
+    match item {
+        PauseItem::Resume => {
+            pause.item = 0;
+            pause.resuming = true;
+            app_state.set(State::Running);
+        }
+        PauseItem::Restart => {
+            let Some(start) = pause.start.clone() else {
+                return;
+            };
+
+            *state = start;
+            pause.item = 0;
+            app_state.set(State::Running);
+        }
+        PauseItem::Settings => {
+            settings_menu.return_to = State::Paused;
+            app_state.set(State::Settings);
+            return;
+        }
+        PauseItem::QuitToMenu => {
+            pause.item = 0;
+            app_state.set(State::MainMenu);
+        }
+    }
+
+    // Keys held while paused should not reach the game.
+    keyboard_input.reset_all();
+}
+
+fn on_pause_exit(mut commands: Commands, text: Query<Entity, With<PauseText>>) {
+    for ent in text.iter() {
+        commands.entity(ent).despawn();
+    }
+}
+
+fn pause_text(pause: &PauseMenu, language: Language) -> String {
+    let mut text = format!("{}\n\n", i18n::text(language, "pause.title"));
+
+    for (index, item) in PAUSE_ITEMS.iter().enumerate() {
+        let marker = if index == pause.item { ">" } else { " " };
+
+        let line = match item {
+            PauseItem::Resume => "pause.resume",
+            PauseItem::Restart => "pause.restart",
+            PauseItem::Settings => "menu.settings",
+            PauseItem::QuitToMenu => "pause.quit",
+        };
+
+        text.push_str(&format!("{} {}\n", marker, i18n::text(language, line)));
+    }
+
+    text.push('\n');
+    text.push_str(i18n::text(language, "pause.help"));
+
+    text
+}
@@ -0,0 +1,516 @@
+use bevy::prelude::*;
+
+use super::controls::{Action, Actions, Controls, RESERVED_KEY};
+use super::gamepad::{Players, MAX_PLAYERS};
+use super::menu::{MenuText, TextComponent};
+use super::skin::{ActiveSkin, SkinFont};
+use super::State;
+use crate::i18n;
+use crate::settings::{Language, Settings, MAX_ARR, MAX_DAS};
+use crate::skins::Skins;
+use crate::sounds::SoundPacks;
+
+/// Milliseconds added or taken from the timings per press.
+const TIMING_STEP: u64 = 10;
+/// Added or taken from a volume per press.
+const VOLUME_STEP: f32 = 0.1;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Page {
+    General,
+    Controls,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum GeneralItem {
+    Das,
+    Arr,
+    MasterVolume,
+    MusicVolume,
+    EffectsVolume,
+    SoundPack,
+    Ghost,
+    Grid,
+    Language,
+    Skin,
+    IntegerScaling,
+    Controls,
+    Reset,
+    Back,
+}
+
+const GENERAL_ITEMS: [GeneralItem; 14] = [
+    GeneralItem::Das,
+    GeneralItem::Arr,
+    GeneralItem::MasterVolume,
+    GeneralItem::MusicVolume,
+    GeneralItem::EffectsVolume,
+    GeneralItem::SoundPack,
+    GeneralItem::Ghost,
+    GeneralItem::Grid,
+    GeneralItem::Language,
+    GeneralItem::Skin,
+    GeneralItem::IntegerScaling,
+    GeneralItem::Controls,
+    GeneralItem::Reset,
+    GeneralItem::Back,
+];
+
+/// Items on the controls page after the actions: a controller for each
+/// player, then the rest.
+const PLAYER_ITEMS: usize = Action::ALL.len();
+const RESET_ITEM: usize = PLAYER_ITEMS + MAX_PLAYERS;
+const BACK_ITEM: usize = RESET_ITEM + 1;
+const CONTROL_ITEMS: usize = BACK_ITEM + 1;
+
+/// The settings screen, and where to go back to from it.
+#[derive(Resource)]
+pub struct SettingsMenu {
+    pub page: Page,
+    pub item: usize,
+    /// Waiting for a key or button to bind to the selected action.
+    pub capturing: bool,
+    pub return_to: State,
+}
+
+impl Default for SettingsMenu {
+    fn default() -> Self {
+        SettingsMenu {
+            page: Page::General,
+            item: 0,
+            capturing: false,
+            return_to: State::MainMenu,
+        }
+    }
+}
+
+#[derive(Component)]
+pub struct SettingsText;
+
+pub struct SettingsHandler;
+
+impl Plugin for SettingsHandler {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<SettingsMenu>()
+            .add_systems(OnEnter(State::Settings), on_settings_enter)
+            .add_systems(Update, handle_settings.run_if(in_state(State::Settings)))
+            .add_systems(OnExit(State::Settings), on_settings_exit);
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn on_settings_enter(
+    mut commands: Commands,
+    menu_text: Query<Entity, With<MenuText>>,
+    mut menu: ResMut<SettingsMenu>,
+    settings: Res<Settings>,
+    controls: Res<Controls>,
+    players: Res<Players>,
+    skin: Res<ActiveSkin>,
+    asset_server: Res<AssetServer>,
+) {
+    for ent in menu_text.iter() {
+        commands.entity(ent).despawn();
+    }
+
+    menu.page = Page::General;
+    menu.item = 0;
+    menu.capturing = false;
+
+    commands.spawn((
+        TextComponent {},
+        SettingsText,
+        SkinFont,
+        TextBundle::from_section(
+            settings_text(&menu, &settings, &controls, &players),
+            TextStyle {
+                font: skin.font(&asset_server),
+                font_size: 30.0,
+                ..default()
+            },
+        )
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            top: Val::Px(5.0),
+            left: Val::Px(5.0),
+            ..default()
+        })
+        .with_background_color(Color::BLACK),
+    ));
+}
+
+/// What the settings screen reads input from.
+struct Devices<'a> {
+    keyboard: &'a Input<KeyCode>,
+    buttons: &'a Input<GamepadButton>,
+    gamepads: &'a Gamepads,
+    actions: &'a Actions,
+    sound_packs: &'a SoundPacks,
+    skins: &'a Skins,
+}
+
+impl Devices<'_> {
+    /// -1 or 1 when adjusting to the left or right, 0 otherwise.
+    fn direction(&self) -> isize {
+        if self.actions.just_pressed(Action::MenuLeft) {
+            -1
+        } else if self.actions.just_pressed(Action::MenuRight) {
+            1
+        } else {
+            0
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn handle_settings(
+    keyboard_input: Res<Input<KeyCode>>,
+    buttons: Res<Input<GamepadButton>>,
+    gamepads: Res<Gamepads>,
+    actions: Res<Actions>,
+    sound_packs: Res<SoundPacks>,
+    skins: Res<Skins>,
+    mut settings: ResMut<Settings>,
+    mut controls: ResMut<Controls>,
+    mut players: ResMut<Players>,
+    mut menu: ResMut<SettingsMenu>,
+    mut app_state: ResMut<NextState<State>>,
+    mut text: Query<&mut Text, With<SettingsText>>,
+) {
+    let input = Devices {
+        keyboard: &keyboard_input,
+        buttons: &buttons,
+        gamepads: &gamepads,
+        actions: &actions,
+        sound_packs: &sound_packs,
+        skins: &skins,
+    };
+
+    match menu.page {
+        Page::General => handle_general(&input, &mut settings, &mut menu, &mut app_state),
+        Page::Controls => handle_controls(&input, &mut controls, &mut players, &mut menu),
+    }
+
+    if menu.is_changed() || settings.is_changed() || controls.is_changed() || players.is_changed() {
+        for mut text in text.iter_mut() {
+            text.sections[0].value = settings_text(&menu, &settings, &controls, &players);
+        }
+    }
+}
+
+fn handle_general(
+    input: &Devices,
+    settings: &mut Settings,
+    menu: &mut SettingsMenu,
+    app_state: &mut NextState<State>,
+) {
+    let count = GENERAL_ITEMS.len();
+
+    if input.actions.just_pressed(Action::MenuUp) {
+        menu.item = (menu.item + count - 1) % count;
+    }
+
+    if input.actions.just_pressed(Action::MenuDown) {
+        menu.item = (menu.item + 1) % count;
+    }
+
+    let item = GENERAL_ITEMS[menu.item];
+    let direction = input.direction();
+
+    if direction != 0 && adjust(settings, item, direction, input) {
+        save(settings);
+    }
+
+    if !input.actions.just_released(Action::Confirm) {
+        return;
+    }
+
+    match item {
+        GeneralItem::Ghost | GeneralItem::Grid | GeneralItem::IntegerScaling => {
+            adjust(settings, item, 1, input);
+            save(settings);
+        }
+        GeneralItem::Controls => {
+            menu.page = Page::Controls;
+            menu.item = 0;
+        }
+        GeneralItem::Reset => {
+            *settings = Settings::default();
+            save(settings);
+        }
+        GeneralItem::Back => app_state.set(menu.return_to),
+        _ => {}
+    }
+}
+
+/// Changes a setting by one step in `direction`. Returns whether the item
+/// was a setting.
+fn adjust(settings: &mut Settings, item: GeneralItem, direction: isize, input: &Devices) -> bool {
+    let timing = |value: u64, max: u64| {
+        (value as i64 + direction as i64 * TIMING_STEP as i64).clamp(0, max as i64) as u64
+    };
+    let volume = |value: f32| (value + direction as f32 * VOLUME_STEP).clamp(0.0, 1.0);
+
+    match item {
+        GeneralItem::Das => settings.das = timing(settings.das, MAX_DAS),
+        GeneralItem::Arr => settings.arr = timing(settings.arr, MAX_ARR),
+        GeneralItem::MasterVolume => settings.volume.master = volume(settings.volume.master),
+        GeneralItem::MusicVolume => settings.volume.music = volume(settings.volume.music),
+        GeneralItem::EffectsVolume => settings.volume.effects = volume(settings.volume.effects),
+        GeneralItem::SoundPack => {
+            let names = input.sound_packs.names();
+
+            if names.is_empty() {
+                return false;
+            }
+
+            settings.sound_pack = cycle_name(&names, &settings.sound_pack, direction);
+        }
+        GeneralItem::Ghost => settings.ghost = !settings.ghost,
+        GeneralItem::Grid => settings.grid = !settings.grid,
+        GeneralItem::IntegerScaling => settings.integer_scaling = !settings.integer_scaling,
+        GeneralItem::Language => {
+            let languages = Language::ALL;
+            let index = languages
+                .iter()
+                .position(|&language| language == settings.language)
+                .unwrap_or(0) as isize;
+
+            settings.language =
+                languages[(index + direction).rem_euclid(languages.len() as isize) as usize];
+        }
+        GeneralItem::Skin => {
+            settings.skin = cycle_name(&input.skins.names(), &settings.skin, direction);
+        }
+        GeneralItem::Controls | GeneralItem::Reset | GeneralItem::Back => return false,
+    }
+
+    true
+}
+
+/// The name `direction` steps away from `current` in `names`, starting from
+/// the first if `current` is not there.
+fn cycle_name(names: &[String], current: &str, direction: isize) -> String {
+    let index = names.iter().position(|name| name == current).unwrap_or(0) as isize;
+
+    names[(index + direction).rem_euclid(names.len() as isize) as usize].clone()
+}
+
+fn handle_controls(
+    input: &Devices,
+    controls: &mut Controls,
+    players: &mut Players,
+    menu: &mut SettingsMenu,
+) {
+    let count = CONTROL_ITEMS;
+
+    if menu.capturing {
+        let action = Action::ALL[menu.item];
+        let key = input
+            .keyboard
+            .get_just_pressed()
+            .copied()
+            .find(|&key| key != RESERVED_KEY);
+
+        if let Some(key) = key {
+            menu.capturing = false;
+
+            if controls.bind(action, key) {
+                save_controls(controls);
+            }
+        } else if let Some(button) = input.buttons.get_just_pressed().next() {
+            menu.capturing = false;
+            controls.bind_button(action, button.button_type);
+            save_controls(controls);
+        }
+    } else if input.actions.just_pressed(Action::MenuUp) {
+        menu.item = (menu.item + count - 1) % count;
+    } else if input.actions.just_pressed(Action::MenuDown) {
+        menu.item = (menu.item + 1) % count;
+    } else if (PLAYER_ITEMS..RESET_ITEM).contains(&menu.item) {
+        let direction = input.direction();
+
+        if direction != 0 {
+            let player = menu.item - PLAYER_ITEMS;
+            let gamepad = cycle(input.gamepads, players.gamepad(player), direction);
+            players.assign(player, gamepad);
+        }
+    } else if input.keyboard.just_pressed(KeyCode::Back) && menu.item < PLAYER_ITEMS {
+        if controls.unbind_last(Action::ALL[menu.item]) {
+            save_controls(controls);
+        }
+    } else if input.actions.just_released(Action::Confirm) {
+        match menu.item {
+            RESET_ITEM => {
+                *controls = Controls::default();
+                save_controls(controls);
+            }
+            BACK_ITEM => {
+                menu.page = Page::General;
+                menu.item = GENERAL_ITEMS
+                    .iter()
+                    .position(|&item| item == GeneralItem::Controls)
+                    .unwrap_or(0);
+            }
+            _ => menu.capturing = true,
+        }
+    }
+}
+
+/// The connected gamepad before or after `current`, going through no
+/// gamepad at all on the way round.
+fn cycle(gamepads: &Gamepads, current: Option<Gamepad>, direction: isize) -> Option<Gamepad> {
+    let mut choices: Vec<Option<Gamepad>> = vec![None];
+    choices.extend(gamepads.iter().map(Some));
+
+    let index = choices
+        .iter()
+        .position(|&choice| choice == current)
+        .unwrap_or(0);
+
+    choices[(index as isize + direction).rem_euclid(choices.len() as isize) as usize]
+}
+
+fn save(settings: &Settings) {
+    if let Err(e) = settings.save() {
+        warn!("Could not save settings: {}", e);
+    }
+}
+
+fn save_controls(controls: &Controls) {
+    if let Err(e) = controls.save() {
+        warn!("Could not save controls: {}", e);
+    }
+}
+
+fn on_settings_exit(mut commands: Commands, text: Query<Entity, With<SettingsText>>) {
+    for ent in text.iter() {
+        commands.entity(ent).despawn();
+    }
+}
+
+fn settings_text(
+    menu: &SettingsMenu,
+    settings: &Settings,
+    controls: &Controls,
+    players: &Players,
+) -> String {
+    match menu.page {
+        Page::General => general_text(menu, settings),
+        Page::Controls => controls_text(menu, controls, players, settings.language),
+    }
+}
+
+fn general_text(menu: &SettingsMenu, settings: &Settings) -> String {
+    let language = settings.language;
+    let mut text = format!("{}\n\n", i18n::text(language, "settings.title"));
+    let percent = |volume: f32| format!("< {:.0}% >", volume * 100.0);
+    let toggle = |on: bool| {
+        let id = if on { "settings.on" } else { "settings.off" };
+        format!("< {} >", i18n::text(language, id))
+    };
+
+    for (index, item) in GENERAL_ITEMS.iter().enumerate() {
+        let marker = if index == menu.item { ">" } else { " " };
+
+        let (name, value) = match item {
+            GeneralItem::Das => ("settings.das", format!("< {} ms >", settings.das)),
+            GeneralItem::Arr => ("settings.arr", format!("< {} ms >", settings.arr)),
+            GeneralItem::MasterVolume => {
+                ("settings.master_volume", percent(settings.volume.master))
+            }
+            GeneralItem::MusicVolume => ("settings.music", percent(settings.volume.music)),
+            GeneralItem::EffectsVolume => ("settings.effects", percent(settings.volume.effects)),
+            GeneralItem::SoundPack => (
+                "settings.sound_pack",
+                format!("< {} >", settings.sound_pack),
+            ),
+            GeneralItem::Ghost => ("settings.ghost", toggle(settings.ghost)),
+            GeneralItem::Grid => ("settings.grid", toggle(settings.grid)),
+            GeneralItem::Language => ("settings.language", format!("< {} >", language.name())),
+            GeneralItem::Skin => ("settings.skin", format!("< {} >", settings.skin)),
+            GeneralItem::IntegerScaling => {
+                ("settings.integer_scaling", toggle(settings.integer_scaling))
+            }
+            GeneralItem::Controls => ("settings.controls", String::new()),
+            GeneralItem::Reset => ("settings.reset", String::new()),
+            GeneralItem::Back => ("settings.back", String::new()),
+        };
+
+        text.push_str(&format!(
+            "{} {:<20} {}\n",
+            marker,
+            i18n::text(language, name),
+            value
+        ));
+    }
+
+    text.push('\n');
+    text.push_str(i18n::text(language, "settings.help"));
+
+    text
+}
+
+fn controls_text(
+    menu: &SettingsMenu,
+    controls: &Controls,
+    players: &Players,
+    language: Language,
+) -> String {
+    let mut text = format!("{}\n\n", i18n::text(language, "settings.controls"));
+
+    for (index, action) in Action::ALL.iter().enumerate() {
+        let marker = if index == menu.item { ">" } else { " " };
+
+        let bindings = if menu.capturing && index == menu.item {
+            i18n::text(language, "settings.capturing").into()
+        } else {
+            let keys = controls
+                .keys(*action)
+                .iter()
+                .map(|key| format!("{:?}", key));
+            let buttons = controls
+                .buttons(*action)
+                .iter()
+                .map(|button| format!("{:?}", button));
+
+            keys.chain(buttons).collect::<Vec<_>>().join(", ")
+        };
+
+        text.push_str(&format!(
+            "{} {:<20} {}\n",
+            marker,
+            action.name(language),
+            bindings
+        ));
+    }
+
+    for player in 0..MAX_PLAYERS {
+        let marker = if PLAYER_ITEMS + player == menu.item {
+            ">"
+        } else {
+            " "
+        };
+        let gamepad = match players.gamepad(player) {
+            Some(gamepad) => i18n::format(language, "settings.gamepad", &[&(gamepad.id + 1)]),
+            None => i18n::text(language, "settings.no_gamepad").into(),
+        };
+
+        text.push_str(&format!(
+            "{} {:<20} < {} >\n",
+            marker,
+            i18n::format(language, "settings.player", &[&(player + 1)]),
+            gamepad
+        ));
+    }
+
+    for (index, line) in [(RESET_ITEM, "settings.reset"), (BACK_ITEM, "settings.back")] {
+        let marker = if index == menu.item { ">" } else { " " };
+        text.push_str(&format!("{} {}\n", marker, i18n::text(language, line)));
+    }
+
+    text.push('\n');
+    text.push_str(i18n::text(language, "settings.controls_help"));
+
+    text
+}
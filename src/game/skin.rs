@@ -0,0 +1,238 @@
+use std::collections::HashMap;
+
+use bevy::asset::io::file::FileAssetReader;
+use bevy::prelude::*;
+
+use super::layout::Layout;
+use super::menu::Tile;
+use crate::settings::Settings;
+use crate::skins::{Blocks, Colour, Skin, Skins};
+
+/// Which tile of a border a sprite is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Border {
+    Top,
+    Bottom,
+    Left,
+    Right,
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    BottomRight,
+}
+
+/// Text drawn in the skin's font, which changes with the skin.
+#[derive(Component)]
+pub struct SkinFont;
+
+/// The skin's background image.
+#[derive(Component)]
+pub struct SkinBackground;
+
+/// The skin in use, with its block textures loaded.
+#[derive(Resource)]
+pub struct ActiveSkin {
+    pub skin: Skin,
+    textures: HashMap<String, Handle<Image>>,
+}
+
+impl ActiveSkin {
+    fn new(skin: &Skin, asset_server: &AssetServer) -> Self {
+        let paths: Vec<&String> = match &skin.blocks {
+            Blocks::Tinted(texture) | Blocks::Atlas { texture, .. } => vec![texture],
+            Blocks::Textures {
+                default: fallback,
+                pieces,
+            } => pieces.values().chain([fallback]).collect(),
+        };
+
+        let textures = paths
+            .into_iter()
+            .map(|path| (path.clone(), asset_server.load(path.clone())))
+            .collect();
+
+        ActiveSkin {
+            skin: skin.clone(),
+            textures,
+        }
+    }
+
+    /// `skin` without its textures, to draw without an asset server.
+    #[cfg(test)]
+    pub fn unloaded(skin: &Skin) -> Self {
+        ActiveSkin {
+            skin: skin.clone(),
+            textures: HashMap::new(),
+        }
+    }
+
+    /// The colour of blocks of `name`, which would otherwise be `colour`.
+    pub fn colour(&self, name: &str, colour: Colour) -> Color {
+        let (r, g, b) = self.skin.colour(name, colour);
+
+        Color::rgb(r, g, b)
+    }
+
+    /// The texture and sprite of a block of `name` coloured `color`, which
+    /// is only applied to textures the skin tints.
+    pub fn block(&self, name: &str, color: Color) -> (Handle<Image>, Sprite) {
+        // Untinted textures still fade with the colour, for the ghost.
+        let plain = Color::WHITE.with_a(color.a());
+
+        let (path, color, rect) = match &self.skin.blocks {
+            Blocks::Tinted(texture) => (texture, color, None),
+            Blocks::Textures {
+                default: fallback,
+                pieces,
+            } => match pieces.get(name) {
+                Some(texture) => (texture, plain, None),
+                None => (fallback, color, None),
+            },
+            Blocks::Atlas {
+                texture,
+                tile_size,
+                columns,
+                tiles,
+                tint,
+                ..
+            } => {
+                let index = tiles.get(name).copied().unwrap_or(0);
+                let size = Vec2::new(tile_size.0, tile_size.1);
+                let corner = Vec2::new((index % columns) as f32, (index / columns) as f32) * size;
+                let color = if *tint { color } else { plain };
+
+                (
+                    texture,
+                    color,
+                    Some(Rect::from_corners(corner, corner + size)),
+                )
+            }
+        };
+
+        let sprite = Sprite {
+            color,
+            rect,
+            ..default()
+        };
+
+        (self.textures.get(path).cloned().unwrap_or_default(), sprite)
+    }
+
+    pub fn border(&self, asset_server: &AssetServer, border: Border) -> Handle<Image> {
+        let borders = &self.skin.borders;
+
+        let path = match border {
+            Border::Top => &borders.top,
+            Border::Bottom => &borders.bottom,
+            Border::Left => &borders.left,
+            Border::Right => &borders.right,
+            Border::TopLeft => &borders.top_left,
+            Border::TopRight => &borders.top_right,
+            Border::BottomLeft => &borders.bottom_left,
+            Border::BottomRight => &borders.bottom_right,
+        };
+
+        asset_server.load(path.clone())
+    }
+
+    pub fn font(&self, asset_server: &AssetServer) -> Handle<Font> {
+        asset_server.load(self.skin.font.clone())
+    }
+}
+
+pub struct SkinHandler;
+
+impl Plugin for SkinHandler {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(load_skins())
+            .add_systems(Startup, insert_active_skin)
+            .add_systems(Update, switch_skin.run_if(resource_changed::<Settings>()))
+            .add_systems(
+                Update,
+                apply_skin.run_if(resource_exists_and_changed::<ActiveSkin>()),
+            );
+    }
+}
+
+fn load_skins() -> Skins {
+    let dir = FileAssetReader::get_base_path().join("assets/skins");
+    let (skins, errors) = Skins::load_dir(&dir);
+
+    for e in errors {
+        warn!("Could not load skin: {}", e);
+    }
+
+    skins
+}
+
+fn insert_active_skin(
+    mut commands: Commands,
+    skins: Res<Skins>,
+    settings: Res<Settings>,
+    asset_server: Res<AssetServer>,
+) {
+    let skin = skins.get(&settings.skin);
+
+    commands.insert_resource(ActiveSkin::new(skin, &asset_server));
+}
+
+fn switch_skin(
+    skins: Res<Skins>,
+    settings: Res<Settings>,
+    asset_server: Res<AssetServer>,
+    mut active: ResMut<ActiveSkin>,
+) {
+    let skin = skins.get(&settings.skin);
+
+    if skin.name != active.skin.name {
+        *active = ActiveSkin::new(skin, &asset_server);
+    }
+}
+
+/// Dresses what is already on screen in the new skin. Blocks are redrawn
+/// by the game.
+#[allow(clippy::too_many_arguments)]
+fn apply_skin(
+    mut commands: Commands,
+    skin: Res<ActiveSkin>,
+    asset_server: Res<AssetServer>,
+    mut clear_colour: ResMut<ClearColor>,
+    layout: Res<Layout>,
+    backgrounds: Query<Entity, With<SkinBackground>>,
+    mut tiles: Query<(&mut Handle<Image>, &Tile)>,
+    mut text: Query<&mut Text, With<SkinFont>>,
+) {
+    let (r, g, b) = skin.skin.background.colour;
+    clear_colour.0 = Color::rgb(r, g, b);
+
+    for entity in backgrounds.iter() {
+        commands.entity(entity).despawn();
+    }
+
+    if let Some(image) = &skin.skin.background.image {
+        commands.spawn((
+            SpriteBundle {
+                transform: Transform::from_xyz(0.0, 0.0, -2.0),
+                sprite: Sprite {
+                    custom_size: Some(Vec2::new(layout.width, layout.height)),
+                    ..default()
+                },
+                texture: asset_server.load(image.clone()),
+                ..default()
+            },
+            SkinBackground,
+        ));
+    }
+
+    for (mut texture, tile) in tiles.iter_mut() {
+        *texture = skin.border(&asset_server, tile.0);
+    }
+
+    let font = skin.font(&asset_server);
+
+    for mut text in text.iter_mut() {
+        for section in text.sections.iter_mut() {
+            section.style.font = font.clone();
+        }
+    }
+}
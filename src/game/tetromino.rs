@@ -1,227 +1,515 @@
-use crate::board::Movement;
-use crate::tetromino::Tetromino;
-use bevy::window::PrimaryWindow;
-use bevy::{audio::PlaybackMode, prelude::*};
+use std::collections::HashMap;
 
-use super::{to_transform, GameState, State, COLUMNS, ROWS};
+use crate::board::{Cell, Movement};
+use crate::engine::Step;
+use crate::pieces::Piece;
+use crate::view::{Change, Mino, View};
+use bevy::ecs::system::EntityCommands;
+use bevy::prelude::*;
+
+use super::controls::{Action, Actions};
+use super::skin::ActiveSkin;
+use super::{to_transform, AiPlayer, GameState, State, COLUMNS, ROWS, TILE_SIZE};
+use crate::settings::Settings;
+use crate::skins::GARBAGE;
 
 pub struct TetrominoHandler;
 
 impl Plugin for TetrominoHandler {
     fn build(&self, app: &mut App) {
-        app
-        .add_systems(Update, update_tetromino.run_if(in_state(State::Running)))
-        .add_systems(
-            OnEnter(State::Running),
-            spawn_components,
-        );
+        app.add_event::<GameEvent>()
+            .add_systems(Startup, spawn_grid)
+            .add_systems(Update, update_grid)
+            .init_resource::<Sprites>()
+            .add_systems(Update, update_tetromino.run_if(in_state(State::Running)))
+            .add_systems(OnEnter(State::GameOver), hide_sprites)
+            .add_systems(OnEnter(State::Running), draw_game);
     }
 }
 
 #[derive(Component)]
 pub struct BoardComponent;
 
+/// A sprite showing a mino of the active piece, its ghost or the next piece.
 #[derive(Component)]
-pub struct TetrominoComponent {}
+pub struct TetrominoComponent {
+    pub slot: usize,
+}
+
+/// Something that happened in the game, for whatever reacts to it besides
+/// the board, like the sound effects.
+#[derive(Event, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GameEvent {
+    Move,
+    Rotate,
+    Lock {
+        lines: usize,
+        t_spin: bool,
+    },
+    /// Garbage rows sent to the opponent in versus.
+    Attack(usize),
+    LevelUp,
+    GameOver,
+}
+
+impl GameEvent {
+    pub fn from_step(step: &Step) -> Vec<GameEvent> {
+        let mut events = vec![];
+
+        if step.shifted {
+            events.push(GameEvent::Move);
+        }
+
+        if step.rotated {
+            events.push(GameEvent::Rotate);
+        }
+
+        if let Some(lines) = step.locked {
+            events.push(GameEvent::Lock {
+                lines,
+                t_spin: step.t_spin,
+            });
+        }
+
+        if step.attack > 0 {
+            events.push(GameEvent::Attack(step.attack));
+        }
 
+        if step.levelled_up {
+            events.push(GameEvent::LevelUp);
+        }
+
+        if step.over {
+            events.push(GameEvent::GameOver);
+        }
+
+        events
+    }
+}
+
+/// A sprite showing a cell of the board.
 #[derive(Component)]
-pub struct ScheduledSound {
-    pub timer: Timer,
+pub struct Block {
+    pub i: isize,
+    pub j: isize,
 }
 
+/// The sprites showing the game. They are kept from move to move and only
+/// changed where the game changed.
+#[derive(Resource, Default)]
+pub struct Sprites {
+    view: View,
+    cells: HashMap<(isize, isize), Entity>,
+    minos: Vec<Entity>,
+}
+
+type SpriteQuery<'w, 's> = Query<
+    'w,
+    's,
+    (
+        &'static mut Handle<Image>,
+        &'static mut Sprite,
+        &'static mut Transform,
+        &'static mut Visibility,
+    ),
+>;
+
+/// A cell of the board's background, shown when the grid is on.
 #[derive(Component)]
-pub struct Block {}
+pub struct GridCell;
 
-pub fn spawn_components(
+/// Shows the game on entering `State::Running`, whether it is starting or
+/// resuming, from scratch since it was hidden.
+pub fn draw_game(
     mut commands: Commands,
     state: Res<GameState>,
-    asset_server: Res<AssetServer>,
-    window_query: Query<&Window, With<PrimaryWindow>>,
+    settings: Res<Settings>,
+    skin: Res<ActiveSkin>,
+    mut sprites: ResMut<Sprites>,
+    mut query: SpriteQuery,
 ) {
-    let window = window_query.get_single().unwrap();
-    let width = window.width();
-    let height = window.height();
-
-    spawn_blocks(&state, &mut commands, width, height, &asset_server);
-
-    spawn_tetromino(
+    sprites.view.reset();
+    draw(
         &mut commands,
-        width,
-        height,
-        &asset_server,
-        &state.board.tetromino,
+        &mut sprites,
+        &mut query,
+        &state,
+        &settings,
+        &skin,
     );
+}
+
+/// Hides the game, as when it is paused or over, forgetting what was drawn
+/// so showing it again redraws everything.
+pub fn hide_sprites(mut sprites: ResMut<Sprites>, mut query: SpriteQuery) {
+    let Sprites { cells, minos, view } = &mut *sprites;
 
-    spawn_tetromino(&mut commands, width, height, &asset_server, &state.next);
+    for &entity in cells.values().chain(minos.iter()) {
+        if let Ok((_, _, _, mut visibility)) = query.get_mut(entity) {
+            *visibility = Visibility::Hidden;
+        }
+    }
+
+    view.reset();
 }
 
+#[allow(clippy::too_many_arguments)]
 pub fn update_tetromino(
     mut commands: Commands,
     time: Res<Time>,
-    keyboard_input: Res<Input<KeyCode>>,
+    actions: Res<Actions>,
+    settings: Res<Settings>,
     mut state: ResMut<GameState>,
-    tetromino: Query<Entity, With<TetrominoComponent>>,
-    blocks: Query<Entity, With<Block>>,
-    asset_server: Res<AssetServer>,
-    window_query: Query<&Window, With<PrimaryWindow>>,
+    mut ai: ResMut<AiPlayer>,
+    mut events: EventWriter<GameEvent>,
+    skin: Res<ActiveSkin>,
+    mut sprites: ResMut<Sprites>,
+    mut query: SpriteQuery,
     mut app_state: ResMut<NextState<State>>,
 ) {
-    state.timer.tick(time.delta());
-
-    let window = window_query.get_single().unwrap();
-    let width = window.width();
-    let height = window.height();
-
     let mut movement: Option<Movement> = None;
 
-    if keyboard_input.just_released(KeyCode::Up) {
+    if actions.just_pressed(Action::RotateCW) {
         movement = Some(Movement::RotateRight);
     }
 
-    if keyboard_input.pressed(KeyCode::Down) {
+    if actions.just_pressed(Action::RotateCCW) {
+        movement = Some(Movement::RotateLeft);
+    }
+
+    if actions.pressed(Action::SoftDrop) {
         movement = Some(Movement::Down);
     }
 
-    if keyboard_input.just_released(KeyCode::Left) {
+    if actions.repeated(Action::MoveLeft) {
         movement = Some(Movement::Left);
     }
 
-    if keyboard_input.just_released(KeyCode::Right) {
+    if actions.repeated(Action::MoveRight) {
         movement = Some(Movement::Right);
     }
 
-    if state.timer.finished() {
-        movement = Some(Movement::Down);
-    }
-
-    if let Some(movement) = movement {
-        let status = state.board.apply_movement(movement);
+    let mut hard_drop = actions.just_pressed(Action::HardDrop);
 
-        if !status && movement == Movement::Down {
-            state.count += 1;
-            state.board.merge();
-            let completed = state.board.check_completed_rows();
-
-            if completed > 0 {
-                commands.spawn(AudioBundle {
-                    source: asset_server.load("sounds/oogas.ogg"),
-                    settings: PlaybackSettings {
-                        mode: PlaybackMode::Despawn,
-                        ..default()
-                    },
-                });
-            } else {
-                let sound = state.count % 2;
-                let path = format!("sounds/sound{}.ogg", sound);
-
-                commands.spawn(AudioBundle {
-                    source: asset_server.load(path),
-                    settings: PlaybackSettings {
-                        mode: PlaybackMode::Despawn,
-                        ..default()
-                    },
-                });
-            }
+    if let Some(bot) = ai.bot.as_mut() {
+        movement = bot.update(time.delta(), &state);
+        hard_drop = false;
+    }
 
-            state.next.i = 2;
-            state.next.j = 2;
-            state.board.tetromino = state.next;
-            let mut next = Tetromino::random();
-            next.i = 4;
-            next.j = -6;
-            state.next = next;
-
-            if state.board.overlaps() {
-                app_state.set(State::GameOver);
-                *state = GameState::default();
-                return;
-            }
+    let step = if hard_drop {
+        state.hard_drop(time.delta())
+    } else {
+        state.update(time.delta(), movement)
+    };
 
-            for ent in &blocks {
-                commands.entity(ent).despawn();
-            }
+    events.send_batch(GameEvent::from_step(&step));
 
-            spawn_blocks(&state, &mut commands, width, height, &asset_server);
-        }
+    if step.over {
+        app_state.set(State::GameOver);
+        return;
+    }
 
-        for ent in &tetromino {
-            commands.entity(ent).despawn();
-        }
+    if skin.is_changed() {
+        sprites.view.reset();
+    }
 
-        spawn_tetromino(
+    if step.board_changed || step.moved || skin.is_changed() || settings.is_changed() {
+        draw(
             &mut commands,
-            width,
-            height,
-            &asset_server,
-            &state.board.tetromino,
+            &mut sprites,
+            &mut query,
+            &state,
+            &settings,
+            &skin,
         );
-        spawn_tetromino(&mut commands, width, height, &asset_server, &state.next);
     }
 }
 
-fn spawn_blocks(
+/// Changes the sprites that no longer match the game, spawning the ones that
+/// are needed for the first time or that were despawned.
+fn draw(
+    commands: &mut Commands,
+    sprites: &mut Sprites,
+    query: &mut SpriteQuery,
     state: &GameState,
-    commands: &mut Commands<'_, '_>,
-    width: f32,
-    height: f32,
-    asset_server: &Res<'_, AssetServer>,
+    settings: &Settings,
+    skin: &ActiveSkin,
 ) {
-    for (i, j) in state.board.blocks() {
-        let skip = match (i, j) {
-            (0, 0) => true,
-            (0, col) if col == COLUMNS - 1 => true,
-            (row, 0) if row == ROWS - 1 => true,
-            (row, col) if row == ROWS - 1 && col == COLUMNS - 1 => true,
-            (_, 0) => true,
-            (_, col) if col == COLUMNS - 1 => true,
-            (0, _) => true,
-            (row, _) if row == ROWS - 1 => true,
-            _ => false,
-        };
-
-        if skip {
-            continue;
+    for change in sprites.view.update(state, settings.ghost) {
+        match change {
+            Change::Cell { i, j, cell } => {
+                let look = cell_look(skin, cell, i, j);
+
+                match sprites.cells.get(&(i, j)) {
+                    Some(&entity) if query.contains(entity) => show(query, entity, look),
+                    _ => {
+                        let entity = spawn(commands, look).insert(Block { i, j }).id();
+                        sprites.cells.insert((i, j), entity);
+                    }
+                }
+            }
+            Change::Mino { slot, mino } => {
+                let look = mino.map(|mino| mino_look(skin, mino));
+
+                match sprites.minos.get(slot) {
+                    Some(&entity) if query.contains(entity) => show(query, entity, look),
+                    existing => {
+                        let entity = spawn(commands, look)
+                            .insert(TetrominoComponent { slot })
+                            .id();
+
+                        if existing.is_some() {
+                            sprites.minos[slot] = entity;
+                        } else {
+                            sprites.minos.push(entity);
+                        }
+                    }
+                }
+            }
         }
+    }
+}
 
-        commands.spawn((
-            SpriteBundle {
-                transform: Transform {
-                    ..to_transform(i, j, width, height)
-                },
-                texture: asset_server.load("sprites/bujaum.png"),
+/// A sprite's texture, sprite and place.
+type Look = (Handle<Image>, Sprite, Transform);
 
-                ..default()
-            },
-            Block {},
-        ));
+fn show(query: &mut SpriteQuery, entity: Entity, look: Option<Look>) {
+    let Ok((mut texture, mut sprite, mut transform, mut visibility)) = query.get_mut(entity) else {
+        return;
+    };
+
+    match look {
+        Some((new_texture, new_sprite, new_transform)) => {
+            *texture = new_texture;
+            *sprite = new_sprite;
+            *transform = new_transform;
+            *visibility = Visibility::Visible;
+        }
+        None => *visibility = Visibility::Hidden,
     }
 }
 
-fn spawn_tetromino(
-    commands: &mut Commands<'_, '_>,
-    width: f32,
-    height: f32,
-    asset_server: &Res<'_, AssetServer>,
-    tetromino: &Tetromino,
-) {
-    let rotation = tetromino.actual_rotation();
-    for i in 0..4 {
-        for j in 0..4 {
-            let (i_board, j_board) = (tetromino.i + i, tetromino.j + j);
-
-            if rotation[i as usize][j as usize] {
-                let t = to_transform(i_board, j_board, width, height);
-
-                commands.spawn((
-                    SpriteBundle {
-                        transform: t,
-                        texture: asset_server.load("sprites/bujaum.png"),
-                        visibility: Visibility::Visible,
+fn spawn<'w, 's, 'a>(
+    commands: &'a mut Commands<'w, 's>,
+    look: Option<Look>,
+) -> EntityCommands<'w, 's, 'a> {
+    let visibility = if look.is_some() {
+        Visibility::Visible
+    } else {
+        Visibility::Hidden
+    };
+    let (texture, sprite, transform) = look.unwrap_or_default();
+
+    commands.spawn(SpriteBundle {
+        texture,
+        sprite,
+        transform,
+        visibility,
+        ..default()
+    })
+}
+
+fn cell_look(skin: &ActiveSkin, cell: Cell, i: isize, j: isize) -> Option<Look> {
+    let name = match cell {
+        Cell::Piece(piece) => piece.name.as_str(),
+        Cell::Garbage => GARBAGE,
+        Cell::Empty | Cell::Wall => return None,
+    };
+
+    let (texture, sprite) = skin.block(name, cell_colour(skin, cell));
+
+    Some((texture, sprite, to_transform(i, j)))
+}
+
+/// Ghosts are faded out and drawn behind the active piece.
+fn mino_look(skin: &ActiveSkin, mino: Mino) -> Look {
+    let mut transform = to_transform(mino.i, mino.j);
+    let mut colour = piece_colour(skin, mino.piece);
+
+    if mino.ghost {
+        transform.translation.z = -0.5;
+        colour = colour.with_a(0.3);
+    }
+
+    let (texture, sprite) = skin.block(&mino.piece.name, colour);
+
+    (texture, sprite, transform)
+}
+
+fn spawn_grid(mut commands: Commands) {
+    for i in 1..ROWS - 1 {
+        for j in 1..COLUMNS - 1 {
+            let mut transform = to_transform(i, j);
+            transform.translation.z = -1.0;
+
+            commands.spawn((
+                SpriteBundle {
+                    transform,
+                    sprite: Sprite {
+                        color: Color::rgba(1.0, 1.0, 1.0, 0.05),
+                        custom_size: Some(Vec2::splat(TILE_SIZE - 2.0)),
                         ..default()
                     },
-                    TetrominoComponent {},
-                ));
-            }
+                    visibility: Visibility::Hidden,
+                    ..default()
+                },
+                GridCell,
+            ));
+        }
+    }
+}
+
+/// Shows the grid during games when the settings ask for it.
+fn update_grid(
+    settings: Res<Settings>,
+    app_state: Res<bevy::prelude::State<State>>,
+    mut cells: Query<&mut Visibility, With<GridCell>>,
+) {
+    let in_game = matches!(
+        app_state.get(),
+        State::Running | State::Paused | State::GameOver
+    );
+
+    let visibility = if settings.grid && in_game {
+        Visibility::Visible
+    } else {
+        Visibility::Hidden
+    };
+
+    for mut cell in cells.iter_mut() {
+        if *cell != visibility {
+            *cell = visibility;
+        }
+    }
+}
+
+fn piece_colour(skin: &ActiveSkin, piece: &Piece) -> Color {
+    skin.colour(&piece.name, piece.colour)
+}
+
+fn cell_colour(skin: &ActiveSkin, cell: Cell) -> Color {
+    match cell {
+        Cell::Piece(piece) => piece_colour(skin, piece),
+        Cell::Garbage => skin.colour(GARBAGE, (0.5, 0.5, 0.5)),
+        Cell::Empty | Cell::Wall => Color::WHITE,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use bevy::ecs::system::RunSystemOnce;
+
+    use super::*;
+    use crate::mode::GameMode;
+    use crate::pieces;
+    use crate::skins::Skin;
+    use crate::view;
+
+    fn world(state: GameState) -> World {
+        let mut world = World::new();
+        world.insert_resource(state);
+        world.insert_resource(Settings::default());
+        world.insert_resource(ActiveSkin::unloaded(&Skin::builtin()));
+        world.init_resource::<Sprites>();
+        world
+    }
+
+    fn game() -> GameState {
+        GameState::new(
+            GameMode::Marathon { endless: true },
+            1,
+            pieces::default_set(),
+        )
+    }
+
+    /// Checks every sprite still exists and that the visible ones are the
+    /// blocks and minos of the game.
+    fn assert_shows_game(world: &mut World) {
+        let sprites = world.resource::<Sprites>();
+        let entities: Vec<Entity> = sprites
+            .cells
+            .values()
+            .chain(sprites.minos.iter())
+            .copied()
+            .collect();
+
+        let visible = entities
+            .iter()
+            .map(|&entity| {
+                *world
+                    .get::<Visibility>(entity)
+                    .expect("sprite entity exists")
+            })
+            .filter(|&visibility| visibility == Visibility::Visible)
+            .count();
+
+        let state = world.resource::<GameState>();
+        let blocks = state
+            .board
+            .matrix
+            .iter()
+            .flatten()
+            .filter(|cell| cell.is_filled() && **cell != Cell::Wall)
+            .count();
+        let ghost = world.resource::<Settings>().ghost;
+
+        assert_eq!(visible, blocks + view::minos(state, ghost).len());
+    }
+
+    fn drop_pieces(world: &mut World, count: usize) {
+        let mut state = world.resource_mut::<GameState>();
+
+        for _ in 0..count {
+            state.hard_drop(Duration::from_millis(16));
         }
     }
+
+    #[test]
+    fn restart_shows_the_new_game() {
+        let mut world = world(game());
+        drop_pieces(&mut world, 3);
+        world.run_system_once(draw_game);
+        assert_shows_game(&mut world);
+
+        // Pause, then restart.
+        world.run_system_once(hide_sprites);
+        world.insert_resource(game());
+        world.run_system_once(draw_game);
+        assert_shows_game(&mut world);
+    }
+
+    #[test]
+    fn resume_shows_the_game_as_it_is() {
+        let mut world = world(game());
+        world.run_system_once(draw_game);
+
+        world.run_system_once(hide_sprites);
+        drop_pieces(&mut world, 3);
+        world.run_system_once(draw_game);
+        assert_shows_game(&mut world);
+    }
+
+    #[test]
+    fn despawned_sprites_are_spawned_again() {
+        let mut world = world(game());
+        drop_pieces(&mut world, 3);
+        world.run_system_once(draw_game);
+
+        let entities: Vec<Entity> = world
+            .resource::<Sprites>()
+            .cells
+            .values()
+            .copied()
+            .collect();
+
+        for entity in entities {
+            world.despawn(entity);
+        }
+
+        world.run_system_once(hide_sprites);
+        world.run_system_once(draw_game);
+        assert_shows_game(&mut world);
+    }
 }
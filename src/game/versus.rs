@@ -0,0 +1,215 @@
+use bevy::prelude::*;
+
+use super::pause::starting_game;
+use super::skin::ActiveSkin;
+use super::tetromino::{update_tetromino, GameEvent};
+use super::{to_transform, GameState, State, TILE_SIZE};
+use crate::board::Cell;
+use crate::bot::{Bot, Controller};
+use crate::mode::GameMode;
+use crate::skins::GARBAGE;
+
+/// How big the opponent's board is next to the player's.
+const SCALE: f32 = 0.5;
+/// Where the opponent's board starts, in columns of the player's board.
+const LEFT: isize = 17;
+
+/// The bot playing against the player in versus. It is only there during
+/// versus games.
+#[derive(Resource)]
+pub struct Opponent {
+    pub state: GameState,
+    pub bot: Bot,
+}
+
+impl Opponent {
+    pub fn new(player: &GameState) -> Opponent {
+        Opponent {
+            state: GameState::new(GameMode::Versus, 1, player.pieces),
+            bot: Bot::default(),
+        }
+    }
+}
+
+/// A sprite showing a cell of the opponent's board, active piece included.
+#[derive(Component)]
+pub struct OpponentBlock {
+    pub i: isize,
+    pub j: isize,
+}
+
+pub struct VersusHandler;
+
+impl Plugin for VersusHandler {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            OnEnter(State::Running),
+            (restart_opponent, spawn_board)
+                .chain()
+                .run_if(resource_exists::<Opponent>().and_then(starting_game)),
+        )
+        .add_systems(
+            Update,
+            (send_garbage, update_opponent, draw_board)
+                .chain()
+                .after(update_tetromino)
+                .run_if(in_state(State::Running).and_then(resource_exists::<Opponent>())),
+        )
+        .add_systems(OnEnter(State::Paused), hide_board)
+        .add_systems(OnEnter(State::GameOver), hide_board)
+        .add_systems(OnEnter(State::MainMenu), despawn_board);
+    }
+}
+
+/// Gives the opponent a new game whenever the player starts or restarts one.
+fn restart_opponent(state: Res<GameState>, mut opponent: ResMut<Opponent>) {
+    *opponent = Opponent::new(&state);
+}
+
+fn opponent_transform(i: isize, j: isize) -> Transform {
+    let origin = to_transform(0, LEFT).translation;
+    let tile = TILE_SIZE * SCALE;
+
+    Transform::from_xyz(origin.x + j as f32 * tile, origin.y - i as f32 * tile, 0.0)
+        .with_scale(Vec3::splat(SCALE))
+}
+
+fn spawn_board(
+    mut commands: Commands,
+    opponent: Res<Opponent>,
+    blocks: Query<Entity, With<OpponentBlock>>,
+) {
+    for entity in blocks.iter() {
+        commands.entity(entity).despawn();
+    }
+
+    for (i, row) in opponent.state.board.matrix.iter().enumerate() {
+        for j in 0..row.len() {
+            let (i, j) = (i as isize, j as isize);
+
+            commands.spawn((
+                SpriteBundle {
+                    transform: opponent_transform(i, j),
+                    visibility: Visibility::Hidden,
+                    ..default()
+                },
+                OpponentBlock { i, j },
+            ));
+        }
+    }
+}
+
+/// Sends the garbage of the player's clears to the opponent.
+fn send_garbage(mut events: EventReader<GameEvent>, mut opponent: ResMut<Opponent>) {
+    for event in events.read() {
+        if let GameEvent::Attack(rows) = event {
+            opponent.state.incoming += rows;
+        }
+    }
+}
+
+/// Plays the opponent's side and sends its garbage to the player. The
+/// player wins when the opponent tops out.
+fn update_opponent(
+    time: Res<Time>,
+    mut state: ResMut<GameState>,
+    mut opponent: ResMut<Opponent>,
+    mut app_state: ResMut<NextState<State>>,
+) {
+    // The player topped out or paused this frame.
+    if app_state.0.is_some() {
+        return;
+    }
+
+    let Opponent {
+        state: opponent_state,
+        bot,
+    } = &mut *opponent;
+
+    let movement = bot.update(time.delta(), opponent_state);
+    let step = opponent_state.update(time.delta(), movement);
+
+    state.incoming += step.attack;
+
+    if step.over {
+        state.finished = true;
+        app_state.set(State::GameOver);
+    }
+}
+
+fn draw_board(
+    skin: Res<ActiveSkin>,
+    opponent: Res<Opponent>,
+    mut blocks: Query<(
+        &OpponentBlock,
+        &mut Handle<Image>,
+        &mut Sprite,
+        &mut Visibility,
+    )>,
+) {
+    let board = &opponent.state.board;
+    let active = board.tetromino.cells();
+
+    for (block, mut texture, mut sprite, mut visibility) in blocks.iter_mut() {
+        let cell = match board.tetromino.piece() {
+            Some(piece) if active.contains(&(block.i, block.j)) => Cell::Piece(piece),
+            _ => board.matrix[block.i as usize][block.j as usize],
+        };
+
+        let (name, colour) = match cell {
+            Cell::Piece(piece) => (piece.name.as_str(), skin.colour(&piece.name, piece.colour)),
+            Cell::Garbage => (GARBAGE, skin.colour(GARBAGE, (0.5, 0.5, 0.5))),
+            Cell::Wall => (GARBAGE, Color::DARK_GRAY),
+            Cell::Empty => {
+                visibility.set_if_neq(Visibility::Hidden);
+                continue;
+            }
+        };
+
+        let (new_texture, new_sprite) = skin.block(name, colour);
+
+        texture.set_if_neq(new_texture);
+        *sprite = new_sprite;
+        visibility.set_if_neq(Visibility::Visible);
+    }
+}
+
+fn hide_board(mut blocks: Query<&mut Visibility, With<OpponentBlock>>) {
+    for mut visibility in blocks.iter_mut() {
+        *visibility = Visibility::Hidden;
+    }
+}
+
+fn despawn_board(mut commands: Commands, blocks: Query<Entity, With<OpponentBlock>>) {
+    commands.remove_resource::<Opponent>();
+
+    for entity in blocks.iter() {
+        commands.entity(entity).despawn();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::system::RunSystemOnce;
+
+    use super::*;
+
+    #[test]
+    fn sends_the_players_attacks_to_the_opponent() {
+        let state = GameState::new(GameMode::Versus, 1, crate::pieces::default_set());
+        let mut world = World::new();
+
+        world.insert_resource(Opponent::new(&state));
+        world.insert_resource(state);
+        world.init_resource::<Events<GameEvent>>();
+        world.send_event(GameEvent::Lock {
+            lines: 4,
+            t_spin: false,
+        });
+        world.send_event(GameEvent::Attack(4));
+
+        world.run_system_once(send_garbage);
+
+        assert_eq!(world.resource::<Opponent>().state.incoming, 4);
+    }
+}
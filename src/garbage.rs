@@ -0,0 +1,28 @@
+use rand::Rng;
+
+/// Picks the hole column of each garbage row. Consecutive rows never share a
+/// hole, so every row has to be dug out on its own.
+#[derive(Debug, Default, Clone)]
+pub struct GarbageGenerator {
+    previous: Option<usize>,
+}
+
+impl GarbageGenerator {
+    /// Returns a hole inside the walls of a board `columns` wide.
+    pub fn next_hole(&mut self, columns: usize) -> usize {
+        let mut rng = rand::thread_rng();
+        let mut hole = rng.gen_range(1..columns - 1);
+
+        while columns > 3 && Some(hole) == self.previous {
+            hole = rng.gen_range(1..columns - 1);
+        }
+
+        self.previous = Some(hole);
+
+        hole
+    }
+
+    pub fn holes(&mut self, columns: usize, rows: usize) -> Vec<usize> {
+        (0..rows).map(|_| self.next_hole(columns)).collect()
+    }
+}
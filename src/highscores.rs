@@ -0,0 +1,244 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+
+use crate::mode::GameMode;
+
+const TABLE_SIZE: usize = 10;
+const FILE_NAME: &str = "highscores.ron";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Record {
+    pub name: String,
+    pub score: usize,
+    pub lines: usize,
+    pub level: usize,
+    pub duration: Duration,
+    pub date: DateTime<Local>,
+}
+
+#[cfg_attr(feature = "bevy", derive(bevy::prelude::Resource))]
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct HighScores {
+    tables: BTreeMap<GameMode, Vec<Record>>,
+}
+
+impl HighScores {
+    pub fn path() -> Option<PathBuf> {
+        dirs::data_dir().map(|dir| dir.join("rtetris").join(FILE_NAME))
+    }
+
+    pub fn load() -> io::Result<HighScores> {
+        let Some(path) = HighScores::path() else {
+            return Ok(HighScores::default());
+        };
+
+        if !path.exists() {
+            return Ok(HighScores::default());
+        }
+
+        let contents = fs::read_to_string(path)?;
+
+        ron::from_str(&contents).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    pub fn save(&self) -> io::Result<()> {
+        let Some(path) = HighScores::path() else {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                "no data directory available",
+            ));
+        };
+
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        let contents = ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+
+        fs::write(path, contents)
+    }
+
+    pub fn table(&self, mode: GameMode) -> &[Record] {
+        self.tables.get(&mode).map(Vec::as_slice).unwrap_or(&[])
+    }
+
+    pub fn best(&self, mode: GameMode) -> Option<&Record> {
+        self.table(mode).first()
+    }
+
+    pub fn qualifies(&self, mode: GameMode, record: &Record) -> bool {
+        let table = self.table(mode);
+
+        table.len() < TABLE_SIZE || table.iter().any(|other| beats(mode, record, other))
+    }
+
+    /// Inserts the record in its ranked position and returns that position,
+    /// or `None` if it did not make it into the table.
+    pub fn insert(&mut self, mode: GameMode, record: Record) -> Option<usize> {
+        let table = self.tables.entry(mode).or_default();
+        let position = table
+            .iter()
+            .position(|other| beats(mode, &record, other))
+            .unwrap_or(table.len());
+
+        if position >= TABLE_SIZE {
+            return None;
+        }
+
+        table.insert(position, record);
+        table.truncate(TABLE_SIZE);
+
+        Some(position)
+    }
+}
+
+fn beats(mode: GameMode, record: &Record, other: &Record) -> bool {
+    if mode.ranks_by_time() {
+        record.duration < other.duration
+    } else {
+        record.score > other.score
+    }
+}
+
+pub fn format_duration(duration: Duration) -> String {
+    let seconds = duration.as_secs();
+
+    format!(
+        "{:02}:{:02}.{:03}",
+        seconds / 60,
+        seconds % 60,
+        duration.subsec_millis()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MARATHON: GameMode = GameMode::Marathon { endless: false };
+
+    fn record(name: &str, score: usize, seconds: u64) -> Record {
+        Record {
+            name: name.into(),
+            score,
+            lines: 0,
+            level: 1,
+            duration: Duration::from_secs(seconds),
+            date: Local::now(),
+        }
+    }
+
+    fn names(scores: &HighScores, mode: GameMode) -> Vec<&str> {
+        scores
+            .table(mode)
+            .iter()
+            .map(|record| record.name.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn ranks_by_highest_score() {
+        let mut scores = HighScores::default();
+
+        assert_eq!(scores.insert(MARATHON, record("b", 200, 60)), Some(0));
+        assert_eq!(scores.insert(MARATHON, record("c", 100, 10)), Some(1));
+        assert_eq!(scores.insert(MARATHON, record("a", 300, 90)), Some(0));
+
+        assert_eq!(names(&scores, MARATHON), ["a", "b", "c"]);
+        assert_eq!(scores.best(MARATHON).unwrap().name, "a");
+    }
+
+    #[test]
+    fn ties_go_below_the_records_they_match() {
+        let mut scores = HighScores::default();
+
+        scores.insert(MARATHON, record("first", 100, 60));
+
+        assert_eq!(scores.insert(MARATHON, record("second", 100, 30)), Some(1));
+        assert_eq!(names(&scores, MARATHON), ["first", "second"]);
+    }
+
+    #[test]
+    fn keeps_the_best_ten() {
+        let mut scores = HighScores::default();
+
+        for n in 1..=TABLE_SIZE {
+            let record = record(&n.to_string(), n * 100, 60);
+
+            assert!(scores.qualifies(MARATHON, &record));
+            scores.insert(MARATHON, record);
+        }
+
+        assert_eq!(scores.table(MARATHON).len(), TABLE_SIZE);
+
+        // Tying the lowest score is not enough once the table is full.
+        let tie = record("tie", 100, 60);
+        assert!(!scores.qualifies(MARATHON, &tie));
+        assert_eq!(scores.insert(MARATHON, tie), None);
+
+        let better = record("better", 150, 60);
+        assert!(scores.qualifies(MARATHON, &better));
+        assert_eq!(scores.insert(MARATHON, better), Some(TABLE_SIZE - 1));
+
+        assert_eq!(scores.table(MARATHON).len(), TABLE_SIZE);
+        assert_eq!(scores.table(MARATHON).last().unwrap().name, "better");
+        assert!(!names(&scores, MARATHON).contains(&"1"));
+    }
+
+    #[test]
+    fn keeps_a_table_per_mode() {
+        let mut scores = HighScores::default();
+        let endless = GameMode::Marathon { endless: true };
+
+        scores.insert(MARATHON, record("a", 100, 60));
+
+        assert!(scores.table(endless).is_empty());
+        assert!(scores.best(endless).is_none());
+    }
+
+    #[test]
+    fn loads_tables_saved_with_a_replay_field() {
+        let mut scores = HighScores::default();
+        scores.insert(MARATHON, record("a", 100, 60));
+
+        let saved = ron::to_string(&scores)
+            .unwrap()
+            .replace("date:", "replay:None,date:");
+        let loaded: HighScores = ron::from_str(&saved).unwrap();
+
+        assert!(saved.contains("replay:None"));
+        assert_eq!(names(&loaded, MARATHON), ["a"]);
+    }
+
+    #[test]
+    fn races_rank_by_lowest_time() {
+        for mode in [
+            GameMode::Sprint { goal: 40 },
+            GameMode::Dig {
+                rows: 8,
+                rising: false,
+            },
+        ] {
+            let mut scores = HighScores::default();
+
+            scores.insert(mode, record("slow", 900, 120));
+            scores.insert(mode, record("fast", 100, 45));
+            scores.insert(mode, record("middle", 500, 60));
+
+            assert_eq!(
+                names(&scores, mode),
+                ["fast", "middle", "slow"],
+                "{:?}",
+                mode
+            );
+            assert_eq!(scores.best(mode).unwrap().duration, Duration::from_secs(45));
+        }
+    }
+}
@@ -0,0 +1,321 @@
+//! User-facing text, looked up by message id in the player's language.
+//!
+//! Every language has a bundle of `(id, text)` pairs. English is the
+//! reference bundle: a message missing from another language is shown in
+//! English, and one missing from English too is shown as its id, so a typo
+//! in an id shows up on screen instead of as blank text.
+
+use crate::settings::Language;
+
+type Bundle = &'static [(&'static str, &'static str)];
+
+const ENGLISH: Bundle = &[
+    ("menu.title", "HERE COMES THE GAAAAAAAAAAS!!!!!!"),
+    ("menu.marathon", "Marathon"),
+    ("menu.endless", "Endless marathon"),
+    ("menu.sprint", "Sprint"),
+    ("menu.ultra", "Ultra"),
+    ("menu.dig", "Dig"),
+    ("menu.rising_dig", "Rising dig"),
+    ("menu.pieces", "Pieces"),
+    ("menu.watch_ai", "Watch the AI"),
+    ("menu.versus", "Versus the AI"),
+    ("menu.puzzle", "Puzzle"),
+    ("menu.settings", "Settings"),
+    ("menu.high_scores", "High scores"),
+    ("menu.level", "level {0}"),
+    ("menu.lines", "{0} lines"),
+    ("menu.none", "none"),
+    (
+        "menu.help",
+        "Up/down choose, left/right adjust, space starts.",
+    ),
+    ("hud.score", "Score"),
+    ("hud.next", " Next"),
+    ("hud.lines", " Lines"),
+    ("hud.left", " Left"),
+    ("hud.time", " Time"),
+    ("hud.level", " Level"),
+    ("mode.marathon", "Marathon"),
+    ("mode.endless", "Marathon (endless)"),
+    ("mode.sprint", "Sprint {0}"),
+    ("mode.ultra", "Ultra {0}"),
+    ("mode.dig", "Dig {0}"),
+    ("mode.rising_dig", "Dig {0} (rising)"),
+    ("mode.puzzle", "Puzzle"),
+    ("mode.practice", "Practice"),
+    ("mode.versus", "Versus"),
+    ("goal.one_line", "Clear 1 line"),
+    ("goal.lines", "Clear {0} lines"),
+    ("goal.perfect_clear", "Clear the whole board"),
+    ("goal.t_spin_double", "Do a T-spin double"),
+    ("summary.finished", "Finished! Time: {0}  PPS: {1}"),
+    ("summary.personal_best", "Personal best: {0}"),
+    ("summary.new_best", "New personal best!"),
+    ("summary.time_up", "Time's up! Score: {0}"),
+    ("summary.lost", "Game over! Score: {0}"),
+    ("summary.clear", "{0}: {1} ({2} points)"),
+    ("summary.solved", "Puzzle solved! {0}"),
+    ("summary.failed", "Not this time. {0}"),
+    ("summary.marathon_complete", "Marathon complete! Score: {0}"),
+    ("summary.won", "You win! The AI topped out."),
+    ("summary.beaten", "The AI wins this one."),
+    ("summary.stats", "Lines: {0}  Level: {1}  Time: {2}"),
+    ("clear.single", "Single"),
+    ("clear.double", "Double"),
+    ("clear.triple", "Triple"),
+    ("clear.tetris", "Tetris"),
+    ("clear.t_spin_single", "T-Spin Single"),
+    ("clear.t_spin_double", "T-Spin Double"),
+    ("clear.t_spin_triple", "T-Spin Triple"),
+    ("scores.new_record", "New high score! Type your name: {0}_"),
+    ("scores.confirm", "Press enter to confirm."),
+    ("scores.back", "Press space to go back to the menu."),
+    ("scores.title", "High scores - {0}"),
+    ("scores.empty", "Nobody has played yet."),
+    ("scores.lines", "lines"),
+    ("scores.level", "level"),
+    ("scores.save_failed", "Could not save high scores: {0}"),
+    (
+        "scores.help",
+        "Left/right change the mode. Press space to go back to the menu.",
+    ),
+    ("pause.title", "Paused"),
+    ("pause.resume", "Resume"),
+    ("pause.restart", "Restart"),
+    ("pause.quit", "Quit to menu"),
+    ("pause.help", "Up/down choose, space confirms, P resumes."),
+    ("settings.title", "Settings"),
+    ("settings.das", "DAS"),
+    ("settings.arr", "ARR"),
+    ("settings.master_volume", "Master volume"),
+    ("settings.music", "Music"),
+    ("settings.effects", "Sound effects"),
+    ("settings.sound_pack", "Sound pack"),
+    ("settings.ghost", "Ghost piece"),
+    ("settings.grid", "Grid"),
+    ("settings.language", "Language"),
+    ("settings.skin", "Skin"),
+    ("settings.integer_scaling", "Integer scaling"),
+    ("settings.controls", "Controls"),
+    ("settings.reset", "Restore defaults"),
+    ("settings.back", "Back"),
+    ("settings.on", "on"),
+    ("settings.off", "off"),
+    ("settings.help", "Up/down choose, left/right adjust."),
+    ("settings.capturing", "press a key or button..."),
+    ("settings.player", "Player {0}"),
+    ("settings.gamepad", "gamepad {0}"),
+    ("settings.no_gamepad", "none"),
+    (
+        "settings.controls_help",
+        "Confirm adds a key or button, backspace removes the last one.",
+    ),
+    ("action.move_left", "Move left"),
+    ("action.move_right", "Move right"),
+    ("action.soft_drop", "Soft drop"),
+    ("action.hard_drop", "Hard drop"),
+    ("action.rotate_cw", "Rotate clockwise"),
+    ("action.rotate_ccw", "Rotate counter-clockwise"),
+    ("action.pause", "Pause"),
+    ("action.menu_up", "Menu: up"),
+    ("action.menu_down", "Menu: down"),
+    ("action.menu_left", "Menu: left"),
+    ("action.menu_right", "Menu: right"),
+    ("action.confirm", "Menu: confirm"),
+    (
+        "tui.help",
+        "Up/down choose, enter starts, a watches the AI, v plays it, q quits.",
+    ),
+    ("tui.back", "Press enter to go back to the menu."),
+];
+
+/// Names that read the same in Portuguese, like "Sprint" and "DAS", are left
+/// out and come from the English bundle.
+const PORTUGUESE: Bundle = &[
+    ("menu.title", "Ó O GÁAAAAAAAAAAS!!!!!!"),
+    ("menu.marathon", "Maratona"),
+    ("menu.endless", "Maratona sem fim"),
+    ("menu.dig", "Escavação"),
+    ("menu.rising_dig", "Escavação subindo"),
+    ("menu.pieces", "Peças"),
+    ("menu.watch_ai", "Assistir a IA"),
+    ("menu.versus", "Contra a IA"),
+    ("menu.settings", "Configurações"),
+    ("menu.high_scores", "Recordes"),
+    ("menu.level", "nível {0}"),
+    ("menu.lines", "{0} linhas"),
+    ("menu.none", "nenhum"),
+    (
+        "menu.help",
+        "Cima/baixo escolhem, esquerda/direita ajustam, espaço começa.",
+    ),
+    ("hud.score", "Pontos"),
+    ("hud.next", " Próxima"),
+    ("hud.lines", " Linhas"),
+    ("hud.left", " Faltam"),
+    ("hud.time", " Tempo"),
+    ("hud.level", " Nível"),
+    ("mode.marathon", "Maratona"),
+    ("mode.endless", "Maratona (sem fim)"),
+    ("mode.dig", "Escavação {0}"),
+    ("mode.rising_dig", "Escavação {0} (subindo)"),
+    ("mode.practice", "Treino"),
+    ("mode.versus", "Contra"),
+    ("goal.one_line", "Limpe 1 linha"),
+    ("goal.lines", "Limpe {0} linhas"),
+    ("goal.perfect_clear", "Limpe o tabuleiro inteiro"),
+    ("goal.t_spin_double", "Faça um T-spin double"),
+    ("summary.finished", "Terminou! Tempo: {0}  PPS: {1}"),
+    ("summary.personal_best", "Recorde pessoal: {0}"),
+    ("summary.new_best", "Novo recorde pessoal!"),
+    ("summary.time_up", "Acabou o tempo! Score: {0}"),
+    ("summary.lost", "Perdeu, mané! Score: {0}"),
+    ("summary.clear", "{0}: {1} ({2} pontos)"),
+    ("summary.solved", "Puzzle resolvido! {0}"),
+    ("summary.failed", "Não foi dessa vez. {0}"),
+    ("summary.marathon_complete", "Maratona completa! Score: {0}"),
+    ("summary.won", "Ganhou! A IA não aguentou."),
+    ("summary.beaten", "Dessa vez a IA levou."),
+    ("summary.stats", "Linhas: {0}  Nível: {1}  Tempo: {2}"),
+    ("clear.single", "Simples"),
+    ("clear.double", "Dupla"),
+    ("clear.triple", "Tripla"),
+    ("clear.t_spin_single", "T-Spin Simples"),
+    ("clear.t_spin_double", "T-Spin Dupla"),
+    ("clear.t_spin_triple", "T-Spin Tripla"),
+    ("scores.new_record", "Novo recorde! Digite seu nome: {0}_"),
+    ("scores.confirm", "Aperte enter pra confirmar."),
+    ("scores.back", "Aperte espaço pra voltar pro menu."),
+    ("scores.title", "Recordes - {0}"),
+    ("scores.empty", "Ninguém jogou ainda."),
+    ("scores.lines", "linhas"),
+    ("scores.level", "nível"),
+    ("scores.save_failed", "Não deu pra salvar os recordes: {0}"),
+    (
+        "scores.help",
+        "Esquerda/direita trocam o modo. Aperte espaço pra voltar pro menu.",
+    ),
+    ("pause.title", "Pausado"),
+    ("pause.resume", "Continuar"),
+    ("pause.restart", "Recomeçar"),
+    ("pause.quit", "Voltar ao menu"),
+    (
+        "pause.help",
+        "Cima/baixo escolhem, espaço confirma, P continua.",
+    ),
+    ("settings.title", "Configurações"),
+    ("settings.master_volume", "Volume geral"),
+    ("settings.music", "Música"),
+    ("settings.effects", "Efeitos"),
+    ("settings.sound_pack", "Pacote de sons"),
+    ("settings.ghost", "Peça fantasma"),
+    ("settings.grid", "Grade"),
+    ("settings.language", "Idioma"),
+    ("settings.skin", "Visual"),
+    ("settings.integer_scaling", "Escala inteira"),
+    ("settings.controls", "Controles"),
+    ("settings.reset", "Restaurar padrões"),
+    ("settings.back", "Voltar"),
+    ("settings.on", "sim"),
+    ("settings.off", "não"),
+    (
+        "settings.help",
+        "Cima/baixo escolhem, esquerda/direita ajustam.",
+    ),
+    ("settings.capturing", "aperte uma tecla ou botão..."),
+    ("settings.player", "Jogador {0}"),
+    ("settings.gamepad", "controle {0}"),
+    ("settings.no_gamepad", "nenhum"),
+    (
+        "settings.controls_help",
+        "Confirmar adiciona uma tecla ou botão, backspace remove o último.",
+    ),
+    ("action.move_left", "Mover pra esquerda"),
+    ("action.move_right", "Mover pra direita"),
+    ("action.soft_drop", "Descer"),
+    ("action.hard_drop", "Derrubar"),
+    ("action.rotate_cw", "Girar horário"),
+    ("action.rotate_ccw", "Girar anti-horário"),
+    ("action.pause", "Pausar"),
+    ("action.menu_up", "Menu: cima"),
+    ("action.menu_down", "Menu: baixo"),
+    ("action.menu_left", "Menu: esquerda"),
+    ("action.menu_right", "Menu: direita"),
+    ("action.confirm", "Menu: confirmar"),
+    (
+        "tui.help",
+        "Cima/baixo escolhem, enter começa, a assiste a IA, v joga contra ela, q sai.",
+    ),
+    ("tui.back", "Aperte enter pra voltar pro menu."),
+];
+
+fn bundle(language: Language) -> Bundle {
+    match language {
+        Language::English => ENGLISH,
+        Language::Portuguese => PORTUGUESE,
+    }
+}
+
+fn find(bundle: Bundle, id: &str) -> Option<&'static str> {
+    bundle
+        .iter()
+        .find(|(key, _)| *key == id)
+        .map(|(_, text)| *text)
+}
+
+/// The text of message `id` in `language`.
+pub fn text(language: Language, id: &str) -> &str {
+    find(bundle(language), id)
+        .or_else(|| find(ENGLISH, id))
+        .unwrap_or(id)
+}
+
+/// The text of message `id` in `language`, with each `{n}` replaced by the
+/// n-th argument. Numbering the placeholders lets a translation put them in
+/// a different order.
+pub fn format(language: Language, id: &str, args: &[&dyn std::fmt::Display]) -> String {
+    let mut result = text(language, id).to_string();
+
+    for (index, arg) in args.iter().enumerate() {
+        result = result.replace(&format!("{{{}}}", index), &arg.to_string());
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn falls_back_to_english() {
+        assert!(find(PORTUGUESE, "mode.sprint").is_none());
+        assert_eq!(text(Language::Portuguese, "mode.sprint"), "Sprint {0}");
+        assert_eq!(
+            format(Language::Portuguese, "clear.tetris", &[]),
+            text(Language::English, "clear.tetris")
+        );
+    }
+
+    #[test]
+    fn shows_unknown_ids_as_they_are() {
+        assert_eq!(text(Language::English, "menu.nope"), "menu.nope");
+        assert_eq!(text(Language::Portuguese, "menu.nope"), "menu.nope");
+    }
+
+    #[test]
+    fn every_translation_has_an_english_original() {
+        for (id, _) in PORTUGUESE {
+            assert!(find(ENGLISH, id).is_some(), "{}", id);
+        }
+    }
+
+    #[test]
+    fn fills_in_arguments_by_position() {
+        assert_eq!(
+            format(Language::English, "summary.stats", &[&12, &3, &"1:02"]),
+            "Lines: 12  Level: 3  Time: 1:02"
+        );
+    }
+}
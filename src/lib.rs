@@ -0,0 +1,24 @@
+pub mod board;
+pub mod bot;
+pub mod engine;
+pub mod environment;
+pub mod fumen;
+#[cfg(feature = "bevy")]
+pub mod game;
+pub mod garbage;
+pub mod highscores;
+pub mod i18n;
+pub mod mode;
+pub mod patterns;
+pub mod pieces;
+pub mod placements;
+pub mod puzzle;
+pub mod scoring;
+pub mod settings;
+pub mod skins;
+pub mod sounds;
+pub mod tbp;
+pub mod tetromino;
+#[cfg(feature = "tui")]
+pub mod tui;
+pub mod view;
@@ -1,40 +1,60 @@
-use game::start_game;
-
-pub mod board;
-pub mod game;
-pub mod patterns;
-pub mod tetromino;
-
-fn main() {
-    start_game();
-    /*App::new()
-    .add_plugins(
-        DefaultPlugins
-            .set(ImagePlugin::default_nearest())
-            .set(WindowPlugin {
-                primary_window: Some(Window {
-                    title: "R-Tetris".into(),
-                    resizable: false,
-                    ..default()
-                }),
-                ..default()
-            })
-            .build(),
-    )
-    .add_plugins(
-        WorldInspectorPlugin::default().run_if(input_toggle_active(false, KeyCode::Escape)),
-    )
-    .init_resource::<GameState>()
-    .add_state::<State>()
-    .add_systems(Startup, spawn_camera)
-    .add_systems(Update, handle_main_menu.run_if(in_state(State::MainMenu)))
-    .add_systems(Update, handle_game_over.run_if(in_state(State::GameOver)))
-    .add_systems(OnEnter(State::MainMenu), on_menu_enter)
-    .add_systems(
-        OnEnter(State::Running),
-        (draw_rectangle, spawn_components, on_game_start),
-    )
-    .add_systems(Update, update_tetromino.run_if(in_state(State::Running)))
-    .add_systems(OnEnter(State::GameOver), on_game_over)
-    .run();*/
+use std::io;
+use std::process::ExitCode;
+
+use rtetris::fumen;
+use rtetris::game::{start_game, start_practice, start_tbp};
+
+const USAGE: &str = "usage:
+    rtetris
+    rtetris fumen decode <fumen>    print the pages of a fumen as text
+    rtetris fumen encode            read text pages from stdin, print a fumen
+    rtetris practice <fumen> [page] play from a fumen page, the first by default
+    rtetris tbp <bot> [args...]     watch a Tetris Bot Protocol bot play";
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+
+    let result = match args.as_slice() {
+        [] => {
+            start_game();
+            Ok(())
+        }
+        ["fumen", command @ ..] => match fumen::command(command, &mut io::stdin()) {
+            Some(result) => result.map(|output| print!("{}", output)),
+            None => {
+                eprintln!("{}", USAGE);
+                return ExitCode::FAILURE;
+            }
+        },
+        ["practice", data] => start_practice(data, 0),
+        ["practice", data, page] => match page.parse::<usize>() {
+            Ok(page) if page > 0 => start_practice(data, page - 1),
+            _ => {
+                eprintln!("page must be a number from 1\n{}", USAGE);
+                return ExitCode::FAILURE;
+            }
+        },
+        ["tbp", program, bot_args @ ..] => {
+            let bot_args: Vec<String> = bot_args.iter().map(|arg| arg.to_string()).collect();
+
+            if let Err(e) = start_tbp(program, &bot_args) {
+                eprintln!("could not start bot: {}", e);
+                return ExitCode::FAILURE;
+            }
+
+            Ok(())
+        }
+        _ => {
+            eprintln!("{}", USAGE);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    if let Err(e) = result {
+        eprintln!("{}", e);
+        return ExitCode::FAILURE;
+    }
+
+    ExitCode::SUCCESS
 }
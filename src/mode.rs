@@ -0,0 +1,104 @@
+use serde::{Deserialize, Serialize};
+
+use crate::i18n;
+use crate::settings::Language;
+
+pub const MARATHON_LEVELS: usize = 15;
+pub const LINES_PER_LEVEL: usize = 10;
+pub const SPRINT_GOALS: [usize; 3] = [20, 40, 100];
+pub const ULTRA_DURATIONS: [u64; 3] = [120, 180, 300];
+pub const DIG_ROWS: [usize; 3] = [4, 8, 12];
+pub const DIG_RISE_SECONDS: f32 = 5.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum GameMode {
+    Marathon {
+        endless: bool,
+    },
+    Sprint {
+        goal: usize,
+    },
+    Ultra {
+        seconds: u64,
+    },
+    Dig {
+        rows: usize,
+        rising: bool,
+    },
+    Puzzle,
+    Practice,
+    /// Against an opponent with a board of their own, sending each other
+    /// garbage. Frontends run both games and set `GameState::finished` on
+    /// the one left standing.
+    Versus,
+}
+
+impl GameMode {
+    pub fn all() -> Vec<GameMode> {
+        let mut modes = vec![
+            GameMode::Marathon { endless: false },
+            GameMode::Marathon { endless: true },
+        ];
+        modes.extend(SPRINT_GOALS.iter().map(|&goal| GameMode::Sprint { goal }));
+        modes.extend(
+            ULTRA_DURATIONS
+                .iter()
+                .map(|&seconds| GameMode::Ultra { seconds }),
+        );
+
+        for rising in [false, true] {
+            modes.extend(DIG_ROWS.iter().map(|&rows| GameMode::Dig { rows, rising }));
+        }
+
+        modes
+    }
+
+    pub fn name(&self, language: Language) -> String {
+        match self {
+            GameMode::Marathon { endless: false } => i18n::text(language, "mode.marathon").into(),
+            GameMode::Marathon { endless: true } => i18n::text(language, "mode.endless").into(),
+            GameMode::Sprint { goal } => i18n::format(language, "mode.sprint", &[goal]),
+            GameMode::Ultra { seconds } => i18n::format(
+                language,
+                "mode.ultra",
+                &[&format!("{}:{:02}", seconds / 60, seconds % 60)],
+            ),
+            GameMode::Dig {
+                rows,
+                rising: false,
+            } => i18n::format(language, "mode.dig", &[rows]),
+            GameMode::Dig { rows, rising: true } => {
+                i18n::format(language, "mode.rising_dig", &[rows])
+            }
+            GameMode::Puzzle => i18n::text(language, "mode.puzzle").into(),
+            GameMode::Practice => i18n::text(language, "mode.practice").into(),
+            GameMode::Versus => i18n::text(language, "mode.versus").into(),
+        }
+    }
+
+    /// Modes racing to a line goal rank records by completion time instead of score.
+    pub fn ranks_by_time(&self) -> bool {
+        matches!(self, GameMode::Sprint { .. } | GameMode::Dig { .. })
+    }
+
+    /// Puzzles are solved or failed, practice boards are set up by hand and
+    /// versus games are won or lost, so none of them has a high score table.
+    pub fn keeps_records(&self) -> bool {
+        !matches!(
+            self,
+            GameMode::Puzzle | GameMode::Practice | GameMode::Versus
+        )
+    }
+
+    /// Only Marathon speeds up as lines are cleared; the other modes play at
+    /// their starting level throughout.
+    pub fn levels_up(&self) -> bool {
+        matches!(self, GameMode::Marathon { .. })
+    }
+}
+
+impl Default for GameMode {
+    fn default() -> Self {
+        GameMode::Marathon { endless: false }
+    }
+}
@@ -1,137 +1,74 @@
-pub type Pattern = [[bool; 4]; 4];
+/// The cells a piece covers in one orientation, as `(row, column)` offsets
+/// inside a square box. The box is as large as the furthest cell needs, so
+/// rotating inside it turns the piece around the box's centre.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Pattern {
+    size: usize,
+    cells: Vec<(usize, usize)>,
+}
 
-const ___: bool = false;
-const _X_: bool = true;
+impl Pattern {
+    /// Builds a pattern from `(row, column)` cells, failing if there are none
+    /// or the same cell is listed twice.
+    pub fn from_cells(cells: &[(usize, usize)]) -> Result<Pattern, String> {
+        if cells.is_empty() {
+            return Err("orientation has no cells".into());
+        }
 
-pub const I_BLOCK_PATTERNS: [Pattern; 2] = [
-    [
-        [___, _X_, ___, ___],
-        [___, _X_, ___, ___],
-        [___, _X_, ___, ___],
-        [___, _X_, ___, ___],
-    ],
-    [
-        [___, ___, ___, ___],
-        [___, ___, ___, ___],
-        [___, ___, ___, ___],
-        [_X_, _X_, _X_, _X_],
-    ],
-];
+        let mut sorted = cells.to_vec();
+        sorted.sort();
+        sorted.dedup();
 
-pub const O_BLOCK_PATTERNS: [Pattern; 1] = [[
-    [___, ___, ___, ___],
-    [___, ___, ___, ___],
-    [___, _X_, _X_, ___],
-    [___, _X_, _X_, ___],
-]];
+        if sorted.len() != cells.len() {
+            return Err("orientation lists the same cell twice".into());
+        }
 
-pub const L_BLOCK_PATTERNS: [Pattern; 4] = [
-    [
-        [___, ___, ___, ___],
-        [___, _X_, ___, ___],
-        [___, _X_, ___, ___],
-        [___, _X_, _X_, ___],
-    ],
-    [
-        [___, ___, ___, ___],
-        [___, ___, ___, ___],
-        [___, _X_, _X_, _X_],
-        [___, _X_, ___, ___],
-    ],
-    [
-        [___, ___, ___, ___],
-        [___, _X_, _X_, ___],
-        [___, ___, _X_, ___],
-        [___, ___, _X_, ___],
-    ],
-    [
-        [___, ___, ___, ___],
-        [___, ___, ___, ___],
-        [___, ___, ___, _X_],
-        [___, _X_, _X_, _X_],
-    ],
-];
+        let size = cells.iter().map(|&(i, j)| i.max(j) + 1).max().unwrap_or(0);
 
-pub const J_BLOCK_PATTERNS: [Pattern; 4] = [
-    [
-        [___, ___, ___, ___],
-        [___, ___, _X_, ___],
-        [___, ___, _X_, ___],
-        [___, _X_, _X_, ___],
-    ],
-    [
-        [___, ___, ___, ___],
-        [___, ___, ___, ___],
-        [_X_, _X_, _X_, ___],
-        [___, ___, _X_, ___],
-    ],
-    [
-        [___, ___, ___, ___],
-        [___, _X_, _X_, ___],
-        [___, _X_, ___, ___],
-        [___, _X_, ___, ___],
-    ],
-    [
-        [___, ___, ___, ___],
-        [___, ___, ___, ___],
-        [_X_, ___, ___, ___],
-        [_X_, _X_, _X_, ___],
-    ],
-];
+        Ok(Pattern {
+            size,
+            cells: sorted,
+        })
+    }
 
-pub const T_BLOCK_PATTERNS: [Pattern; 4] = [
-    [
-        [___, ___, ___, ___],
-        [___, ___, ___, ___],
-        [___, ___, _X_, ___],
-        [___, _X_, _X_, _X_],
-    ],
-    [
-        [___, ___, ___, ___],
-        [___, ___, _X_, ___],
-        [___, _X_, _X_, ___],
-        [___, ___, _X_, ___],
-    ],
-    [
-        [___, ___, ___, ___],
-        [___, _X_, _X_, _X_],
-        [___, ___, _X_, ___],
-        [___, ___, ___, ___],
-    ],
-    [
-        [___, ___, ___, ___],
-        [___, _X_, ___, ___],
-        [___, _X_, _X_, ___],
-        [___, _X_, ___, ___],
-    ],
-];
+    pub fn size(&self) -> usize {
+        self.size
+    }
 
-pub const S_BLOCK_PATTERNS: [Pattern; 2] = [
-    [
-        [___, ___, ___, ___],
-        [___, ___, ___, ___],
-        [___, _X_, _X_, ___],
-        [_X_, _X_, ___, ___],
-    ],
-    [
-        [___, ___, ___, ___],
-        [___, _X_, ___, ___],
-        [___, _X_, _X_, ___],
-        [___, ___, _X_, ___],
-    ],
-];
+    pub fn cells(&self) -> &[(usize, usize)] {
+        &self.cells
+    }
 
-pub const Z_BLOCK_PATTERNS: [Pattern; 2] = [
-    [
-        [___, ___, ___, ___],
-        [___, ___, ___, ___],
-        [_X_, _X_, ___, ___],
-        [___, _X_, _X_, ___],
-    ],
-    [
-        [___, ___, ___, ___],
-        [___, ___, _X_, ___],
-        [___, _X_, _X_, ___],
-        [___, _X_, ___, ___],
-    ],
-];
+    pub fn contains(&self, i: isize, j: isize) -> bool {
+        i >= 0 && j >= 0 && self.cells.contains(&(i as usize, j as usize))
+    }
+
+    /// The pattern turned a quarter clockwise inside its box.
+    pub fn rotated(&self) -> Pattern {
+        let mut cells: Vec<(usize, usize)> = self
+            .cells
+            .iter()
+            .map(|&(i, j)| (j, self.size - 1 - i))
+            .collect();
+        cells.sort();
+
+        Pattern {
+            size: self.size,
+            cells,
+        }
+    }
+
+    /// Every distinct orientation reached by rotating clockwise, starting
+    /// with this one. Symmetric shapes yield one or two orientations.
+    pub fn rotations(&self) -> Vec<Pattern> {
+        let mut rotations = vec![self.clone()];
+        let mut next = self.rotated();
+
+        while next != *self && rotations.len() < 4 {
+            rotations.push(next.clone());
+            next = next.rotated();
+        }
+
+        rotations
+    }
+}
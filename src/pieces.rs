@@ -0,0 +1,521 @@
+use std::collections::HashSet;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::patterns::Pattern;
+
+const BUILTIN: &str = include_str!("../assets/pieces/tetrominoes.ron");
+
+static DEFAULT_SET: OnceLock<&'static PieceSet> = OnceLock::new();
+
+/// The set installed as default when the pieces folder is loaded.
+pub const DEFAULT_SET_NAME: &str = "Tetrominoes";
+
+/// Offsets tried in order when a rotation is blocked, indexed by the
+/// orientation being rotated from. An empty table means no kicks and a table
+/// with a single entry applies to every orientation.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct KickTable {
+    #[serde(default)]
+    pub clockwise: Vec<Vec<(isize, isize)>>,
+    #[serde(default)]
+    pub counter_clockwise: Vec<Vec<(isize, isize)>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Piece {
+    pub name: String,
+    pub colour: (f32, f32, f32),
+    pub spawn: (isize, isize),
+    /// Each orientation's cells, listed clockwise. Leave empty and give
+    /// `shape` instead to have them worked out by rotating it in its box.
+    #[serde(default)]
+    pub orientations: Vec<Vec<(usize, usize)>>,
+    #[serde(default)]
+    pub shape: Vec<(usize, usize)>,
+    #[serde(default)]
+    pub kicks: KickTable,
+    #[serde(default)]
+    pub t_spin: bool,
+    #[serde(skip)]
+    pub patterns: Vec<Pattern>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PieceSet {
+    pub name: String,
+    /// Other sets, by name, whose pieces are added to this one.
+    #[serde(default)]
+    pub include: Vec<String>,
+    #[serde(default)]
+    pub pieces: Vec<Piece>,
+}
+
+#[derive(Debug)]
+pub enum PieceError {
+    Io(PathBuf, io::Error),
+    Parse(ron::error::SpannedError),
+    Invalid(String, String),
+}
+
+impl fmt::Display for PieceError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PieceError::Io(path, e) => write!(f, "{}: {}", path.display(), e),
+            PieceError::Parse(e) => write!(f, "{}", e),
+            PieceError::Invalid(piece, reason) => write!(f, "piece \"{}\": {}", piece, reason),
+        }
+    }
+}
+
+impl std::error::Error for PieceError {}
+
+impl Piece {
+    pub fn kicks(&self, from: usize, clockwise: bool) -> &[(isize, isize)] {
+        let table = if clockwise {
+            &self.kicks.clockwise
+        } else {
+            &self.kicks.counter_clockwise
+        };
+
+        match table.as_slice() {
+            [all] => all,
+            table => table.get(from).map(Vec::as_slice).unwrap_or(&[]),
+        }
+    }
+
+    fn validate(&mut self) -> Result<(), PieceError> {
+        let invalid = |reason: String| PieceError::Invalid(self.name.clone(), reason);
+
+        let patterns = match (self.shape.is_empty(), self.orientations.is_empty()) {
+            (false, true) => Pattern::from_cells(&self.shape)
+                .map_err(invalid)?
+                .rotations(),
+            (true, false) => self
+                .orientations
+                .iter()
+                .map(|cells| Pattern::from_cells(cells))
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(invalid)?,
+            (false, false) => {
+                return Err(invalid(
+                    "give either a shape or orientations, not both".into(),
+                ))
+            }
+            (true, true) => return Err(invalid("no shape or orientations given".into())),
+        };
+
+        let (r, g, b) = self.colour;
+
+        if [r, g, b].iter().any(|c| !(0.0..=1.0).contains(c)) {
+            return Err(invalid("colour components must be between 0 and 1".into()));
+        }
+
+        for (name, table) in [
+            ("clockwise", &self.kicks.clockwise),
+            ("counter_clockwise", &self.kicks.counter_clockwise),
+        ] {
+            if table.len() > 1 && table.len() != patterns.len() {
+                return Err(invalid(format!(
+                    "{} kicks have {} entries, expected one or one per orientation ({})",
+                    name,
+                    table.len(),
+                    patterns.len()
+                )));
+            }
+        }
+
+        self.patterns = patterns;
+
+        Ok(())
+    }
+}
+
+impl PieceSet {
+    pub fn builtin() -> PieceSet {
+        PieceSet::parse(BUILTIN).expect("built-in piece set is valid")
+    }
+
+    /// Parses a set on its own. Sets that include others are resolved by
+    /// `PieceSets::load_dir`, so here they may have no pieces of their own.
+    pub fn parse(contents: &str) -> Result<PieceSet, PieceError> {
+        let mut set: PieceSet = ron::from_str(contents).map_err(PieceError::Parse)?;
+
+        for piece in set.pieces.iter_mut() {
+            piece.validate()?;
+        }
+
+        if set.include.is_empty() {
+            set.check()?;
+        }
+
+        Ok(set)
+    }
+
+    fn check(&self) -> Result<(), PieceError> {
+        if self.pieces.is_empty() {
+            return Err(PieceError::Invalid(
+                self.name.clone(),
+                "set has no pieces".into(),
+            ));
+        }
+
+        let mut names = HashSet::new();
+
+        for piece in &self.pieces {
+            if !names.insert(&piece.name) {
+                return Err(PieceError::Invalid(
+                    piece.name.clone(),
+                    format!("name is used by more than one piece in \"{}\"", self.name),
+                ));
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn load(path: &Path) -> Result<PieceSet, PieceError> {
+        let contents =
+            fs::read_to_string(path).map_err(|e| PieceError::Io(path.to_path_buf(), e))?;
+
+        PieceSet::parse(&contents)
+    }
+
+    pub fn get(&'static self, name: &str) -> Option<&'static Piece> {
+        self.pieces.iter().find(|piece| piece.name == name)
+    }
+
+    pub fn random(&'static self) -> &'static Piece {
+        self.random_with(&mut rand::thread_rng())
+    }
+
+    /// Draws a piece with the given generator, for reproducible sequences.
+    pub fn random_with<R: Rng>(&'static self, rng: &mut R) -> &'static Piece {
+        &self.pieces[rng.gen_range(0..self.pieces.len())]
+    }
+
+    /// Leaks the set so pieces can be shared by `Copy` tetrominoes for the
+    /// rest of the program. Sets are loaded once at startup.
+    pub fn leak(self) -> &'static PieceSet {
+        Box::leak(Box::new(self))
+    }
+}
+
+/// The set random pieces are drawn from, the built-in tetrominoes unless
+/// another set was installed with `set_default` first.
+pub fn default_set() -> &'static PieceSet {
+    DEFAULT_SET.get_or_init(|| PieceSet::builtin().leak())
+}
+
+/// Installs the default piece set. Returns false if a default was already in
+/// use, in which case it is left unchanged.
+pub fn set_default(set: &'static PieceSet) -> bool {
+    DEFAULT_SET.set(set).is_ok()
+}
+
+/// Every piece set that can be played, the default one first.
+#[cfg_attr(feature = "bevy", derive(bevy::prelude::Resource))]
+#[derive(Debug)]
+pub struct PieceSets {
+    pub sets: Vec<&'static PieceSet>,
+}
+
+impl Default for PieceSets {
+    fn default() -> Self {
+        PieceSets {
+            sets: vec![default_set()],
+        }
+    }
+}
+
+impl PieceSets {
+    /// Loads every `.ron` set in `dir`, resolving the sets they include, and
+    /// installs the one named `DEFAULT_SET_NAME` as the default. Returns the
+    /// sets that loaded and the errors of those that did not.
+    pub fn load_dir(dir: &Path) -> (PieceSets, Vec<PieceError>) {
+        let mut errors = vec![];
+
+        let entries = match fs::read_dir(dir) {
+            Ok(entries) => entries,
+            Err(e) => {
+                return (
+                    PieceSets::default(),
+                    vec![PieceError::Io(dir.to_path_buf(), e)],
+                )
+            }
+        };
+
+        let mut paths: Vec<PathBuf> = entries
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| path.extension().is_some_and(|ext| ext == "ron"))
+            .collect();
+        paths.sort();
+
+        let mut loaded = vec![];
+
+        for path in paths {
+            match PieceSet::load(&path) {
+                Ok(set) => loaded.push(set),
+                Err(e) => errors.push(e),
+            }
+        }
+
+        let mut resolved = vec![];
+
+        for set in &loaded {
+            match resolve(set, &loaded) {
+                Ok(set) => resolved.push(set),
+                Err(e) => errors.push(e),
+            }
+        }
+
+        let mut sets = vec![];
+
+        for set in resolved {
+            let set = set.leak();
+
+            if set.name == DEFAULT_SET_NAME && set_default(set) {
+                sets.insert(0, set);
+            } else {
+                sets.push(set);
+            }
+        }
+
+        if !sets.iter().any(|&set| std::ptr::eq(set, default_set())) {
+            sets.insert(0, default_set());
+        }
+
+        (PieceSets { sets }, errors)
+    }
+
+    pub fn get(&self, name: &str) -> Option<&'static PieceSet> {
+        self.sets.iter().copied().find(|set| set.name == name)
+    }
+}
+
+/// Adds the pieces of the sets `set` includes. Included sets are not
+/// themselves expanded, so a set can only pull in sets with pieces of their
+/// own.
+fn resolve(set: &PieceSet, loaded: &[PieceSet]) -> Result<PieceSet, PieceError> {
+    let mut resolved = set.clone();
+
+    for name in &set.include {
+        let Some(other) = loaded.iter().find(|other| other.name == *name) else {
+            return Err(PieceError::Invalid(
+                set.name.clone(),
+                format!("included set \"{}\" was not found", name),
+            ));
+        };
+
+        resolved.pieces.extend(other.pieces.iter().cloned());
+    }
+
+    resolved.include.clear();
+    resolved.check()?;
+
+    Ok(resolved)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A set named `name` holding pieces written as RON.
+    fn set(name: &str, pieces: &[&str]) -> String {
+        format!(
+            "PieceSet(name: \"{}\", pieces: [{}])",
+            name,
+            pieces.join(", ")
+        )
+    }
+
+    /// A piece named `name` with `fields` added to its colour and spawn.
+    fn piece(name: &str, fields: &str) -> String {
+        format!(
+            "(name: \"{}\", colour: (0.5, 0.5, 0.5), spawn: (0, 4), {})",
+            name, fields
+        )
+    }
+
+    fn domino(name: &str) -> String {
+        piece(name, "shape: [(0, 0), (0, 1)]")
+    }
+
+    /// The reason `contents` is rejected, checking it is for `expected`.
+    fn reason(contents: &str, expected: &str) -> String {
+        match PieceSet::parse(contents) {
+            Err(PieceError::Invalid(name, reason)) => {
+                assert_eq!(name, expected, "{}", reason);
+                reason
+            }
+            Err(e) => panic!("unexpected error {}", e),
+            Ok(_) => panic!("{} should not be valid", contents),
+        }
+    }
+
+    /// A fresh directory holding `files`, named `.ron` files with contents.
+    fn directory(test: &str, files: &[(&str, String)]) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("rtetris-pieces-{}-{}", test, std::process::id()));
+
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+
+        for (name, contents) in files {
+            fs::write(dir.join(format!("{}.ron", name)), contents).unwrap();
+        }
+
+        dir
+    }
+
+    #[test]
+    fn parses_the_bundled_sets() {
+        let tetrominoes = PieceSet::parse(BUILTIN).unwrap();
+        let pentominoes =
+            PieceSet::parse(include_str!("../assets/pieces/pentominoes.ron")).unwrap();
+
+        assert_eq!(tetrominoes.pieces.len(), 7);
+        assert_eq!(pentominoes.pieces.len(), 18);
+        assert!(pentominoes
+            .pieces
+            .iter()
+            .all(|piece| !piece.patterns.is_empty()));
+    }
+
+    #[test]
+    fn rejects_invalid_ron() {
+        assert!(matches!(
+            PieceSet::parse("PieceSet(name: "),
+            Err(PieceError::Parse(_))
+        ));
+        assert!(matches!(
+            PieceSet::parse(&set(
+                "Set",
+                &["(name: \"A\", colour: \"red\", spawn: (0, 4))"]
+            )),
+            Err(PieceError::Parse(_))
+        ));
+    }
+
+    #[test]
+    fn rejects_invalid_pieces() {
+        let cases = [
+            (piece("A", ""), "no shape or orientations"),
+            (
+                piece("A", "shape: [(0, 0)], orientations: [[(0, 0)]]"),
+                "not both",
+            ),
+            (piece("A", "orientations: [[(0, 0)], []]"), "has no cells"),
+            (
+                piece("A", "shape: [(0, 0), (0, 1), (0, 0)]"),
+                "same cell twice",
+            ),
+            (
+                "(name: \"A\", colour: (1.5, 0.0, 0.0), spawn: (0, 4), shape: [(0, 0)])".into(),
+                "between 0 and 1",
+            ),
+            (
+                piece(
+                    "A",
+                    "shape: [(0, 0), (0, 1)], kicks: (clockwise: [[(0, 1)], [(0, -1)]])",
+                ),
+                "clockwise kicks have 2 entries",
+            ),
+        ];
+
+        for (piece, expected) in cases {
+            let reason = reason(&set("Set", &[&piece]), "A");
+
+            assert!(reason.contains(expected), "{:?} for {}", reason, piece);
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_sets() {
+        assert!(reason(&set("Empty", &[]), "Empty").contains("no pieces"));
+        assert!(reason(&set("Twice", &[&domino("A"), &domino("A")]), "A").contains("\"Twice\""));
+    }
+
+    #[test]
+    fn resolves_included_sets() {
+        let dir = directory(
+            "include",
+            &[
+                ("a", set("Dominoes", &[&domino("D")])),
+                ("b", set("Monominoes", &[&piece("M", "shape: [(0, 0)]")])),
+                (
+                    "c",
+                    "PieceSet(name: \"Both\", include: [\"Dominoes\", \"Monominoes\"])".into(),
+                ),
+            ],
+        );
+
+        let (sets, errors) = PieceSets::load_dir(&dir);
+        let both = sets.get("Both").unwrap();
+
+        assert!(errors.is_empty(), "{:?}", errors);
+        assert!(std::ptr::eq(sets.sets[0], default_set()));
+        assert_eq!(sets.sets.len(), 4);
+        assert!(both.get("D").is_some() && both.get("M").is_some());
+        assert!(both.include.is_empty());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn reports_sets_that_do_not_load() {
+        let dir = directory(
+            "errors",
+            &[
+                ("a", set("Dominoes", &[&domino("D")])),
+                (
+                    "b",
+                    "PieceSet(name: \"Missing\", include: [\"Nope\"])".into(),
+                ),
+                (
+                    "c",
+                    format!(
+                        "PieceSet(name: \"Clash\", include: [\"Dominoes\"], pieces: [{}])",
+                        domino("D")
+                    ),
+                ),
+                ("d", "not a piece set".into()),
+                ("e", set("Empty", &[])),
+            ],
+        );
+        fs::write(dir.join("ignored.txt"), "not a set either").unwrap();
+
+        let (sets, errors) = PieceSets::load_dir(&dir);
+        let messages: Vec<String> = errors.iter().map(ToString::to_string).collect();
+
+        assert_eq!(errors.len(), 4, "{:?}", messages);
+        assert!(messages
+            .iter()
+            .any(|e| e.contains("included set \"Nope\" was not found")));
+        assert!(messages
+            .iter()
+            .any(|e| e.contains("used by more than one piece in \"Clash\"")));
+        assert!(errors.iter().any(|e| matches!(e, PieceError::Parse(_))));
+        assert!(messages.iter().any(|e| e.contains("set has no pieces")));
+
+        let names: Vec<&str> = sets.sets.iter().map(|set| set.name.as_str()).collect();
+        assert_eq!(names, [DEFAULT_SET_NAME, "Dominoes"]);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn keeps_the_default_set_when_the_folder_is_missing() {
+        let (sets, errors) = PieceSets::load_dir(Path::new("/no/such/rtetris/pieces"));
+
+        assert!(matches!(errors.as_slice(), [PieceError::Io(..)]));
+        assert_eq!(sets.sets.len(), 1);
+        assert!(std::ptr::eq(sets.sets[0], default_set()));
+    }
+}
@@ -0,0 +1,439 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::board::{Board, Movement};
+use crate::tetromino::Tetromino;
+
+/// Tried in this order, so of equally short paths the one that rotates and
+/// shifts before dropping is found first. Pieces lock as soon as they fail
+/// to fall, so moving them once they rest on the stack is best left to the
+/// tucks and spins that need it.
+const MOVEMENTS: [Movement; 5] = [
+    Movement::RotateRight,
+    Movement::RotateLeft,
+    Movement::Left,
+    Movement::Right,
+    Movement::Down,
+];
+
+/// A position the active piece can lock in and the inputs that get it
+/// there. After the path the piece rests on the stack; one more `Down`
+/// locks it.
+#[derive(Debug, Clone)]
+pub struct Placement {
+    pub tetromino: Tetromino,
+    pub path: Vec<Movement>,
+    /// Whether the piece locks as a T-spin, having rotated into place.
+    pub t_spin: bool,
+}
+
+#[derive(Clone, Copy)]
+struct Node {
+    tetromino: Tetromino,
+    rotated: bool,
+    parent: Option<(usize, Movement)>,
+}
+
+/// Every position the board's active piece can lock in, found by trying
+/// each movement from every position reached so far, so tucks under
+/// overhangs and spins through kicks are included. Positions covering the
+/// same cells count once; when both reach them, the T-spin is kept. Paths
+/// are as short as possible and placements come in the order found.
+pub fn reachable(board: &Board) -> Vec<Placement> {
+    if board.tetromino.is_null() || board.overlaps() {
+        return vec![];
+    }
+
+    let start = Node {
+        tetromino: board.tetromino,
+        rotated: false,
+        parent: None,
+    };
+
+    let mut nodes = vec![start];
+    let mut seen = HashSet::from([key(&start)]);
+    let mut locks: HashMap<Vec<(isize, isize)>, usize> = HashMap::new();
+    let mut placements: Vec<Placement> = vec![];
+    let mut probe = board.clone();
+    let mut current = 0;
+
+    while current < nodes.len() {
+        let node = nodes[current];
+
+        for movement in MOVEMENTS {
+            probe.tetromino = node.tetromino;
+
+            if probe.apply_movement(movement) {
+                let next = Node {
+                    tetromino: probe.tetromino,
+                    rotated: matches!(movement, Movement::RotateLeft | Movement::RotateRight),
+                    parent: Some((current, movement)),
+                };
+
+                if seen.insert(key(&next)) {
+                    nodes.push(next);
+                }
+
+                continue;
+            }
+
+            if movement != Movement::Down {
+                continue;
+            }
+
+            let t_spin = node.rotated && probe.is_t_spin();
+            let mut cells = node.tetromino.cells();
+            cells.sort();
+
+            let placement = Placement {
+                tetromino: node.tetromino,
+                path: path_to(&nodes, current),
+                t_spin,
+            };
+
+            match locks.get(&cells) {
+                Some(&index) if t_spin && !placements[index].t_spin => {
+                    placements[index] = placement;
+                }
+                Some(_) => {}
+                None => {
+                    locks.insert(cells, placements.len());
+                    placements.push(placement);
+                }
+            }
+        }
+
+        current += 1;
+    }
+
+    placements
+}
+
+fn key(node: &Node) -> (isize, isize, usize, bool) {
+    (
+        node.tetromino.i,
+        node.tetromino.j,
+        node.tetromino.orientation(),
+        node.rotated,
+    )
+}
+
+fn path_to(nodes: &[Node], mut index: usize) -> Vec<Movement> {
+    let mut path = vec![];
+
+    while let Some((parent, movement)) = nodes[index].parent {
+        path.push(movement);
+        index = parent;
+    }
+
+    path.reverse();
+    path
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pieces;
+
+    const EMPTY: &str = "
+        ############
+        #__________#
+        #__________#
+        #__________#
+        #__________#
+        #__________#
+        #__________#
+        #__________#
+        #__________#
+        #__________#
+        #__________#
+        #__________#
+        #__________#
+        #__________#
+        #__________#
+        #__________#
+        #__________#
+        #__________#
+        #__________#
+        #__________#
+        #__________#
+        ############
+    ";
+
+    /// A slot under an overhang that a T can only spin into.
+    const SLOT: &str = "
+        ############
+        #__________#
+        #__________#
+        #__________#
+        #__________#
+        #XX________#
+        #X___XXXXXX#
+        #XX_XXXXXXX#
+        #XXXXXXXX_X#
+        ############
+    ";
+
+    /// A shelf the piece has to slide under once it is down.
+    const SHELF: &str = "
+        ############
+        #__________#
+        #__________#
+        #__________#
+        #__________#
+        #__________#
+        #__________#
+        #__________#
+        #__________#
+        #__________#
+        #__________#
+        #__________#
+        #__________#
+        #__________#
+        #__________#
+        #__________#
+        #XXXXXX____#
+        #__________#
+        #__________#
+        #__________#
+        #X_XXXXXXXX#
+        ############
+    ";
+
+    /// Pockets under overhangs on both sides, that pieces can only get into
+    /// by sliding or spinning in once they are down.
+    const OVERHANGS: &str = "
+        ############
+        #__________#
+        #__________#
+        #__________#
+        #__________#
+        #__________#
+        #__________#
+        #XXX____XXX#
+        #X______X__#
+        #X_X____X__#
+        #X_XX__XX__#
+        ############
+    ";
+
+    fn with_piece(text: &str, name: &str) -> Board {
+        let set = pieces::default_set();
+        let mut board = Board::parse(text, set).unwrap();
+        board.tetromino = Tetromino::new(set.get(name).unwrap(), 0, 0).at_spawn();
+        board
+    }
+
+    /// Counts the boards reached by placing `pieces` one after another.
+    fn perft(board: &Board, pieces: &[&str]) -> usize {
+        count(board, pieces, &|board| {
+            reachable(board)
+                .into_iter()
+                .map(|placement| placement.tetromino)
+                .collect()
+        })
+    }
+
+    /// `perft` with the placements of each piece found by `placements`.
+    fn count(
+        board: &Board,
+        pieces: &[&str],
+        placements: &dyn Fn(&Board) -> Vec<Tetromino>,
+    ) -> usize {
+        let Some((first, rest)) = pieces.split_first() else {
+            return 1;
+        };
+
+        let set = pieces::default_set();
+        let mut board = board.clone();
+        board.tetromino = Tetromino::new(set.get(first).unwrap(), 0, 0).at_spawn();
+
+        placements(&board)
+            .into_iter()
+            .map(|tetromino| {
+                let mut next = board.clone();
+                next.tetromino = tetromino;
+                next.merge();
+                next.check_completed_rows();
+                next.tetromino = Tetromino::null();
+
+                count(&next, rest, placements)
+            })
+            .sum()
+    }
+
+    /// The active piece turned at spawn, shifted one way or the other as far
+    /// as it goes and dropped straight down on the way: every placement
+    /// without tucks or spins.
+    fn hard_drops(board: &Board) -> Vec<Tetromino> {
+        if board.overlaps() {
+            return vec![];
+        }
+
+        let piece = board.tetromino.piece().unwrap();
+        let mut probe = board.clone();
+        let mut dropped = vec![];
+
+        for turns in 0..piece.patterns.len() {
+            probe.tetromino = board.tetromino;
+
+            if !(0..turns).all(|_| probe.apply_movement(Movement::RotateRight)) {
+                continue;
+            }
+
+            let turned = probe.tetromino;
+
+            for shift in [Movement::Left, Movement::Right] {
+                let mut shifted = turned;
+
+                loop {
+                    probe.tetromino = shifted;
+                    while probe.apply_movement(Movement::Down) {}
+                    dropped.push(probe.tetromino);
+
+                    probe.tetromino = shifted;
+
+                    if !probe.apply_movement(shift) {
+                        break;
+                    }
+
+                    shifted = probe.tetromino;
+                }
+            }
+        }
+
+        unique(dropped.into_iter())
+    }
+
+    /// Every position the active piece fits in and cannot fall from,
+    /// whether it can get there or not.
+    fn resting(board: &Board) -> Vec<Tetromino> {
+        if board.overlaps() {
+            return vec![];
+        }
+
+        let piece = board.tetromino.piece().unwrap();
+        let rows = board.matrix.len() as isize;
+        let cols = board.matrix[0].len() as isize;
+        let mut probe = board.clone();
+
+        let positions = (0..piece.patterns.len()).flat_map(|orientation| {
+            (-4..rows).flat_map(move |i| {
+                (-4..cols).map(move |j| Tetromino::with_orientation(piece, orientation, i, j))
+            })
+        });
+
+        unique(positions.filter(|&tetromino| {
+            probe.tetromino = tetromino;
+
+            !probe.overlaps() && !probe.apply_movement(Movement::Down)
+        }))
+    }
+
+    /// `tetrominoes`, keeping one of those covering the same cells.
+    fn unique(tetrominoes: impl Iterator<Item = Tetromino>) -> Vec<Tetromino> {
+        let mut seen = HashSet::new();
+
+        tetrominoes
+            .filter(|tetromino| {
+                let mut cells = tetromino.cells();
+                cells.sort();
+                seen.insert(cells)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn counts_placements_on_an_empty_board() {
+        let counts: Vec<usize> = ["I", "O", "T", "S", "Z", "J", "L"]
+            .iter()
+            .map(|name| reachable(&with_piece(EMPTY, name)).len())
+            .collect();
+
+        assert_eq!(counts, [17, 9, 34, 17, 17, 34, 34]);
+    }
+
+    #[test]
+    fn counts_sequences_on_an_empty_board() {
+        let board = Board::parse(EMPTY, pieces::default_set()).unwrap();
+
+        assert_eq!(perft(&board, &["O"]), 9);
+        assert_eq!(perft(&board, &["O", "O"]), 81);
+        assert_eq!(perft(&board, &["I", "O"]), 17 * 9);
+    }
+
+    #[test]
+    fn counts_sequences_under_overhangs() {
+        let board = Board::parse(OVERHANGS, pieces::default_set()).unwrap();
+
+        for (sequence, expected) in [
+            (["T", "I"], 551),
+            (["S", "Z"], 215),
+            (["J", "L"], 1010),
+            (["I", "T"], 635),
+        ] {
+            let count_with = |placements: &dyn Fn(&Board) -> Vec<Tetromino>| {
+                count(&board, &sequence, placements)
+            };
+
+            // Tucks and spins add to the straight drops, but never reach
+            // more than the places pieces can rest in.
+            assert_eq!(perft(&board, &sequence), expected, "{:?}", sequence);
+            assert!(count_with(&hard_drops) < expected, "{:?}", sequence);
+            assert!(count_with(&resting) > expected, "{:?}", sequence);
+        }
+    }
+
+    #[test]
+    fn paths_lead_to_their_placements() {
+        for (text, name) in [SLOT, SHELF]
+            .iter()
+            .flat_map(|text| ["I", "O", "T", "S", "Z", "J", "L"].map(|name| (text, name)))
+        {
+            let board = with_piece(text, name);
+
+            for placement in reachable(&board) {
+                let mut replay = board.clone();
+
+                for &movement in &placement.path {
+                    assert!(replay.apply_movement(movement));
+                }
+
+                assert_eq!(replay.tetromino.cells(), placement.tetromino.cells());
+                assert!(!replay.apply_movement(Movement::Down));
+            }
+        }
+    }
+
+    #[test]
+    fn finds_t_spins() {
+        let board = with_piece(SLOT, "T");
+        let placements = reachable(&board);
+
+        let spin = placements
+            .iter()
+            .find(|placement| {
+                let mut cells = placement.tetromino.cells();
+                cells.sort();
+                cells == [(5, 3), (6, 2), (6, 3), (6, 4)]
+            })
+            .expect("T slot not reached");
+
+        assert!(spin.t_spin);
+        assert!(matches!(
+            spin.path.last(),
+            Some(Movement::RotateLeft | Movement::RotateRight)
+        ));
+    }
+
+    #[test]
+    fn finds_tucks_under_overhangs() {
+        let board = with_piece(SHELF, "I");
+        let tucked = reachable(&board).iter().any(|placement| {
+            let mut cells = placement.tetromino.cells();
+            cells.sort();
+            cells == [(19, 1), (19, 2), (19, 3), (19, 4)]
+        });
+
+        assert!(tucked);
+    }
+}
@@ -0,0 +1,315 @@
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::board::Board;
+use crate::i18n;
+use crate::pieces::{self, Piece};
+use crate::settings::Language;
+use crate::tetromino::Tetromino;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Goal {
+    Lines(usize),
+    PerfectClear,
+    TSpinDouble,
+}
+
+impl Goal {
+    pub fn description(&self, language: Language) -> String {
+        match self {
+            Goal::Lines(1) => i18n::text(language, "goal.one_line").into(),
+            Goal::Lines(lines) => i18n::format(language, "goal.lines", &[lines]),
+            Goal::PerfectClear => i18n::text(language, "goal.perfect_clear").into(),
+            Goal::TSpinDouble => i18n::text(language, "goal.t_spin_double").into(),
+        }
+    }
+}
+
+/// A starting board, the exact pieces the player gets and what they have to
+/// achieve with them. The board is a diagram as `Board::parse` reads it,
+/// walls included, as wide as the playfield and at most as tall. It is
+/// placed at the bottom of the playfield. Pieces are named after the
+/// default piece set.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Puzzle {
+    pub name: String,
+    pub goal: Goal,
+    pub pieces: Vec<String>,
+    pub board: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PuzzlePack {
+    pub name: String,
+    pub puzzles: Vec<Puzzle>,
+}
+
+#[cfg_attr(feature = "bevy", derive(bevy::prelude::Resource))]
+#[derive(Debug, Default)]
+pub struct PuzzlePacks {
+    pub packs: Vec<PuzzlePack>,
+}
+
+#[derive(Debug)]
+pub enum PuzzleError {
+    Io(PathBuf, io::Error),
+    Parse(PathBuf, ron::error::SpannedError),
+    Invalid(String, String),
+}
+
+impl fmt::Display for PuzzleError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PuzzleError::Io(path, e) => write!(f, "{}: {}", path.display(), e),
+            PuzzleError::Parse(path, e) => write!(f, "{}: {}", path.display(), e),
+            PuzzleError::Invalid(puzzle, reason) => write!(f, "puzzle \"{}\": {}", puzzle, reason),
+        }
+    }
+}
+
+impl std::error::Error for PuzzleError {}
+
+impl Puzzle {
+    /// Looks up the puzzle's pieces in the default piece set.
+    pub fn pieces(&self) -> Result<Vec<&'static Piece>, PuzzleError> {
+        let set = pieces::default_set();
+
+        self.pieces
+            .iter()
+            .map(|name| {
+                set.get(name).ok_or_else(|| {
+                    PuzzleError::Invalid(self.name.clone(), format!("unknown piece \"{}\"", name))
+                })
+            })
+            .collect()
+    }
+
+    /// Builds the starting board, checking that the diagram fits the
+    /// playfield and leaves room for the first piece to spawn.
+    pub fn to_board(&self, rows: usize, cols: usize) -> Result<Board, PuzzleError> {
+        let invalid = |reason: String| PuzzleError::Invalid(self.name.clone(), reason);
+
+        let pieces = self.pieces()?;
+
+        if pieces.is_empty() {
+            return Err(invalid("no pieces given".into()));
+        }
+
+        if self.goal == Goal::Lines(0) {
+            return Err(invalid("goal must clear at least one line".into()));
+        }
+
+        let diagram = Board::parse(&self.board, pieces::default_set())
+            .map_err(|e| invalid(format!("board: {}", e)))?;
+        let height = diagram.matrix.len();
+        let width = diagram.matrix[0].len();
+
+        if width != cols || height > rows {
+            return Err(invalid(format!(
+                "board is {}x{}, it must be {} wide and at most {} tall",
+                width, height, cols, rows
+            )));
+        }
+
+        if !diagram.tetromino.is_null() {
+            return Err(invalid(
+                "board has an active piece, the first piece comes from the list".into(),
+            ));
+        }
+
+        // The diagram's top wall is left out, its floor lands on the
+        // playfield's.
+        let mut board = Board::new(rows, cols);
+        board
+            .matrix
+            .splice(rows - height + 1.., diagram.matrix[1..].iter().cloned());
+
+        if !board.completed_rows().is_empty() {
+            return Err(invalid("board starts with completed rows".into()));
+        }
+
+        board.tetromino = Tetromino::new(pieces[0], 0, 0).at_spawn();
+
+        if board.overlaps() {
+            return Err(invalid("first piece has no room to spawn".into()));
+        }
+
+        Ok(board)
+    }
+}
+
+impl PuzzlePack {
+    pub fn load(path: &Path, rows: usize, cols: usize) -> Result<PuzzlePack, PuzzleError> {
+        let contents =
+            fs::read_to_string(path).map_err(|e| PuzzleError::Io(path.to_path_buf(), e))?;
+        let pack: PuzzlePack =
+            ron::from_str(&contents).map_err(|e| PuzzleError::Parse(path.to_path_buf(), e))?;
+
+        for puzzle in &pack.puzzles {
+            puzzle.to_board(rows, cols)?;
+        }
+
+        Ok(pack)
+    }
+}
+
+impl PuzzlePacks {
+    /// Loads every `.ron` pack in `dir`, returning the packs that loaded and
+    /// the errors of those that did not.
+    pub fn load_dir(dir: &Path, rows: usize, cols: usize) -> (PuzzlePacks, Vec<PuzzleError>) {
+        let mut packs = PuzzlePacks::default();
+        let mut errors = vec![];
+
+        let entries = match fs::read_dir(dir) {
+            Ok(entries) => entries,
+            Err(e) => return (packs, vec![PuzzleError::Io(dir.to_path_buf(), e)]),
+        };
+
+        let mut paths: Vec<PathBuf> = entries
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| path.extension().is_some_and(|ext| ext == "ron"))
+            .collect();
+        paths.sort();
+
+        for path in paths {
+            match PuzzlePack::load(&path, rows, cols) {
+                Ok(pack) => packs.packs.push(pack),
+                Err(e) => errors.push(e),
+            }
+        }
+
+        (packs, errors)
+    }
+
+    pub fn puzzles(&self) -> Vec<(&PuzzlePack, &Puzzle)> {
+        self.packs
+            .iter()
+            .flat_map(|pack| pack.puzzles.iter().map(move |puzzle| (pack, puzzle)))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn puzzle(goal: Goal, pieces: &[&str], board: &str) -> Puzzle {
+        Puzzle {
+            name: "test".into(),
+            goal,
+            pieces: pieces.iter().map(|&name| name.into()).collect(),
+            board: board.into(),
+        }
+    }
+
+    fn reason(puzzle: &Puzzle) -> String {
+        match puzzle.to_board(20, 15) {
+            Err(PuzzleError::Invalid(name, reason)) => {
+                assert_eq!(name, puzzle.name);
+                reason
+            }
+            Err(e) => panic!("unexpected error {}", e),
+            Ok(_) => panic!("{} should not be valid", puzzle.board),
+        }
+    }
+
+    const FLOOR: &str = "
+        ###############
+        #XXXXXXXXXXXX_#
+        ###############
+    ";
+
+    #[test]
+    fn every_bundled_puzzle_is_valid() {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("assets/puzzles");
+        let (packs, errors) = PuzzlePacks::load_dir(&dir, 20, 15);
+
+        assert!(errors.is_empty(), "{:?}", errors);
+        assert!(packs.packs.iter().any(|pack| pack.name == "Básico"));
+
+        for (_, puzzle) in packs.puzzles() {
+            let board = puzzle.to_board(20, 15).unwrap();
+
+            assert_eq!(board.matrix.len(), 20);
+            assert_eq!(board.matrix[0].len(), 15);
+        }
+    }
+
+    #[test]
+    fn places_the_diagram_at_the_bottom() {
+        let board = puzzle(Goal::Lines(1), &["I"], FLOOR)
+            .to_board(20, 15)
+            .unwrap();
+
+        assert_eq!(
+            board.matrix[18][1..14]
+                .iter()
+                .filter(|c| c.is_filled())
+                .count(),
+            12
+        );
+        assert!(board.matrix[17][1..14].iter().all(|c| !c.is_filled()));
+        assert!(board.matrix[19].iter().all(|c| c.is_filled()));
+    }
+
+    #[test]
+    fn rejects_invalid_puzzles() {
+        let completed = "
+            ###############
+            #XXXXXXXXXXXXX#
+            ###############
+        ";
+        let stack = |rows: usize| {
+            format!(
+                "###############\n{}###############",
+                "#XXXXXXXXXXXX_#\n".repeat(rows)
+            )
+        };
+
+        let cases = [
+            (puzzle(Goal::Lines(1), &[], FLOOR), "no pieces"),
+            (puzzle(Goal::Lines(0), &["I"], FLOOR), "at least one line"),
+            (puzzle(Goal::Lines(1), &["Q"], FLOOR), "unknown piece \"Q\""),
+            (
+                puzzle(Goal::Lines(1), &["I"], "###\n#?#\n###"),
+                "board: line 2",
+            ),
+            (
+                puzzle(Goal::Lines(1), &["I"], "#####\n#X__#\n#####"),
+                "must be 15 wide",
+            ),
+            (
+                puzzle(Goal::Lines(1), &["I"], &stack(19)),
+                "at most 20 tall",
+            ),
+            (
+                puzzle(
+                    Goal::Lines(1),
+                    &["I"],
+                    "###############\n#@@@@_________#\n###############",
+                ),
+                "active piece",
+            ),
+            (puzzle(Goal::Lines(1), &["I"], completed), "completed rows"),
+            (
+                puzzle(Goal::Lines(1), &["I"], &stack(18)),
+                "no room to spawn",
+            ),
+        ];
+
+        for (puzzle, expected) in cases {
+            let reason = reason(&puzzle);
+
+            assert!(
+                reason.contains(expected),
+                "{:?} for {}",
+                reason,
+                puzzle.board
+            );
+        }
+    }
+}
@@ -0,0 +1,99 @@
+use crate::i18n;
+use crate::settings::Language;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ClearType {
+    Single,
+    Double,
+    Triple,
+    Tetris,
+    TSpinSingle,
+    TSpinDouble,
+    TSpinTriple,
+}
+
+impl ClearType {
+    pub const ALL: [ClearType; 7] = [
+        ClearType::Single,
+        ClearType::Double,
+        ClearType::Triple,
+        ClearType::Tetris,
+        ClearType::TSpinSingle,
+        ClearType::TSpinDouble,
+        ClearType::TSpinTriple,
+    ];
+
+    pub fn from_lines(lines: usize, t_spin: bool) -> Option<ClearType> {
+        match (lines, t_spin) {
+            (0, _) => None,
+            (1, false) => Some(ClearType::Single),
+            (2, false) => Some(ClearType::Double),
+            (3, false) => Some(ClearType::Triple),
+            (_, false) => Some(ClearType::Tetris),
+            (1, true) => Some(ClearType::TSpinSingle),
+            (2, true) => Some(ClearType::TSpinDouble),
+            (_, true) => Some(ClearType::TSpinTriple),
+        }
+    }
+
+    pub fn base_points(&self) -> usize {
+        match self {
+            ClearType::Single => 100,
+            ClearType::Double => 300,
+            ClearType::Triple => 500,
+            ClearType::Tetris => 800,
+            ClearType::TSpinSingle => 800,
+            ClearType::TSpinDouble => 1200,
+            ClearType::TSpinTriple => 1600,
+        }
+    }
+
+    /// Garbage rows the clear sends to the opponent in versus.
+    pub fn garbage(&self) -> usize {
+        match self {
+            ClearType::Single => 0,
+            ClearType::Double => 1,
+            ClearType::Triple => 2,
+            ClearType::Tetris => 4,
+            ClearType::TSpinSingle => 2,
+            ClearType::TSpinDouble => 4,
+            ClearType::TSpinTriple => 6,
+        }
+    }
+
+    pub fn name(&self, language: Language) -> &'static str {
+        let id = match self {
+            ClearType::Single => "clear.single",
+            ClearType::Double => "clear.double",
+            ClearType::Triple => "clear.triple",
+            ClearType::Tetris => "clear.tetris",
+            ClearType::TSpinSingle => "clear.t_spin_single",
+            ClearType::TSpinDouble => "clear.t_spin_double",
+            ClearType::TSpinTriple => "clear.t_spin_triple",
+        };
+
+        i18n::text(language, id)
+    }
+}
+
+/// How many clears of each type were made and how many points they awarded.
+#[derive(Debug, Clone, Default)]
+pub struct ClearStats {
+    counts: [usize; 7],
+    points: [usize; 7],
+}
+
+impl ClearStats {
+    pub fn record(&mut self, clear_type: ClearType, points: usize) {
+        self.counts[clear_type as usize] += 1;
+        self.points[clear_type as usize] += points;
+    }
+
+    pub fn count(&self, clear_type: ClearType) -> usize {
+        self.counts[clear_type as usize]
+    }
+
+    pub fn points(&self, clear_type: ClearType) -> usize {
+        self.points[clear_type as usize]
+    }
+}
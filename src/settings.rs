@@ -0,0 +1,163 @@
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+
+use crate::skins::DEFAULT_SKIN_NAME;
+use crate::sounds::DEFAULT_PACK_NAME;
+
+const FILE_NAME: &str = "settings.ron";
+/// Longest delay before a held move repeats, in milliseconds.
+pub const MAX_DAS: u64 = 500;
+/// Longest time between repeated moves, in milliseconds.
+pub const MAX_ARR: u64 = 200;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum Language {
+    English,
+    #[default]
+    Portuguese,
+}
+
+impl Language {
+    pub const ALL: [Language; 2] = [Language::English, Language::Portuguese];
+
+    /// The language's name, in that language.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Language::English => "English",
+            Language::Portuguese => "Português",
+        }
+    }
+}
+
+/// Loudness of everything, of the music and of the sound effects, from 0
+/// to 1. Music and effects are relative to the master volume.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Volume {
+    pub master: f32,
+    pub music: f32,
+    pub effects: f32,
+}
+
+impl Default for Volume {
+    fn default() -> Self {
+        Volume {
+            master: 1.0,
+            music: 0.7,
+            effects: 1.0,
+        }
+    }
+}
+
+/// The player's preferences, kept in the config directory. Missing or
+/// invalid values fall back to their defaults when loaded.
+#[cfg_attr(feature = "bevy", derive(bevy::prelude::Resource))]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    /// Milliseconds a move is held before it starts repeating.
+    pub das: u64,
+    /// Milliseconds between repeated moves.
+    pub arr: u64,
+    pub volume: Volume,
+    /// The name of the sound pack the effects are played from.
+    pub sound_pack: String,
+    /// Shows where the active piece would land.
+    pub ghost: bool,
+    /// Shows the board's cells.
+    pub grid: bool,
+    pub language: Language,
+    pub skin: String,
+    /// Scales the game by whole numbers only, so pixel art stays crisp, at
+    /// the cost of a border when the window is not a multiple of its size.
+    pub integer_scaling: bool,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            das: 170,
+            arr: 50,
+            volume: Volume::default(),
+            sound_pack: DEFAULT_PACK_NAME.into(),
+            ghost: true,
+            grid: false,
+            language: Language::default(),
+            skin: DEFAULT_SKIN_NAME.into(),
+            integer_scaling: false,
+        }
+    }
+}
+
+impl Settings {
+    pub fn path() -> Option<PathBuf> {
+        dirs::config_dir().map(|dir| dir.join("rtetris").join(FILE_NAME))
+    }
+
+    pub fn load() -> io::Result<Settings> {
+        let Some(path) = Settings::path() else {
+            return Ok(Settings::default());
+        };
+
+        if !path.exists() {
+            return Ok(Settings::default());
+        }
+
+        let contents = fs::read_to_string(path)?;
+        let settings: Settings =
+            ron::from_str(&contents).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+
+        Ok(settings.validated())
+    }
+
+    pub fn save(&self) -> io::Result<()> {
+        let Some(path) = Settings::path() else {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                "no config directory available",
+            ));
+        };
+
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        let contents = ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+
+        fs::write(path, contents)
+    }
+
+    /// Brings every value back into its range.
+    pub fn validated(mut self) -> Settings {
+        let defaults = Settings::default();
+
+        self.das = self.das.min(MAX_DAS);
+        self.arr = self.arr.min(MAX_ARR);
+
+        for (volume, default) in [
+            (&mut self.volume.master, defaults.volume.master),
+            (&mut self.volume.music, defaults.volume.music),
+            (&mut self.volume.effects, defaults.volume.effects),
+        ] {
+            *volume = if volume.is_finite() {
+                volume.clamp(0.0, 1.0)
+            } else {
+                default
+            };
+        }
+
+        if self.sound_pack.trim().is_empty() {
+            self.sound_pack = defaults.sound_pack;
+        }
+
+        if self.skin.trim().is_empty() {
+            self.skin = defaults.skin;
+        }
+
+        self
+    }
+}
@@ -0,0 +1,245 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+const BUILTIN: &str = include_str!("../assets/skins/classic.ron");
+
+/// The skin used when the settings name one that was not found.
+pub const DEFAULT_SKIN_NAME: &str = "classic";
+/// The key garbage blocks go by in a skin's colours and block textures.
+pub const GARBAGE: &str = "garbage";
+
+pub type Colour = (f32, f32, f32);
+
+/// How blocks are drawn. Blocks are looked up by piece name, or `GARBAGE`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Blocks {
+    /// One texture for every block, tinted with its colour.
+    Tinted(String),
+    /// A texture per piece, drawn as it is. Blocks without one use
+    /// `default`, tinted.
+    Textures {
+        default: String,
+        pieces: BTreeMap<String, String>,
+    },
+    /// One image cut into `columns` by `rows` tiles of `tile_size` pixels,
+    /// with the tile each piece uses counted from the top left. Blocks
+    /// without a tile use the first one.
+    Atlas {
+        texture: String,
+        tile_size: (f32, f32),
+        columns: usize,
+        rows: usize,
+        tiles: BTreeMap<String, usize>,
+        /// Tints the tiles with the blocks' colours.
+        #[serde(default)]
+        tint: bool,
+    },
+}
+
+/// The tiles drawn around the board and the panels.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Borders {
+    pub top: String,
+    pub bottom: String,
+    pub left: String,
+    pub right: String,
+    pub top_left: String,
+    pub top_right: String,
+    pub bottom_left: String,
+    pub bottom_right: String,
+}
+
+impl Default for Borders {
+    fn default() -> Self {
+        let sprite = |name: &str| format!("sprites/{}.png", name);
+
+        Borders {
+            top: sprite("border_top"),
+            bottom: sprite("border_bottom"),
+            left: sprite("border"),
+            right: sprite("border_right"),
+            top_left: sprite("border_topleft"),
+            top_right: sprite("border_topright"),
+            bottom_left: sprite("border_bottomleft"),
+            bottom_right: sprite("border_bottomright"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Background {
+    pub colour: Colour,
+    /// Stretched over the window, behind everything.
+    pub image: Option<String>,
+}
+
+impl Default for Background {
+    fn default() -> Self {
+        Background {
+            colour: (0.4, 0.4, 0.4),
+            image: None,
+        }
+    }
+}
+
+/// How the game looks. Files are paths under the assets folder.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Skin {
+    pub name: String,
+    pub blocks: Blocks,
+    /// Replaces the colours of the pieces named, and of garbage.
+    #[serde(default)]
+    pub colours: BTreeMap<String, Colour>,
+    #[serde(default)]
+    pub borders: Borders,
+    #[serde(default)]
+    pub background: Background,
+    /// The font of the panels beside the board and of the game over text.
+    #[serde(default = "default_font")]
+    pub font: String,
+}
+
+fn default_font() -> String {
+    "fonts/FiraSans-Bold.ttf".into()
+}
+
+#[derive(Debug)]
+pub enum SkinError {
+    Io(PathBuf, io::Error),
+    Parse(ron::error::SpannedError),
+    Invalid(String, String),
+}
+
+impl fmt::Display for SkinError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SkinError::Io(path, e) => write!(f, "{}: {}", path.display(), e),
+            SkinError::Parse(e) => write!(f, "{}", e),
+            SkinError::Invalid(skin, reason) => write!(f, "skin \"{}\": {}", skin, reason),
+        }
+    }
+}
+
+impl std::error::Error for SkinError {}
+
+impl Skin {
+    pub fn builtin() -> Skin {
+        Skin::parse(BUILTIN).expect("built-in skin is valid")
+    }
+
+    pub fn parse(contents: &str) -> Result<Skin, SkinError> {
+        let skin: Skin = ron::from_str(contents).map_err(SkinError::Parse)?;
+        let invalid = |reason: String| SkinError::Invalid(skin.name.clone(), reason);
+
+        let colours = skin.colours.values().chain([&skin.background.colour]);
+
+        for &(r, g, b) in colours {
+            if [r, g, b].iter().any(|c| !(0.0..=1.0).contains(c)) {
+                return Err(invalid("colour components must be between 0 and 1".into()));
+            }
+        }
+
+        if let Blocks::Atlas {
+            tile_size,
+            columns,
+            rows,
+            tiles,
+            ..
+        } = &skin.blocks
+        {
+            if *columns == 0 || *rows == 0 || tile_size.0 <= 0.0 || tile_size.1 <= 0.0 {
+                return Err(invalid("atlas has no tiles".into()));
+            }
+
+            if let Some((name, tile)) = tiles.iter().find(|(_, &tile)| tile >= columns * rows) {
+                return Err(invalid(format!(
+                    "tile {} of \"{}\" is outside the atlas",
+                    tile, name
+                )));
+            }
+        }
+
+        Ok(skin)
+    }
+
+    pub fn load(path: &Path) -> Result<Skin, SkinError> {
+        let contents =
+            fs::read_to_string(path).map_err(|e| SkinError::Io(path.to_path_buf(), e))?;
+
+        Skin::parse(&contents)
+    }
+
+    /// The colour of blocks of `name`, which would otherwise be `colour`.
+    pub fn colour(&self, name: &str, colour: Colour) -> Colour {
+        self.colours.get(name).copied().unwrap_or(colour)
+    }
+}
+
+/// Every skin that can be picked, the classic one first.
+#[cfg_attr(feature = "bevy", derive(bevy::prelude::Resource))]
+#[derive(Debug)]
+pub struct Skins {
+    pub skins: Vec<Skin>,
+}
+
+impl Default for Skins {
+    fn default() -> Self {
+        Skins {
+            skins: vec![Skin::builtin()],
+        }
+    }
+}
+
+impl Skins {
+    /// Loads every `.ron` skin in `dir`, returning the skins that loaded and
+    /// the errors of those that did not. The built-in classic skin is added
+    /// if none of them is called that.
+    pub fn load_dir(dir: &Path) -> (Skins, Vec<SkinError>) {
+        let mut errors = vec![];
+
+        let entries = match fs::read_dir(dir) {
+            Ok(entries) => entries,
+            Err(e) => return (Skins::default(), vec![SkinError::Io(dir.to_path_buf(), e)]),
+        };
+
+        let mut paths: Vec<PathBuf> = entries
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| path.extension().is_some_and(|ext| ext == "ron"))
+            .collect();
+        paths.sort();
+
+        let mut skins = vec![];
+
+        for path in paths {
+            match Skin::load(&path) {
+                Ok(skin) if skin.name == DEFAULT_SKIN_NAME => skins.insert(0, skin),
+                Ok(skin) => skins.push(skin),
+                Err(e) => errors.push(e),
+            }
+        }
+
+        if !skins.iter().any(|skin| skin.name == DEFAULT_SKIN_NAME) {
+            skins.insert(0, Skin::builtin());
+        }
+
+        (Skins { skins }, errors)
+    }
+
+    /// The skin called `name`, or the classic one if there is none.
+    pub fn get(&self, name: &str) -> &Skin {
+        self.skins
+            .iter()
+            .find(|skin| skin.name == name)
+            .unwrap_or(&self.skins[0])
+    }
+
+    pub fn names(&self) -> Vec<String> {
+        self.skins.iter().map(|skin| skin.name.clone()).collect()
+    }
+}
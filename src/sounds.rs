@@ -0,0 +1,386 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::mode::GameMode;
+
+/// The pack used when the settings name one that was not found.
+pub const DEFAULT_PACK_NAME: &str = "classic";
+
+/// Something in a game a sound pack can give a sound to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum Sound {
+    Move,
+    Rotate,
+    /// A piece locked without clearing lines.
+    Lock,
+    /// A piece locked clearing this many lines. Clears bigger than any the
+    /// pack has a sound for use the biggest one it has.
+    Clear(usize),
+    /// A piece locked with a T-spin, whether it cleared lines or not.
+    TSpin,
+    LevelUp,
+    GameOver,
+}
+
+/// The sounds played for one kind of event.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Cue {
+    /// Files under the sounds folder, played in turn each time the event
+    /// happens.
+    pub sounds: Vec<String>,
+    /// Times the sound is played, `interval` seconds apart.
+    #[serde(default = "once")]
+    pub times: usize,
+    #[serde(default)]
+    pub interval: f32,
+}
+
+fn once() -> usize {
+    1
+}
+
+/// What is going on, for choosing the music. During a game the most
+/// specific theme the pack has a track for plays: `Danger` when the stack is
+/// high, then the `Level` reached, then the mode's own theme, then `Game`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum Theme {
+    Menu,
+    Game,
+    Marathon,
+    Sprint,
+    Ultra,
+    Dig,
+    Puzzle,
+    Practice,
+    /// From this level up.
+    Level(usize),
+    Danger,
+    GameOver,
+}
+
+impl Theme {
+    pub fn of_mode(mode: GameMode) -> Theme {
+        match mode {
+            GameMode::Marathon { .. } | GameMode::Versus => Theme::Marathon,
+            GameMode::Sprint { .. } => Theme::Sprint,
+            GameMode::Ultra { .. } => Theme::Ultra,
+            GameMode::Dig { .. } => Theme::Dig,
+            GameMode::Puzzle => Theme::Puzzle,
+            GameMode::Practice => Theme::Practice,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Track {
+    /// A file under the sounds folder.
+    File(String),
+    Tune(Tune),
+}
+
+/// A melody played on a simple synthesiser, so packs can have music without
+/// recordings. Notes are written like `E5`, `F#4` or `Bb3`, with `-` for a
+/// rest, and last a beat unless followed by `:` and a number of beats, as
+/// in `C5:0.5`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Tune {
+    /// Beats per minute.
+    pub tempo: f32,
+    pub notes: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Note {
+    /// In hertz, or `None` for a rest.
+    pub frequency: Option<f32>,
+    pub seconds: f32,
+}
+
+impl Tune {
+    pub fn notes(&self) -> Result<Vec<Note>, String> {
+        if !self.tempo.is_finite() || self.tempo <= 0.0 {
+            return Err("tempo must be above 0".into());
+        }
+
+        let beat = 60.0 / self.tempo;
+
+        self.notes
+            .split_whitespace()
+            .map(|token| {
+                let (pitch, beats) = match token.split_once(':') {
+                    Some((pitch, beats)) => match beats.parse::<f32>() {
+                        Ok(beats) if beats.is_finite() && beats > 0.0 => (pitch, beats),
+                        _ => return Err(format!("\"{}\" has an invalid length", token)),
+                    },
+                    None => (token, 1.0),
+                };
+
+                let frequency = if pitch == "-" {
+                    None
+                } else {
+                    let frequency =
+                        frequency(pitch).ok_or_else(|| format!("\"{}\" is not a note", token))?;
+                    Some(frequency)
+                };
+
+                Ok(Note {
+                    frequency,
+                    seconds: beats * beat,
+                })
+            })
+            .collect()
+    }
+}
+
+/// The frequency of a note like `A4` or `C#5`, in equal temperament with A4
+/// at 440 Hz.
+fn frequency(pitch: &str) -> Option<f32> {
+    let mut chars = pitch.chars();
+
+    let mut semitone: i32 = match chars.next()? {
+        'C' => 0,
+        'D' => 2,
+        'E' => 4,
+        'F' => 5,
+        'G' => 7,
+        'A' => 9,
+        'B' => 11,
+        _ => return None,
+    };
+
+    let rest = chars.as_str();
+    let octave = if let Some(octave) = rest.strip_prefix('#') {
+        semitone += 1;
+        octave
+    } else if let Some(octave) = rest.strip_prefix('b') {
+        semitone -= 1;
+        octave
+    } else {
+        rest
+    };
+
+    let octave: i32 = octave
+        .parse()
+        .ok()
+        .filter(|octave| (0..=8).contains(octave))?;
+    let midi = 12 * (octave + 1) + semitone;
+
+    Some(440.0 * 2f32.powf((midi - 69) as f32 / 12.0))
+}
+
+/// The background music, and how it reacts to the game.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Music {
+    /// Seconds a track takes to fade into the next.
+    pub crossfade: f32,
+    /// Added to the playback speed for each level above the first.
+    pub speed_per_level: f32,
+    pub max_speed: f32,
+    /// The stack is dangerously high when it comes within this many rows of
+    /// the top.
+    pub danger_rows: usize,
+    /// Multiplies the speed when the stack is dangerously high and there is
+    /// no `Danger` track to switch to.
+    pub danger_speed: f32,
+    pub tracks: BTreeMap<Theme, Track>,
+}
+
+impl Default for Music {
+    fn default() -> Self {
+        Music {
+            crossfade: 1.0,
+            speed_per_level: 0.0,
+            max_speed: 1.0,
+            danger_rows: 4,
+            danger_speed: 1.0,
+            tracks: BTreeMap::new(),
+        }
+    }
+}
+
+impl Music {
+    /// The theme to play during a game, if the pack has music for it.
+    pub fn game_theme(&self, mode: GameMode, level: usize, danger: bool) -> Option<Theme> {
+        let level = self
+            .tracks
+            .keys()
+            .filter(|theme| matches!(theme, Theme::Level(from) if *from <= level))
+            .max()
+            .copied();
+
+        let candidates = [
+            danger.then_some(Theme::Danger),
+            level,
+            Some(Theme::of_mode(mode)),
+            Some(Theme::Game),
+        ];
+
+        candidates
+            .into_iter()
+            .flatten()
+            .find(|theme| self.tracks.contains_key(theme))
+    }
+
+    /// How fast game music plays at `level`.
+    pub fn speed(&self, level: usize, danger: bool) -> f32 {
+        let speed = (1.0 + self.speed_per_level * level.saturating_sub(1) as f32)
+            .min(self.max_speed.max(1.0));
+
+        if danger && !self.tracks.contains_key(&Theme::Danger) {
+            speed * self.danger_speed
+        } else {
+            speed
+        }
+    }
+}
+
+/// Sound effects for the events of a game, and the music. Events without a
+/// cue are silent.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SoundPack {
+    pub name: String,
+    #[serde(default)]
+    pub cues: BTreeMap<Sound, Cue>,
+    #[serde(default)]
+    pub music: Music,
+}
+
+#[cfg_attr(feature = "bevy", derive(bevy::prelude::Resource))]
+#[derive(Debug, Default)]
+pub struct SoundPacks {
+    pub packs: Vec<SoundPack>,
+}
+
+#[derive(Debug)]
+pub enum SoundError {
+    Io(PathBuf, io::Error),
+    Parse(PathBuf, ron::error::SpannedError),
+    Invalid(String, String),
+}
+
+impl fmt::Display for SoundError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SoundError::Io(path, e) => write!(f, "{}: {}", path.display(), e),
+            SoundError::Parse(path, e) => write!(f, "{}: {}", path.display(), e),
+            SoundError::Invalid(pack, reason) => write!(f, "sound pack \"{}\": {}", pack, reason),
+        }
+    }
+}
+
+impl std::error::Error for SoundError {}
+
+impl SoundPack {
+    pub fn load(path: &Path) -> Result<SoundPack, SoundError> {
+        let contents =
+            fs::read_to_string(path).map_err(|e| SoundError::Io(path.to_path_buf(), e))?;
+        let pack: SoundPack =
+            ron::from_str(&contents).map_err(|e| SoundError::Parse(path.to_path_buf(), e))?;
+
+        for (sound, cue) in &pack.cues {
+            if cue.sounds.is_empty() {
+                return Err(SoundError::Invalid(
+                    pack.name.clone(),
+                    format!("{:?} has no sounds", sound),
+                ));
+            }
+
+            if !cue.interval.is_finite() || cue.interval < 0.0 {
+                return Err(SoundError::Invalid(
+                    pack.name.clone(),
+                    format!("{:?} has an invalid interval", sound),
+                ));
+            }
+        }
+
+        for (theme, track) in &pack.music.tracks {
+            if let Track::Tune(tune) = track {
+                tune.notes().map_err(|reason| {
+                    SoundError::Invalid(pack.name.clone(), format!("{:?}: {}", theme, reason))
+                })?;
+            }
+        }
+
+        let music = &pack.music;
+
+        if !music.crossfade.is_finite() || music.crossfade < 0.0 {
+            return Err(SoundError::Invalid(
+                pack.name.clone(),
+                "the crossfade is invalid".into(),
+            ));
+        }
+
+        for speed in [music.speed_per_level, music.max_speed, music.danger_speed] {
+            if !speed.is_finite() || speed < 0.0 {
+                return Err(SoundError::Invalid(
+                    pack.name.clone(),
+                    "music speeds must not be negative".into(),
+                ));
+            }
+        }
+
+        Ok(pack)
+    }
+
+    /// The cue for `sound`, looking smaller clears up for clears the pack
+    /// has no sound for.
+    pub fn cue(&self, sound: Sound) -> Option<&Cue> {
+        match sound {
+            Sound::Clear(lines) => (1..=lines)
+                .rev()
+                .find_map(|lines| self.cues.get(&Sound::Clear(lines))),
+            _ => self.cues.get(&sound),
+        }
+    }
+}
+
+impl SoundPacks {
+    /// Loads every `.ron` pack in `dir`, returning the packs that loaded and
+    /// the errors of those that did not.
+    pub fn load_dir(dir: &Path) -> (SoundPacks, Vec<SoundError>) {
+        let mut packs = SoundPacks::default();
+        let mut errors = vec![];
+
+        let entries = match fs::read_dir(dir) {
+            Ok(entries) => entries,
+            Err(e) => return (packs, vec![SoundError::Io(dir.to_path_buf(), e)]),
+        };
+
+        let mut paths: Vec<PathBuf> = entries
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| path.extension().is_some_and(|ext| ext == "ron"))
+            .collect();
+        paths.sort();
+
+        for path in paths {
+            match SoundPack::load(&path) {
+                Ok(pack) => packs.packs.push(pack),
+                Err(e) => errors.push(e),
+            }
+        }
+
+        (packs, errors)
+    }
+
+    /// The pack called `name`, or the default one if there is none.
+    pub fn get(&self, name: &str) -> Option<&SoundPack> {
+        self.packs
+            .iter()
+            .find(|pack| pack.name == name)
+            .or_else(|| {
+                self.packs
+                    .iter()
+                    .find(|pack| pack.name == DEFAULT_PACK_NAME)
+            })
+    }
+
+    pub fn names(&self) -> Vec<String> {
+        self.packs.iter().map(|pack| pack.name.clone()).collect()
+    }
+}
@@ -0,0 +1,470 @@
+use std::io::{self, BufRead, BufReader, Write};
+use std::process::{Child, ChildStdin, Command, Stdio};
+use std::sync::mpsc::{self, Receiver};
+use std::sync::Mutex;
+use std::thread;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use crate::board::{Board, Cell, Movement};
+use crate::bot::{Controller, Pilot};
+use crate::engine::GameState;
+use crate::mode::GameMode;
+use crate::pieces::{self, PieceSet};
+use crate::placements;
+use crate::tetromino::Tetromino;
+
+/// Rows in the protocol's boards, listed bottom first. Rows the game's board
+/// does not have are sent empty.
+pub const BOARD_ROWS: usize = 40;
+
+/// Columns in the protocol's boards. Games against a bot are played on a
+/// board this wide, so columns are the same on both sides.
+pub const BOARD_COLUMNS: usize = 10;
+
+/// Messages the game sends to the bot, one JSON object per line.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum FrontendMessage {
+    Rules {},
+    Start {
+        hold: Option<String>,
+        queue: Vec<String>,
+        combo: u32,
+        back_to_back: bool,
+        board: Vec<Vec<Option<String>>>,
+    },
+    Stop,
+    Suggest,
+    Play {
+        #[serde(rename = "move")]
+        mv: Move,
+    },
+    NewPiece {
+        piece: String,
+    },
+    Quit,
+}
+
+/// Messages the bot sends back.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum BotMessage {
+    Error {
+        reason: String,
+    },
+    Ready,
+    Info {
+        name: String,
+        version: String,
+        author: String,
+        features: Vec<String>,
+    },
+    Suggestion {
+        moves: Vec<Move>,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Move {
+    pub location: PieceLocation,
+    pub spin: Spin,
+}
+
+/// Where a piece ends up, by the cell it rotates around in SRS, counting
+/// columns from the left and rows from the bottom, both from 0.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PieceLocation {
+    #[serde(rename = "type")]
+    pub piece: String,
+    pub orientation: Orientation,
+    pub x: isize,
+    pub y: isize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Orientation {
+    North,
+    East,
+    South,
+    West,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Spin {
+    None,
+    Mini,
+    Full,
+}
+
+const ORIENTATIONS: [Orientation; 4] = [
+    Orientation::North,
+    Orientation::East,
+    Orientation::South,
+    Orientation::West,
+];
+
+/// The cells of a tetromino pointing north, around its SRS centre, as
+/// `(x, y)` with y pointing up.
+fn shape(piece: &str) -> Option<[(isize, isize); 4]> {
+    match piece {
+        "I" => Some([(-1, 0), (0, 0), (1, 0), (2, 0)]),
+        "O" => Some([(0, 0), (1, 0), (0, 1), (1, 1)]),
+        "T" => Some([(-1, 0), (0, 0), (1, 0), (0, 1)]),
+        "L" => Some([(-1, 0), (0, 0), (1, 0), (1, 1)]),
+        "J" => Some([(-1, 0), (0, 0), (1, 0), (-1, 1)]),
+        "S" => Some([(-1, 0), (0, 0), (0, 1), (1, 1)]),
+        "Z" => Some([(-1, 1), (0, 1), (0, 0), (1, 0)]),
+        _ => None,
+    }
+}
+
+impl PieceLocation {
+    /// The board cells the piece covers, on a board with `rows` rows
+    /// including walls. Only the seven tetrominoes have a location.
+    pub fn cells(&self, rows: usize) -> Option<Vec<(isize, isize)>> {
+        let cells = shape(&self.piece)?.map(|(x, y)| {
+            let (x, y) = match self.orientation {
+                Orientation::North => (x, y),
+                Orientation::East => (y, -x),
+                Orientation::South => (-x, -y),
+                Orientation::West => (-y, x),
+            };
+
+            (rows as isize - 2 - (self.y + y), self.x + x + 1)
+        });
+
+        Some(cells.to_vec())
+    }
+
+    /// Where a placed tetromino is, on a board with `rows` rows including
+    /// walls.
+    pub fn of(tetromino: &Tetromino, rows: usize) -> Option<PieceLocation> {
+        let piece = &tetromino.piece()?.name;
+        let mut cells = tetromino.cells();
+        cells.sort();
+
+        let (i, j) = *cells.first()?;
+
+        ORIENTATIONS.into_iter().find_map(|orientation| {
+            let mut location = PieceLocation {
+                piece: piece.clone(),
+                orientation,
+                x: 0,
+                y: 0,
+            };
+
+            // Move the shape so its first cell lands on the piece's.
+            let (si, sj) = location.cells(rows)?.into_iter().min()?;
+            location.x = j - sj;
+            location.y = si - i;
+
+            let mut placed = location.cells(rows)?;
+            placed.sort();
+
+            (placed == cells).then_some(location)
+        })
+    }
+}
+
+/// An endless marathon on a board `BOARD_COLUMNS` wide, for a bot to play.
+pub fn marathon() -> GameState {
+    let mode = GameMode::Marathon { endless: true };
+    let mut state = GameState::new(mode, 1, pieces::default_set());
+    let tetromino = state.board.tetromino;
+
+    state.board = Board::new(state.board.matrix.len(), BOARD_COLUMNS + 2);
+    state.board.tetromino = tetromino;
+
+    state
+}
+
+/// The board's cells inside the walls, bottom row first and padded to
+/// `BOARD_ROWS`. Locked pieces are named, garbage is `G`. Fails unless the
+/// board is `BOARD_COLUMNS` wide.
+pub fn board_rows(board: &Board) -> Result<Vec<Vec<Option<String>>>, String> {
+    let rows = board.matrix.len();
+    let cols = board.matrix[0].len();
+
+    if cols != BOARD_COLUMNS + 2 {
+        return Err(format!(
+            "bots play on boards {} columns wide, not {}",
+            BOARD_COLUMNS,
+            cols.saturating_sub(2)
+        ));
+    }
+
+    let mut result: Vec<Vec<Option<String>>> = board.matrix[1..rows - 1]
+        .iter()
+        .rev()
+        .map(|row| {
+            row[1..cols - 1]
+                .iter()
+                .map(|cell| match cell {
+                    Cell::Empty => None,
+                    Cell::Piece(piece) => Some(piece.name.clone()),
+                    Cell::Wall | Cell::Garbage => Some("G".into()),
+                })
+                .collect()
+        })
+        .collect();
+
+    while result.len() < BOARD_ROWS {
+        result.push(vec![None; BOARD_COLUMNS]);
+    }
+
+    Ok(result)
+}
+
+/// Builds a walled board from protocol rows, looking pieces up in `set`.
+/// Cells of unknown pieces become garbage.
+pub fn board_from_rows(rows: &[Vec<Option<String>>], set: &'static PieceSet) -> Board {
+    let cols = rows.first().map_or(0, Vec::len);
+    let mut board = Board::new(rows.len() + 2, cols + 2);
+    let bottom = rows.len();
+
+    for (y, row) in rows.iter().enumerate() {
+        for (x, cell) in row.iter().enumerate() {
+            board.matrix[bottom - y][x + 1] = match cell {
+                None => Cell::Empty,
+                Some(name) => set.get(name).map_or(Cell::Garbage, Cell::Piece),
+            };
+        }
+    }
+
+    board
+}
+
+/// A bot running as a child process, talking TBP over its stdin and stdout.
+/// Its messages are read on a separate thread, so checking for them never
+/// blocks the game.
+pub struct TbpBot {
+    child: Child,
+    stdin: ChildStdin,
+    messages: Mutex<Receiver<BotMessage>>,
+}
+
+impl TbpBot {
+    pub fn spawn(program: &str, args: &[String]) -> io::Result<TbpBot> {
+        let mut child = Command::new(program)
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()?;
+
+        let stdin = child.stdin.take().ok_or(io::ErrorKind::BrokenPipe)?;
+        let stdout = child.stdout.take().ok_or(io::ErrorKind::BrokenPipe)?;
+        let (sender, receiver) = mpsc::channel();
+
+        thread::spawn(move || {
+            for line in BufReader::new(stdout).lines() {
+                let Ok(line) = line else {
+                    break;
+                };
+
+                // Messages from newer versions of the protocol are skipped.
+                if let Ok(message) = serde_json::from_str(&line) {
+                    if sender.send(message).is_err() {
+                        break;
+                    }
+                }
+            }
+        });
+
+        Ok(TbpBot {
+            child,
+            stdin,
+            messages: Mutex::new(receiver),
+        })
+    }
+
+    pub fn send(&mut self, message: &FrontendMessage) -> io::Result<()> {
+        let line = serde_json::to_string(message)?;
+        writeln!(self.stdin, "{}", line)?;
+        self.stdin.flush()
+    }
+
+    /// The next message from the bot, if one has arrived.
+    pub fn try_recv(&self) -> Option<BotMessage> {
+        self.messages.lock().ok()?.try_recv().ok()
+    }
+
+    /// The next message from the bot, waiting up to `timeout` for one.
+    pub fn recv_timeout(&self, timeout: Duration) -> Option<BotMessage> {
+        self.messages.lock().ok()?.recv_timeout(timeout).ok()
+    }
+}
+
+impl Drop for TbpBot {
+    fn drop(&mut self) {
+        let _ = self.send(&FrontendMessage::Quit);
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+/// Plays a game with the moves an external bot suggests. The bot is told
+/// about each new piece as it shows up in the preview; whenever the board
+/// turns out different from what the bot expects, say because a piece could
+/// not reach its placement, the bot is stopped and started again from the
+/// current board. The engine has no hold, so held moves are never played.
+pub struct TbpPlayer {
+    bot: TbpBot,
+    pilot: Pilot,
+    ready: bool,
+    started: bool,
+    waiting: bool,
+    planned_for: Option<usize>,
+    expected: Option<Board>,
+    /// The last error the bot reported, if any.
+    pub error: Option<String>,
+}
+
+impl TbpPlayer {
+    pub fn new(bot: TbpBot) -> TbpPlayer {
+        TbpPlayer {
+            bot,
+            pilot: Pilot::default(),
+            ready: false,
+            started: false,
+            waiting: false,
+            planned_for: None,
+            expected: None,
+            error: None,
+        }
+    }
+
+    /// Whether the bot is still thinking about the current piece.
+    pub fn waiting(&self) -> bool {
+        self.waiting || !self.ready
+    }
+
+    fn handle(&mut self, message: BotMessage, state: &GameState) {
+        match message {
+            BotMessage::Info { .. } => self.send(FrontendMessage::Rules {}),
+            BotMessage::Ready => self.ready = true,
+            BotMessage::Error { reason } => self.error = Some(reason),
+            BotMessage::Suggestion { moves } if self.waiting => {
+                self.waiting = false;
+                self.play(&moves, state);
+            }
+            BotMessage::Suggestion { .. } => {}
+        }
+    }
+
+    /// Plays the first suggested move the active piece can reach, or drops
+    /// the piece and starts the bot over if none can.
+    fn play(&mut self, moves: &[Move], state: &GameState) {
+        let board = &state.board;
+        let reachable = placements::reachable(board);
+
+        for mv in moves {
+            let Some(mut cells) = mv.location.cells(board.matrix.len()) else {
+                continue;
+            };
+            cells.sort();
+
+            let placement = reachable.iter().find(|placement| {
+                let mut placed = placement.tetromino.cells();
+                placed.sort();
+                placed == cells
+            });
+
+            if let Some(placement) = placement {
+                let mut expected = board.clone();
+                expected.tetromino = placement.tetromino;
+                expected.merge();
+                expected.check_completed_rows();
+                expected.tetromino = Tetromino::null();
+
+                self.expected = Some(expected);
+                self.pilot
+                    .steer(Some(placement.tetromino), placement.path.clone());
+                self.send(FrontendMessage::Play { mv: mv.clone() });
+                return;
+            }
+        }
+
+        self.expected = None;
+        self.pilot.steer(None, vec![]);
+    }
+
+    /// Tells the bot about the new active piece: just the piece that showed
+    /// up in the preview if its board is still right, the whole game if not.
+    fn new_piece(&mut self, state: &GameState) {
+        let in_sync = self
+            .expected
+            .as_ref()
+            .is_some_and(|expected| expected.matrix == state.board.matrix);
+
+        let names = |tetromino: &Tetromino| tetromino.piece().map(|piece| piece.name.clone());
+
+        if self.started && in_sync {
+            if let Some(piece) = names(&state.next) {
+                self.send(FrontendMessage::NewPiece { piece });
+            }
+        } else {
+            let board = match board_rows(&state.board) {
+                Ok(board) => board,
+                Err(e) => {
+                    self.error = Some(e);
+                    return;
+                }
+            };
+
+            if self.started {
+                self.send(FrontendMessage::Stop);
+            }
+
+            let queue = [&state.board.tetromino, &state.next]
+                .into_iter()
+                .filter_map(names)
+                .collect();
+
+            self.send(FrontendMessage::Start {
+                hold: None,
+                queue,
+                combo: 0,
+                back_to_back: false,
+                board,
+            });
+            self.started = true;
+        }
+
+        self.expected = None;
+        self.send(FrontendMessage::Suggest);
+        self.waiting = true;
+    }
+
+    fn send(&mut self, message: FrontendMessage) {
+        if let Err(e) = self.bot.send(&message) {
+            self.error = Some(e.to_string());
+        }
+    }
+}
+
+impl Controller for TbpPlayer {
+    fn update(&mut self, delta: Duration, state: &GameState) -> Option<Movement> {
+        while let Some(message) = self.bot.try_recv() {
+            self.handle(message, state);
+        }
+
+        if !self.ready || state.board.tetromino.is_null() {
+            return None;
+        }
+
+        if self.planned_for != Some(state.count) {
+            self.planned_for = Some(state.count);
+            self.new_piece(state);
+        }
+
+        if self.waiting {
+            return None;
+        }
+
+        self.pilot.update(delta, state)
+    }
+}
@@ -1,97 +1,125 @@
-use crate::patterns::{self, Pattern};
-extern crate rand;
-use rand::Rng;
+use crate::patterns::Pattern;
+use crate::pieces::{self, Piece, PieceSet};
 
 #[derive(Clone, Copy, Debug)]
 pub struct Tetromino {
     pub i: isize,
     pub j: isize,
-    patterns: &'static [Pattern],
+    piece: Option<&'static Piece>,
     selected_pattern: usize,
 }
 
 const LEFT: isize = -1;
 const RIGHT: isize = 1;
 
-#[allow(non_camel_case_types)]
-pub enum Type {
-    I_BLOCK,
-    O_BLOCK,
-    L_BLOCK,
-    J_BLOCK,
-    T_BLOCK,
-    S_BLOCK,
-    Z_BLOCK,
-}
-
-impl Type {
-    fn random_type() -> Type {
-        let mut rng = rand::thread_rng();
-
-        match rng.gen_range(0..6) {
-            0 => Type::I_BLOCK,
-            1 => Type::O_BLOCK,
-            2 => Type::L_BLOCK,
-            3 => Type::J_BLOCK,
-            4 => Type::T_BLOCK,
-            5 => Type::S_BLOCK,
-            _ => Type::Z_BLOCK,
-        }
+impl Tetromino {
+    pub fn random() -> Tetromino {
+        Tetromino::random_from(pieces::default_set())
     }
-}
 
-fn type_to_patterns(t_type: Type) -> &'static [Pattern] {
-    match t_type {
-        Type::I_BLOCK => &patterns::I_BLOCK_PATTERNS,
-        Type::O_BLOCK => &patterns::O_BLOCK_PATTERNS,
-        Type::L_BLOCK => &patterns::L_BLOCK_PATTERNS,
-        Type::J_BLOCK => &patterns::J_BLOCK_PATTERNS,
-        Type::T_BLOCK => &patterns::T_BLOCK_PATTERNS,
-        Type::S_BLOCK => &patterns::S_BLOCK_PATTERNS,
-        Type::Z_BLOCK => &patterns::Z_BLOCK_PATTERNS,
-    }
-}
+    pub fn random_from(set: &'static PieceSet) -> Tetromino {
+        let piece = set.random();
 
-impl Tetromino {
-    pub fn random() -> Tetromino {
-        let t_type = Type::random_type();
+        Tetromino::new(piece, piece.spawn.0, piece.spawn.1)
+    }
 
+    pub fn new(piece: &'static Piece, i: isize, j: isize) -> Tetromino {
         Tetromino {
-            i: 2,
-            j: 2,
-            patterns: type_to_patterns(t_type),
+            i,
+            j,
+            piece: Some(piece),
             selected_pattern: 0,
         }
     }
 
-    pub fn new(t_type: Type, i: isize, j: isize) -> Tetromino {
-        let patterns = type_to_patterns(t_type);
+    /// A piece placed in one of its orientations, wrapping around if
+    /// `orientation` is past the last one.
+    pub fn with_orientation(
+        piece: &'static Piece,
+        orientation: usize,
+        i: isize,
+        j: isize,
+    ) -> Tetromino {
         Tetromino {
             i,
             j,
-            patterns,
-            selected_pattern: 0,
+            piece: Some(piece),
+            selected_pattern: orientation % piece.patterns.len(),
         }
     }
 
+    /// The piece in whichever orientation and position covers exactly
+    /// `cells` on the board, if any does.
+    pub fn placed(piece: &'static Piece, cells: &[(isize, isize)]) -> Option<Tetromino> {
+        let mut cells = cells.to_vec();
+        cells.sort();
+
+        let first = *cells.first()?;
+
+        piece
+            .patterns
+            .iter()
+            .enumerate()
+            .map(|(orientation, pattern)| {
+                let (i, j) = pattern.cells()[0];
+
+                Tetromino::with_orientation(
+                    piece,
+                    orientation,
+                    first.0 - i as isize,
+                    first.1 - j as isize,
+                )
+            })
+            .find(|tetromino| {
+                let mut placed = tetromino.cells();
+                placed.sort();
+
+                placed == cells
+            })
+    }
+
     pub fn null() -> Tetromino {
         Tetromino {
             i: -100,
             j: -100,
-            patterns: &[],
+            piece: None,
             selected_pattern: 0,
         }
     }
 
+    pub fn piece(&self) -> Option<&'static Piece> {
+        self.piece
+    }
+
+    pub fn is_null(&self) -> bool {
+        self.piece.is_none()
+    }
+
+    pub fn orientation(&self) -> usize {
+        self.selected_pattern
+    }
+
+    /// The same piece moved back to its spawn position and orientation.
+    pub fn at_spawn(&self) -> Tetromino {
+        match self.piece {
+            Some(piece) => Tetromino::new(piece, piece.spawn.0, piece.spawn.1),
+            None => *self,
+        }
+    }
+
     fn rotate(&mut self, direction: isize) {
+        let Some(piece) = self.piece else {
+            return;
+        };
+
         let mut next_pattern = self.selected_pattern as isize + direction;
 
-        if next_pattern >= self.patterns.len() as isize {
+        if next_pattern >= piece.patterns.len() as isize {
             next_pattern = 0;
         }
 
         if next_pattern < 0 {
-            next_pattern = self.patterns.len() as isize - 1;
+            next_pattern = piece.patterns.len() as isize - 1;
         }
 
         self.selected_pattern = next_pattern as usize;
@@ -102,10 +130,6 @@ impl Tetromino {
     }
 
     pub fn move_left(&mut self) {
-        if self.j == 0 {
-            return;
-        }
-
         self.j -= 1;
     }
 
@@ -122,6 +146,21 @@ impl Tetromino {
     }
 
     pub fn actual_rotation(&self) -> &'static Pattern {
-        &self.patterns[self.selected_pattern]
+        let piece = self.piece.expect("null tetromino has no rotation");
+
+        &piece.patterns[self.selected_pattern]
+    }
+
+    /// The board positions the piece covers, none for a null tetromino.
+    pub fn cells(&self) -> Vec<(isize, isize)> {
+        if self.is_null() {
+            return vec![];
+        }
+
+        self.actual_rotation()
+            .cells()
+            .iter()
+            .map(|&(i, j)| (self.i + i as isize, self.j + j as isize))
+            .collect()
     }
 }
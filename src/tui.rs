@@ -0,0 +1,488 @@
+use std::env;
+use std::io::{self, Stdout, Write};
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+use crossterm::cursor::{Hide, MoveTo, Show};
+use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use crossterm::style::{Color, Print, ResetColor, SetForegroundColor};
+use crossterm::terminal::{self, Clear, ClearType, EnterAlternateScreen, LeaveAlternateScreen};
+use crossterm::{execute, queue};
+
+use crate::board::{Board, Cell, Movement};
+use crate::bot::{Bot, Controller};
+use crate::engine::GameState;
+use crate::highscores::{format_duration, HighScores};
+use crate::i18n;
+use crate::mode::GameMode;
+use crate::pieces::{self, Piece, PieceSets};
+use crate::settings::{Language, Settings};
+use crate::tetromino::Tetromino;
+
+const FRAME: Duration = Duration::from_millis(16);
+const MAX_NAME_LENGTH: usize = 12;
+/// Where the side panel starts, in terminal columns.
+const PANEL: u16 = 34;
+/// Where the opponent's board starts in versus, in terminal columns.
+const OPPONENT: u16 = 58;
+
+enum Screen {
+    Menu,
+    Playing,
+    GameOver,
+}
+
+struct App {
+    screen: Screen,
+    selected: usize,
+    state: GameState,
+    bot: Option<Box<dyn Controller>>,
+    /// The bot's game and the bot playing it, in versus.
+    opponent: Option<(GameState, Bot)>,
+    high_scores: HighScores,
+    name: Option<String>,
+    previous_best: Option<Duration>,
+    message: String,
+    language: Language,
+}
+
+/// Where the assets folder is found, following the same rules as the Bevy
+/// frontend: next to the manifest when run through cargo, next to the
+/// executable otherwise.
+fn base_path() -> PathBuf {
+    if let Ok(dir) = env::var("CARGO_MANIFEST_DIR") {
+        return PathBuf::from(dir);
+    }
+
+    env::current_exe()
+        .ok()
+        .and_then(|exe| exe.parent().map(PathBuf::from))
+        .unwrap_or_default()
+}
+
+/// Runs the terminal frontend until the player quits.
+pub fn run() -> io::Result<()> {
+    let (_, errors) = PieceSets::load_dir(&base_path().join("assets/pieces"));
+
+    for e in errors {
+        eprintln!("Could not load piece set: {}", e);
+    }
+
+    let high_scores = HighScores::load().unwrap_or_else(|e| {
+        eprintln!("Could not load high scores: {}", e);
+        HighScores::default()
+    });
+
+    let settings = Settings::load().unwrap_or_else(|e| {
+        eprintln!("Could not load settings: {}", e);
+        Settings::default()
+    });
+
+    let mut app = App {
+        screen: Screen::Menu,
+        selected: 0,
+        state: GameState::default(),
+        bot: None,
+        opponent: None,
+        high_scores,
+        name: None,
+        previous_best: None,
+        message: String::new(),
+        language: settings.language,
+    };
+
+    let mut stdout = io::stdout();
+
+    terminal::enable_raw_mode()?;
+    execute!(stdout, EnterAlternateScreen, Hide, Clear(ClearType::All))?;
+
+    let result = app.run(&mut stdout);
+
+    execute!(stdout, ResetColor, Show, LeaveAlternateScreen)?;
+    terminal::disable_raw_mode()?;
+
+    result
+}
+
+impl App {
+    fn run(&mut self, stdout: &mut Stdout) -> io::Result<()> {
+        let mut last_frame = Instant::now();
+
+        loop {
+            let mut keys = vec![];
+
+            if event::poll(FRAME)? {
+                while event::poll(Duration::ZERO)? {
+                    if let Event::Key(key) = event::read()? {
+                        if key.kind != KeyEventKind::Release {
+                            keys.push(key);
+                        }
+                    }
+                }
+            }
+
+            let now = Instant::now();
+            let delta = now - last_frame;
+            last_frame = now;
+
+            let quit = match self.screen {
+                Screen::Menu => self.handle_menu(&keys),
+                Screen::Playing => {
+                    self.handle_game(&keys, delta);
+                    false
+                }
+                Screen::GameOver => {
+                    self.handle_game_over(&keys);
+                    false
+                }
+            };
+
+            if quit {
+                return Ok(());
+            }
+
+            self.draw(stdout)?;
+        }
+    }
+
+    fn change_screen(&mut self, screen: Screen) {
+        self.screen = screen;
+        self.message.clear();
+        let _ = execute!(io::stdout(), Clear(ClearType::All));
+    }
+
+    fn handle_menu(&mut self, keys: &[KeyEvent]) -> bool {
+        let modes = GameMode::all();
+
+        for key in keys {
+            match key.code {
+                KeyCode::Up => self.selected = (self.selected + modes.len() - 1) % modes.len(),
+                KeyCode::Down => self.selected = (self.selected + 1) % modes.len(),
+                KeyCode::Enter | KeyCode::Char(' ') => {
+                    self.state = GameState::new(modes[self.selected], 1, pieces::default_set());
+                    self.bot = None;
+                    self.opponent = None;
+                    self.change_screen(Screen::Playing);
+                    return false;
+                }
+                KeyCode::Char('a') => {
+                    let mode = GameMode::Marathon { endless: true };
+                    self.state = GameState::new(mode, 1, pieces::default_set());
+                    self.bot = Some(Box::new(Bot::default()));
+                    self.opponent = None;
+                    self.change_screen(Screen::Playing);
+                    return false;
+                }
+                KeyCode::Char('v') => {
+                    let opponent = GameState::new(GameMode::Versus, 1, pieces::default_set());
+                    self.state = GameState::new(GameMode::Versus, 1, pieces::default_set());
+                    self.bot = None;
+                    self.opponent = Some((opponent, Bot::default()));
+                    self.change_screen(Screen::Playing);
+                    return false;
+                }
+                KeyCode::Char('q') | KeyCode::Esc => return true,
+                KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => return true,
+                _ => {}
+            }
+        }
+
+        false
+    }
+
+    fn handle_game(&mut self, keys: &[KeyEvent], delta: Duration) {
+        let mut movement = None;
+
+        for key in keys {
+            movement = match key.code {
+                KeyCode::Left | KeyCode::Char('h') => Some(Movement::Left),
+                KeyCode::Right | KeyCode::Char('l') => Some(Movement::Right),
+                KeyCode::Down | KeyCode::Char('j') => Some(Movement::Down),
+                KeyCode::Up | KeyCode::Char('x') => Some(Movement::RotateRight),
+                KeyCode::Char('z') => Some(Movement::RotateLeft),
+                KeyCode::Char('q') | KeyCode::Esc => {
+                    self.change_screen(Screen::Menu);
+                    return;
+                }
+                _ => movement,
+            };
+        }
+
+        if let Some(bot) = self.bot.as_mut() {
+            movement = bot.update(delta, &self.state);
+        }
+
+        let step = self.state.update(delta, movement);
+
+        if step.over {
+            self.on_game_over();
+            return;
+        }
+
+        if let Some((opponent, bot)) = self.opponent.as_mut() {
+            opponent.incoming += step.attack;
+
+            let movement = bot.update(delta, opponent);
+            let step = opponent.update(delta, movement);
+
+            self.state.incoming += step.attack;
+
+            if step.over {
+                self.state.finished = true;
+                self.on_game_over();
+            }
+        }
+    }
+
+    fn on_game_over(&mut self) {
+        let state = &self.state;
+        let eligible = self.bot.is_none()
+            && state.keeps_records()
+            && (state.finished || !state.mode.ranks_by_time());
+
+        self.previous_best = self
+            .high_scores
+            .best(state.mode)
+            .map(|record| record.duration);
+        self.name = (eligible
+            && self
+                .high_scores
+                .qualifies(state.mode, &state.record(String::new())))
+        .then(String::new);
+
+        self.change_screen(Screen::GameOver);
+    }
+
+    fn handle_game_over(&mut self, keys: &[KeyEvent]) {
+        for key in keys {
+            let Some(name) = self.name.as_mut() else {
+                if matches!(key.code, KeyCode::Enter | KeyCode::Char(' ')) {
+                    self.change_screen(Screen::Menu);
+                }
+
+                continue;
+            };
+
+            match key.code {
+                KeyCode::Char(c) if !c.is_control() && name.chars().count() < MAX_NAME_LENGTH => {
+                    name.push(c);
+                }
+                KeyCode::Backspace => {
+                    name.pop();
+                }
+                KeyCode::Enter => {
+                    let name = name.trim();
+                    let name = if name.is_empty() { "???" } else { name };
+
+                    self.high_scores
+                        .insert(self.state.mode, self.state.record(name.into()));
+
+                    if let Err(e) = self.high_scores.save() {
+                        self.message = i18n::format(self.language, "scores.save_failed", &[&e]);
+                    }
+
+                    self.name = None;
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn draw(&self, stdout: &mut Stdout) -> io::Result<()> {
+        match self.screen {
+            Screen::Menu => self.draw_menu(stdout)?,
+            Screen::Playing => {
+                self.draw_game(stdout)?;
+
+                if let Some((opponent, _)) = &self.opponent {
+                    draw_board(stdout, &opponent.board, OPPONENT)?;
+                }
+            }
+            Screen::GameOver => {
+                self.draw_game(stdout)?;
+                self.draw_game_over(stdout)?;
+            }
+        }
+
+        stdout.flush()
+    }
+
+    fn draw_menu(&self, stdout: &mut Stdout) -> io::Result<()> {
+        queue!(
+            stdout,
+            MoveTo(2, 1),
+            Print(i18n::text(self.language, "menu.title"))
+        )?;
+
+        for (index, mode) in GameMode::all().iter().enumerate() {
+            let marker = if index == self.selected { ">" } else { " " };
+
+            queue!(
+                stdout,
+                MoveTo(2, 3 + index as u16),
+                Print(format!("{} {:<24}", marker, mode.name(self.language)))
+            )?;
+        }
+
+        let bottom = 4 + GameMode::all().len() as u16;
+
+        queue!(
+            stdout,
+            MoveTo(2, bottom),
+            Print(i18n::text(self.language, "tui.help"))
+        )
+    }
+
+    fn draw_game(&self, stdout: &mut Stdout) -> io::Result<()> {
+        draw_board(stdout, &self.state.board, 0)?;
+        self.draw_panel(stdout)
+    }
+
+    fn draw_panel(&self, stdout: &mut Stdout) -> io::Result<()> {
+        let state = &self.state;
+        let label = |id| i18n::text(self.language, id).trim();
+
+        let lines_label = match state.mode {
+            GameMode::Sprint { .. } | GameMode::Dig { .. } => "hud.left",
+            _ => "hud.lines",
+        };
+
+        let fields = [
+            (0, format!("{:<20}", state.mode.name(self.language))),
+            (2, format!("{:<10}", label("hud.next"))),
+            (9, format!("{:<6} {:<10}", label("hud.score"), state.score)),
+            (
+                10,
+                format!(
+                    "{:<6} {:<10}",
+                    label(lines_label),
+                    state.lines_remaining().unwrap_or(state.lines)
+                ),
+            ),
+            (
+                11,
+                format!(
+                    "{:<6} {:<10}",
+                    label("hud.time"),
+                    format_duration(state.time_remaining().unwrap_or(state.elapsed))
+                ),
+            ),
+            (12, format!("{:<6} {:<10}", label("hud.level"), state.level)),
+        ];
+
+        for (row, text) in fields {
+            queue!(stdout, MoveTo(PANEL, row), Print(text))?;
+        }
+
+        for row in 3..8 {
+            queue!(stdout, MoveTo(PANEL, row), Print(" ".repeat(10)))?;
+        }
+
+        draw_preview(stdout, &state.next)
+    }
+
+    fn draw_game_over(&self, stdout: &mut Stdout) -> io::Result<()> {
+        let mut lines: Vec<String> = self
+            .state
+            .summary(self.previous_best, self.language)
+            .lines()
+            .map(String::from)
+            .collect();
+
+        match &self.name {
+            Some(name) => {
+                lines.push(i18n::format(self.language, "scores.new_record", &[name]));
+                lines.push(i18n::text(self.language, "scores.confirm").into());
+            }
+            None => lines.push(i18n::text(self.language, "tui.back").into()),
+        }
+
+        if !self.message.is_empty() {
+            lines.push(self.message.clone());
+        }
+
+        for (n, line) in lines.iter().enumerate() {
+            queue!(
+                stdout,
+                MoveTo(PANEL, 14 + n as u16),
+                Print(format!("{:<50}", line))
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Draws `board` and its active piece with its left edge at column `left`.
+fn draw_board(stdout: &mut Stdout, board: &Board, left: u16) -> io::Result<()> {
+    let rows = board.matrix.len();
+    let active = board.tetromino.cells();
+    let active_piece = board.tetromino.piece();
+
+    for (i, row) in board.matrix.iter().enumerate() {
+        queue!(stdout, MoveTo(left, i as u16))?;
+
+        for (j, cell) in row.iter().enumerate() {
+            if active.contains(&(i as isize, j as isize)) {
+                if let Some(piece) = active_piece {
+                    queue!(stdout, SetForegroundColor(piece_colour(piece)), Print("██"))?;
+                    continue;
+                }
+            }
+
+            match cell {
+                Cell::Wall => {
+                    let last_row = i == rows - 1;
+                    let last_column = j == row.len() - 1;
+
+                    let wall = match (i, j) {
+                        (0, 0) => " ┌",
+                        (0, _) if last_column => "┐ ",
+                        (_, 0) if last_row => " └",
+                        _ if last_row && last_column => "┘ ",
+                        (0, _) => "──",
+                        _ if last_row => "──",
+                        (_, 0) => " │",
+                        _ => "│ ",
+                    };
+
+                    queue!(stdout, ResetColor, Print(wall))?;
+                }
+                Cell::Empty => queue!(stdout, ResetColor, Print("  "))?,
+                Cell::Garbage => queue!(stdout, SetForegroundColor(Color::DarkGrey), Print("██"))?,
+                Cell::Piece(piece) => {
+                    queue!(stdout, SetForegroundColor(piece_colour(piece)), Print("██"))?
+                }
+            }
+        }
+    }
+
+    queue!(stdout, ResetColor)
+}
+
+fn draw_preview(stdout: &mut Stdout, next: &Tetromino) -> io::Result<()> {
+    let Some(piece) = next.piece() else {
+        return Ok(());
+    };
+
+    for &(i, j) in next.actual_rotation().cells() {
+        queue!(
+            stdout,
+            MoveTo(PANEL + 2 * j as u16, 3 + i as u16),
+            SetForegroundColor(piece_colour(piece)),
+            Print("██")
+        )?;
+    }
+
+    queue!(stdout, ResetColor)
+}
+
+fn piece_colour(piece: &Piece) -> Color {
+    let (r, g, b) = piece.colour;
+
+    Color::Rgb {
+        r: (r * 255.0) as u8,
+        g: (g * 255.0) as u8,
+        b: (b * 255.0) as u8,
+    }
+}
@@ -0,0 +1,128 @@
+use crate::board::Cell;
+use crate::engine::GameState;
+use crate::pieces::Piece;
+use crate::tetromino::Tetromino;
+
+/// A block of a piece that is not on the board yet: the active piece, its
+/// ghost or the next piece.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Mino {
+    pub i: isize,
+    pub j: isize,
+    pub piece: &'static Piece,
+    pub ghost: bool,
+}
+
+/// A sprite that has to change to show the game as it is now.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Change {
+    /// The board cell in row `i` and column `j` now shows `cell`.
+    Cell { i: isize, j: isize, cell: Cell },
+    /// Mino sprite `slot` now shows `mino`, or nothing.
+    Mino { slot: usize, mino: Option<Mino> },
+}
+
+/// What a frontend is showing of a game, so it can keep a sprite for each
+/// cell of the board and each mino and only change the ones that differ,
+/// instead of drawing everything again on each move.
+#[derive(Debug, Clone, Default)]
+pub struct View {
+    cells: Vec<Vec<Cell>>,
+    minos: Vec<Option<Mino>>,
+    /// Whether the sprites show `cells` and `minos`. Until then every
+    /// sprite is changed.
+    drawn: bool,
+}
+
+impl View {
+    /// Forgets what is shown, so the next update changes every sprite, as
+    /// when they are replaced.
+    pub fn reset(&mut self) {
+        self.drawn = false;
+    }
+
+    /// The mino sprites needed so far. Slots are numbered from 0 to this.
+    pub fn slots(&self) -> usize {
+        self.minos.len()
+    }
+
+    /// The changes that show `state`, with the ghost piece if `ghost` is
+    /// set. Walls are never shown.
+    pub fn update(&mut self, state: &GameState, ghost: bool) -> Vec<Change> {
+        let mut changes = vec![];
+        let matrix = &state.board.matrix;
+
+        let same_size = self.cells.len() == matrix.len()
+            && self
+                .cells
+                .iter()
+                .zip(matrix)
+                .all(|(a, b)| a.len() == b.len());
+
+        if !same_size {
+            self.cells = matrix.clone();
+            self.drawn = false;
+        }
+
+        for (i, row) in matrix.iter().enumerate() {
+            for (j, &cell) in row.iter().enumerate() {
+                if cell == Cell::Wall || (self.drawn && self.cells[i][j] == cell) {
+                    continue;
+                }
+
+                self.cells[i][j] = cell;
+                changes.push(Change::Cell {
+                    i: i as isize,
+                    j: j as isize,
+                    cell,
+                });
+            }
+        }
+
+        let minos = minos(state, ghost);
+        let slots = minos.len().max(self.minos.len());
+        self.minos.resize(slots, None);
+
+        for (slot, shown) in self.minos.iter_mut().enumerate() {
+            let mino = minos.get(slot).copied();
+
+            if self.drawn && *shown == mino {
+                continue;
+            }
+
+            *shown = mino;
+            changes.push(Change::Mino { slot, mino });
+        }
+
+        self.drawn = true;
+
+        changes
+    }
+}
+
+/// The minos of `state` outside the board, the ghost first so each kind
+/// keeps to the same slots while the pieces keep their size.
+pub fn minos(state: &GameState, ghost: bool) -> Vec<Mino> {
+    let of = |tetromino: &Tetromino, ghost: bool| -> Vec<Mino> {
+        let Some(piece) = tetromino.piece() else {
+            return vec![];
+        };
+
+        tetromino
+            .cells()
+            .into_iter()
+            .map(|(i, j)| Mino { i, j, piece, ghost })
+            .collect()
+    };
+
+    let mut minos = vec![];
+
+    if ghost {
+        minos.extend(of(&state.board.ghost(), true));
+    }
+
+    minos.extend(of(&state.board.tetromino, false));
+    minos.extend(of(&state.next, false));
+
+    minos
+}
@@ -0,0 +1,114 @@
+use std::fs;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use rtetris::bot::Controller;
+use rtetris::engine::GameState;
+use rtetris::mode::GameMode;
+use rtetris::pieces;
+use rtetris::tbp::{self, FrontendMessage, TbpBot, TbpPlayer, BOARD_COLUMNS, BOARD_ROWS};
+
+/// A bot that is ready straight away and writes what it is sent to `path`.
+fn recording_bot(path: &str) -> TbpBot {
+    let script = r#"
+        echo '{"type":"info","name":"recorder","version":"1","author":"","features":[]}'
+        echo '{"type":"ready"}'
+        cat > "$0"
+    "#;
+
+    TbpBot::spawn("sh", &["-c".into(), script.into(), path.into()]).unwrap()
+}
+
+/// Runs `player` on `state` until it has sent a start message or reported
+/// an error, and returns the messages the bot got.
+fn first_messages(player: &mut TbpPlayer, state: &GameState, path: &str) -> Vec<FrontendMessage> {
+    let started = Instant::now();
+    let mut messages = vec![];
+
+    while started.elapsed() < Duration::from_secs(10) {
+        player.update(Duration::from_millis(16), state);
+
+        messages = fs::read_to_string(path)
+            .unwrap_or_default()
+            .lines()
+            .filter_map(|line| serde_json::from_str(line).ok())
+            .collect();
+
+        let started = messages
+            .iter()
+            .any(|message| matches!(message, FrontendMessage::Start { .. }));
+
+        if started || player.error.is_some() {
+            break;
+        }
+
+        thread::sleep(Duration::from_millis(5));
+    }
+
+    messages
+}
+
+#[test]
+fn start_message_has_standard_rows() {
+    let path = std::env::temp_dir().join(format!("rtetris-tbp-start-{}", std::process::id()));
+    let path = path.to_str().unwrap();
+    let mut player = TbpPlayer::new(recording_bot(path));
+
+    let messages = first_messages(&mut player, &tbp::marathon(), path);
+    let _ = fs::remove_file(path);
+
+    let board = messages
+        .iter()
+        .find_map(|message| match message {
+            FrontendMessage::Start { board, .. } => Some(board),
+            _ => None,
+        })
+        .expect("start message sent");
+
+    assert_eq!(board.len(), BOARD_ROWS);
+    assert!(board.iter().all(|row| row.len() == BOARD_COLUMNS));
+    assert_eq!(player.error, None);
+}
+
+#[test]
+fn wider_boards_are_rejected() {
+    let path = std::env::temp_dir().join(format!("rtetris-tbp-wide-{}", std::process::id()));
+    let path = path.to_str().unwrap();
+    let mut player = TbpPlayer::new(recording_bot(path));
+
+    let mode = GameMode::Marathon { endless: true };
+    let state = GameState::new(mode, 1, pieces::default_set());
+    let messages = first_messages(&mut player, &state, path);
+    let _ = fs::remove_file(path);
+
+    assert!(player.error.is_some());
+    assert!(!messages
+        .iter()
+        .any(|message| matches!(message, FrontendMessage::Start { .. })));
+}
+
+#[test]
+fn reference_bot_plays_over_tbp() {
+    let bot = TbpBot::spawn(env!("CARGO_BIN_EXE_rtetris-tbp-bot"), &[]).unwrap();
+    let mut player = TbpPlayer::new(bot);
+
+    let mut state = tbp::marathon();
+    let frame = Duration::from_millis(16);
+    let started = Instant::now();
+
+    while state.count < 30 && started.elapsed() < Duration::from_secs(60) {
+        let movement = player.update(frame, &state);
+
+        // Time stands still while the bot thinks.
+        if player.waiting() {
+            thread::sleep(Duration::from_millis(1));
+            continue;
+        }
+
+        assert!(!state.update(frame, movement).over, "bot topped out");
+    }
+
+    assert_eq!(player.error, None);
+    assert!(state.count >= 30, "only {} pieces placed", state.count);
+    assert!(state.lines > 0);
+}